@@ -0,0 +1,10 @@
+// Cynops の組み込み関数
+// この形のプロトタイプを宣言するとコンパイラが中身を生成する
+#ifndef CYNOPS_H
+#define CYNOPS_H
+
+void putchar(char);
+char getchar(void);
+void exit(void);
+
+#endif
@@ -0,0 +1,30 @@
+// 数と文字列の表示
+#ifndef CYNOPS_PRINT_H
+#define CYNOPS_PRINT_H
+
+#include <cynops.h>
+
+void print_int(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int(x / 10);
+    }
+    // キャストを適当に作っているので明示的に
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+// 文字列リテラルは \0 で終わらないので，呼び出し側で "...\0" と書く
+void printf(char (*s)[0]) {
+    int i = 0;
+    for (i = 0; (*s)[i] != '\0'; i += 1) {
+        putchar((*s)[i]);
+    }
+    return;
+}
+
+#endif
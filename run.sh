@@ -6,10 +6,5 @@ if [ -z "$1" ]; then
   exit 1
 fi
 
-# 出力ディレクトリを作成
-rm -rf ./out/
-mkdir -p ./out/
-
-cargo run "$1" codegen > ./out/result.txt
-gcc -DMASK=2 hydrogen.c -o brainfuck
-./brainfuck ./out/result.txt
+# 組み込みのインタプリタで実行する（gcc は不要）
+cargo run -q -- "$1" run
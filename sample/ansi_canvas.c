@@ -1,19 +1,4 @@
-void putchar(char);
-char getchar(void);
-
-void print_int(int x) {
-    if (x < 0) {
-        putchar('-');
-        x = -x;
-    }
-
-    if (x >= 10) {
-        print_int(x / 10);
-    }
-    // キャストを適当に作っているので明示的に
-    putchar((char)((int)'0' + (x % 10)));
-    return;
-}
+#include <print.h>
 
 int get_int(void) {
     int value = 0;
@@ -28,13 +13,6 @@ int get_int(void) {
     return value;
 }
 
-void printf(char (*s)[0]) {
-    int i = 0;
-    for (i = 0; (*s)[i] != '\0'; i += 1) {
-        putchar((*s)[i]);
-    }
-}
-
 typedef struct {
     int red;
     int green;
@@ -1,20 +1,4 @@
-void putchar(char);
-char getchar(void);
-void exit(void);
-
-void print_int(int x) {
-    if (x < 0) {
-        putchar('-');
-        x = -x;
-    }
-
-    if (x >= 10) {
-        print_int(x / 10);
-    }
-    // キャストを適当に作っているので明示的に
-    putchar((char)((int)'0' + (x % 10)));
-    return;
-}
+#include <print.h>
 
 void print_hex_digit(int digit) {
     if (digit < 10) {
@@ -61,7 +45,7 @@ int main(void) {
 
     int hash = (sum2 << 8) | sum1;
 
-    print_hex(hash & 65535);
+    print_hex(hash & 0xFFFF);
 
     return 0;
 }
@@ -1,25 +1,4 @@
-void putchar(char);
-char getchar(void);
-void exit(void);
-void printf(char (*s)[0]) {
-    int i = 0;
-    for (i = 0; (*s)[i] != '\0'; i += 1) {
-        putchar((*s)[i]);
-    }
-}
-void print_int(int x) {
-    if (x < 0) {
-        putchar('-');
-        x = -x;
-    }
-
-    if (x >= 10) {
-        print_int(x / 10);
-    }
-    putchar('0' + (char)(x % 10));
-
-    return;
-}
+#include <print.h>
 
 // ==start
 typedef struct {
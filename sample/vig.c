@@ -1,6 +1,4 @@
-void putchar(char);
-char getchar(void);
-void exit(void);
+#include <cynops.h>
 
 void main(void) {
     int key[256];
@@ -8,6 +8,7 @@ use super::{
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub enum DeclStmt {
     InitVec(Vec<Init>),
+    Static(Vec<Init>), // 静的記憶域 グローバル領域に置かれる
     Struct(Struct),
     Union(Union),
     Enum(Enum),
@@ -18,6 +19,10 @@ impl DeclStmt {
         DeclStmt::InitVec(vec)
     }
 
+    pub fn r#static(vec: Vec<Init>) -> Self {
+        DeclStmt::Static(vec)
+    }
+
     pub fn r#struct(strct: Struct) -> Self {
         DeclStmt::Struct(strct)
     }
@@ -86,11 +91,11 @@ impl Enum {
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct EnumMember {
     pub ident: Ident,
-    pub value: Option<usize>, // 明示的な値がある場合
+    pub value: Option<Box<Expr>>, // 明示的な値がある場合 定数式
 }
 
 impl EnumMember {
-    pub fn new(ident: Ident, value: Option<usize>) -> Self {
+    pub fn new(ident: Ident, value: Option<Box<Expr>>) -> Self {
         EnumMember { ident, value }
     }
 }
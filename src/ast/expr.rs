@@ -1,20 +1,22 @@
 use super::Ident;
 use crate::ast::Type;
 use crate::op::*;
+use crate::span::Span;
 use ordered_float::OrderedFloat;
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Postfix {
     pub expr: Box<Expr>,
     pub op: PostfixOp,
+    pub span: Option<Span>, // 演算子の位置
 }
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct PostfixChain {
-    pub base: Expr,                   // primary に相当する基の式
-    pub suffixes: Vec<PostfixSuffix>, // 後置操作の連続
+    pub base: Expr,                                   // primary に相当する基の式
+    pub suffixes: Vec<(PostfixSuffix, Option<Span>)>, // 後置操作の連続と演算子の位置
 }
 
 impl PostfixChain {
-    pub fn new(base: Expr, suffixes: Vec<PostfixSuffix>) -> Self {
+    pub fn new(base: Expr, suffixes: Vec<(PostfixSuffix, Option<Span>)>) -> Self {
         PostfixChain { base, suffixes }
     }
 }
@@ -40,6 +42,7 @@ impl PostfixSuffix {
 pub struct Unary {
     pub op: UnaryOp,
     pub expr: Box<Expr>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -47,6 +50,7 @@ pub struct Binary {
     pub op: BinaryOp,
     pub lhs: Box<Expr>,
     pub rhs: Box<Expr>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -54,6 +58,7 @@ pub struct Ternary {
     pub cond: Box<Expr>,
     pub then_branch: Box<Expr>,
     pub else_branch: Box<Expr>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -62,18 +67,21 @@ pub struct Assign {
     pub op: AssignOp,
     pub lhs: Box<Expr>,
     pub rhs: Box<Expr>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Call {
     pub func: Box<Expr>,
     pub args: Vec<Box<Expr>>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Subscript {
     pub name: Box<Expr>,
     pub index: Box<Expr>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -81,6 +89,7 @@ pub struct MemberAccess {
     pub base: Box<Expr>,      // 左側 (構造体 or ポインタ)
     pub member: Ident,        // アクセスされるメンバ名
     pub kind: MemberAccessOp, // . or ->
+    pub span: Option<Span>,   // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -102,6 +111,7 @@ impl Sizeof {
 pub struct Cast {
     pub r#type: Box<Type>,
     pub expr: Box<Expr>,
+    pub span: Option<Span>, // 演算子の位置
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -150,18 +160,28 @@ impl Expr {
     }
 
     pub fn unary(op: UnaryOp, expr: Box<Expr>) -> Box<Self> {
-        Box::new(Expr::Unary(Unary { op, expr }))
+        Box::new(Expr::Unary(Unary {
+            op,
+            expr,
+            span: None,
+        }))
     }
 
     pub fn postfix(op: PostfixOp, expr: Expr) -> Self {
         Expr::Postfix(Postfix {
             op,
             expr: Box::new(expr),
+            span: None,
         })
     }
 
     pub fn binary(op: BinaryOp, lhs: Box<Expr>, rhs: Box<Expr>) -> Box<Self> {
-        Box::new(Expr::Binary(Binary { op, lhs, rhs }))
+        Box::new(Expr::Binary(Binary {
+            op,
+            lhs,
+            rhs,
+            span: None,
+        }))
     }
 
     pub fn ternary(cond: Box<Expr>, then_branch: Expr, else_branch: Expr) -> Box<Self> {
@@ -169,23 +189,31 @@ impl Expr {
             cond,
             then_branch: Box::new(then_branch),
             else_branch: Box::new(else_branch),
+            span: None,
         }))
     }
 
     pub fn assign(op: AssignOp, lhs: Box<Expr>, rhs: Box<Expr>) -> Box<Self> {
-        Box::new(Expr::Assign(Assign { op, lhs, rhs }))
+        Box::new(Expr::Assign(Assign {
+            op,
+            lhs,
+            rhs,
+            span: None,
+        }))
     }
 
     pub fn call(func: Expr, args: Vec<Box<Expr>>) -> Self {
         Expr::Call(Call {
             func: Box::new(func),
             args,
+            span: None,
         })
     }
     pub fn subscript(name: Expr, index: Expr) -> Self {
         Expr::Subscript(Subscript {
             name: Box::new(name),
             index: Box::new(index),
+            span: None,
         })
     }
 
@@ -194,6 +222,7 @@ impl Expr {
             base: Box::new(base),
             member: member,
             kind,
+            span: None,
         })
     }
 
@@ -205,10 +234,50 @@ impl Expr {
         Box::new(Expr::Cast(Cast {
             r#type: Box::new(r#type),
             expr: Box::new(expr),
+            span: None,
         }))
     }
 
     pub fn comma(assigns: Vec<Expr>) -> Self {
         Expr::Comma(Comma { assigns })
     }
+
+    /// エラー表示に使う位置 演算子を持つ式はその演算子，識別子はその識別子
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Assign(this) => this.span,
+            Expr::Binary(this) => this.span,
+            Expr::Call(this) => this.span,
+            Expr::Ident(this) => this.span,
+            Expr::Postfix(this) => this.span,
+            Expr::Subscript(this) => this.span,
+            Expr::MemberAccess(this) => this.span,
+            Expr::Ternary(this) => this.span,
+            Expr::Unary(this) => this.span,
+            Expr::Cast(this) => this.span,
+            Expr::Sizeof(Sizeof::Expr(expr)) => expr.span(),
+            Expr::Comma(this) => this.assigns.first().and_then(|x| x.span()),
+            Expr::Sizeof(Sizeof::Type(_))
+            | Expr::Char(_)
+            | Expr::String(_)
+            | Expr::NumInt(_)
+            | Expr::NumFloat(_) => None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        match &mut self {
+            Expr::Assign(this) => this.span = span,
+            Expr::Binary(this) => this.span = span,
+            Expr::Call(this) => this.span = span,
+            Expr::Postfix(this) => this.span = span,
+            Expr::Subscript(this) => this.span = span,
+            Expr::MemberAccess(this) => this.span = span,
+            Expr::Ternary(this) => this.span = span,
+            Expr::Unary(this) => this.span = span,
+            Expr::Cast(this) => this.span = span,
+            _ => {}
+        }
+        self
+    }
 }
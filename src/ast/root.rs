@@ -8,6 +8,12 @@ use crate::ast::Ident;
 pub struct Program {
     pub items: Vec<TopLevel>,
 }
+impl Default for Program {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Program {
     pub fn new() -> Self {
         Self { items: vec![] }
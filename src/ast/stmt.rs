@@ -1,4 +1,5 @@
 use super::{DeclStmt, Expr, Ident};
+use crate::span::Span;
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Block {
     pub statements: Vec<Box<Stmt>>,
@@ -105,8 +106,8 @@ pub enum Stmt {
     Goto(Goto),
     Label(Label),
     Block(Block),
-    Break,
-    Continue,
+    Break(Option<Span>),    // break の位置
+    Continue(Option<Span>), // continue の位置
 }
 impl Stmt {
     pub fn expr(expr: Expr) -> Box<Self> {
@@ -179,10 +180,10 @@ impl Stmt {
     pub fn block(block: Block) -> Box<Self> {
         Box::new(Stmt::Block(block))
     }
-    pub fn r#break() -> Box<Self> {
-        Box::new(Stmt::Break)
+    pub fn r#break(span: Option<Span>) -> Box<Self> {
+        Box::new(Stmt::Break(span))
     }
-    pub fn r#continue() -> Box<Self> {
-        Box::new(Stmt::Continue)
+    pub fn r#continue(span: Option<Span>) -> Box<Self> {
+        Box::new(Stmt::Continue(span))
     }
 }
@@ -1,5 +1,6 @@
 use crate::{
     ast::{Enum, Expr, Union},
+    span::Span,
     visualize::OneLine,
 };
 
@@ -55,6 +56,7 @@ pub enum Type {
     Pointer(Box<Type>),
     Array(Array),
     Typedef(Ident),
+    Const(Box<Type>),
 }
 
 impl Type {
@@ -67,6 +69,9 @@ impl Type {
     pub fn r#enum(e: Enum) -> Self {
         Type::Enum(e)
     }
+    pub fn r#const(ty: Type) -> Self {
+        Type::Const(Box::new(ty))
+    }
     /// Type を Rust 風の表記に変換する
     pub fn to_rust_format(&self) -> String {
         match self {
@@ -121,6 +126,7 @@ impl Type {
                 )
             }
             Type::Typedef(t) => t.to_string(),
+            Type::Const(inner) => format!("const {}", inner.to_rust_format()),
         }
     }
 }
@@ -136,15 +142,38 @@ impl FunctionSig {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Debug, Clone)]
 
 pub struct Ident {
     pub name: String,
+    pub span: Option<Span>, // 比較やハッシュには使わない
 }
 impl Ident {
     /// 新しく Ident を作る（&str, String 両方対応）
     pub fn new(name: impl Into<String>) -> Self {
-        Self { name: name.into() }
+        Self {
+            name: name.into(),
+            span: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl PartialEq for Ident {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Ident {}
+
+impl std::hash::Hash for Ident {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
     }
 }
 
@@ -504,10 +504,10 @@ impl Visualize for Stmt {
             Stmt::Block(block) => {
                 block.visualize_with_context(indent, is_last, prefix);
             }
-            Stmt::Break => {
+            Stmt::Break(_) => {
                 print_branch("Break", "", indent, is_last, prefix);
             }
-            Stmt::Continue => {
+            Stmt::Continue(_) => {
                 print_branch("Continue", "", indent, is_last, prefix);
             }
         }
@@ -842,8 +842,12 @@ impl Visualize for DeclStmt {
 
     fn visualize_with_context(&self, indent: usize, is_last: bool, prefix: &[bool]) {
         match self {
-            DeclStmt::InitVec(inits) => {
-                print_branch("DeclStmt", "InitVec", indent, is_last, prefix);
+            DeclStmt::InitVec(inits) | DeclStmt::Static(inits) => {
+                let kind = match self {
+                    DeclStmt::Static(_) => "Static",
+                    _ => "InitVec",
+                };
+                print_branch("DeclStmt", kind, indent, is_last, prefix);
                 let next_prefix = extend_prefix(prefix, !is_last);
 
                 for (i, init) in inits.iter().enumerate() {
@@ -1012,7 +1016,7 @@ impl Visualize for EnumMember {
 
     fn visualize_with_context(&self, indent: usize, is_last: bool, prefix: &[bool]) {
         let variant_info = match &self.value {
-            Some(value) => format!("{} = {}", self.ident.name, value),
+            Some(value) => format!("{} = {}", self.ident.name, value.oneline()),
             None => self.ident.name.clone(),
         };
         print_branch("Variant", &variant_info, indent, is_last, prefix);
@@ -1,6 +1,7 @@
 use std::iter::repeat_n;
+use std::sync::OnceLock;
 
-use crate::codegen::stack::StackInst;
+use crate::codegen::stack::{StackInst, Word};
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum BF {
@@ -70,22 +71,118 @@ pub fn translate(stack: &[StackInst]) -> Vec<BF> {
     let mut stack = Vec::from(stack);
     StackInst::expand(&mut stack);
 
+    // Split into blocks, each starting at a label and ending with a jump
+    let mut blocks: Vec<(Word, Vec<StackInst>)> = vec![];
+    for inst in stack {
+        if let StackInst::Label(n) = inst {
+            blocks.push((n, vec![]));
+        }
+        match blocks.last_mut() {
+            Some((_, block)) => block.push(inst),
+            None => panic!("ラベルより前に命令があります: {:?}", inst),
+        }
+    }
+    // Label 0 ends the program, so nothing jumps to it
+    blocks.retain(|(n, _)| *n != 0);
+    for (n, block) in &blocks {
+        let last = block.iter().rev().find(|inst| {
+            !matches!(
+                inst,
+                StackInst::Nop | StackInst::Comment(_) | StackInst::Debug(_)
+            )
+        });
+        assert!(
+            matches!(last, Some(StackInst::Goto | StackInst::Branch(..))),
+            "ラベル {} のブロックが飛ばずに終わっています",
+            n
+        );
+    }
+    // Sorted by the reversed bits, the labels sharing their lowest bits are adjacent
+    blocks.sort_by_key(|(n, _)| n.reverse_bits());
+    if let Some(pair) = blocks.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        panic!("ラベル {} が重複しています", pair[0].0);
+    }
+
     let mut bf = vec![];
 
     // Allocate extra cell (in case stack is empty), then goto 1
-    bf.extend(BF::parse(">+[>"));
+    bf.extend(BF::parse(">+["));
+    dispatch(&mut blocks, 0, &mut bf);
+    // Every block ends just above the new label
+    bf.extend(BF::parse("<]"));
 
-    for inst in stack {
-        use BF::*;
+    bf
+}
 
-        // Profiling, to optimize fastbf
-        bf.push(Profile(inst.clone()));
-        emit_bf(inst, &mut bf);
-    }
+// Bit-tested dispatch on the label cell, lowest bit first
+// The bits below `bit` have already been subtracted from the label, so
+// label * 2^(15 - bit) wraps around to 2^15 if the bit is set and to 0 if not
+// Entered on the label with two empty cells above it; a block always runs
+// and the code ends just above the new label, where every cell is empty,
+// so the rest of the tree is skipped as whole brackets
+fn dispatch(blocks: &mut [(Word, Vec<StackInst>)], bit: u32, bf: &mut Vec<BF>) {
+    use BF::*;
 
-    bf.extend(BF::parse("<]"));
+    let split = blocks.partition_point(|(n, _)| n & (1 << bit) == 0);
+    match blocks {
+        [] => {}
+        [(n, block)] => {
+            // Clear the rest of the label, then enter the block on the stack top
+            add_constant(((*n as u32 >> bit << bit) as Word).wrapping_neg(), bf);
+            bf.push(Left);
+            for inst in std::mem::take(block) {
+                // Profiling, to optimize fastbf
+                bf.push(Profile(inst.clone()));
+                emit_bf(inst, bf);
+            }
+        }
+        // Every label left has the same bit
+        _ if split == blocks.len() => dispatch(blocks, bit + 1, bf),
+        _ if split == 0 => {
+            add_constant(Word::wrapping_neg(1 << bit), bf);
+            dispatch(blocks, bit + 1, bf);
+        }
+        _ => {
+            let (zeros, ones) = blocks.split_at_mut(split);
 
-    bf
+            // Copy the label above, multiplied in steps of at most 2^5
+            let mut shift = 15 - bit;
+            let factor = |shift: &mut u32| {
+                let step = (*shift).min(5);
+                *shift -= step;
+                repeat_n(Inc, 1 << step)
+            };
+            bf.extend(BF::parse("[->"));
+            bf.extend(factor(&mut shift));
+            bf.extend(BF::parse(">+<<]>>[-<<+>>]<<"));
+            let mut above = true;
+            while shift > 0 {
+                let (to, back) = if above {
+                    (">[->", "<]<")
+                } else {
+                    (">>[-<", ">]<<")
+                };
+                bf.extend(BF::parse(to));
+                bf.extend(factor(&mut shift));
+                bf.extend(BF::parse(back));
+                above = !above;
+            }
+            if !above {
+                bf.extend(BF::parse(">>[-<+>]<<"));
+            }
+
+            // If the bit is set, clear the else flag and subtract the bit
+            bf.extend(BF::parse(">>+<[[-]>-<<"));
+            add_constant(Word::wrapping_neg(1 << bit), bf);
+            dispatch(ones, bit + 1, bf);
+            bf.extend(BF::parse(">]<"));
+
+            // Else
+            bf.extend(BF::parse(">>[-<<"));
+            dispatch(zeros, bit + 1, bf);
+            bf.extend(BF::parse(">>]<<"));
+        }
+    }
 }
 
 pub fn emit_bf(inst: StackInst, bf: &mut Vec<BF>) {
@@ -94,7 +191,7 @@ pub fn emit_bf(inst: StackInst, bf: &mut Vec<BF>) {
     match inst {
         Push(b) => {
             bf.push(Right);
-            bf.extend(repeat_n(Inc, b as _));
+            add_constant(b, bf);
         }
         StackInst::Input => {
             bf.push(Right);
@@ -131,20 +228,6 @@ pub fn emit_bf(inst: StackInst, bf: &mut Vec<BF>) {
                 bf.extend(BF::parse("[-]<"));
             }
         },
-        Label(n) if n != 0 => {
-            bf.extend(BF::parse("<[->+>+<<]>>[-<<+>>]<")); // Move to, and then copy, label
-            bf.extend(repeat_n(Dec, n as _)); // Check equality
-            bf.extend(BF::parse(
-                "
-                >+<      // Push 1
-                [[-]>-<] // If unequal then remove 1
-                >[-<+>]< // Move 1 if (label == n) else leave 0
-                ",
-            ));
-            bf.extend(BF::parse("[-<[-]<"));
-            // Enter block if label at head
-            // Then, discard equality check and label, and point to stack
-        }
         Neq => bf.extend(BF::parse("[-<->]<")), // Check equality
         LNot => bf.extend(BF::parse(
             "
@@ -169,6 +252,36 @@ pub fn emit_bf(inst: StackInst, bf: &mut Vec<BF>) {
             >>[-<<+>>]<<            // Push return value
             ",
         )),
+        // Memory layout: a b
+        // Return value: 1 iff a >= b as signed words
+        // x is negative iff x >= !x unsigned, which only races |x| steps,
+        // so the work grows with the magnitudes instead of the biased values
+        SGrEq => {
+            let negative = |bf: &mut Vec<BF>| {
+                emit_bf(Copy, bf);
+                emit_bf(Not, bf);
+                emit_bf(GrEq, bf);
+            };
+            emit_pick(2, bf);
+            negative(bf); // a b na
+            emit_pick(2, bf);
+            negative(bf); // a b na nb
+            emit_bf(Sub, bf);
+            emit_pick(3, bf);
+            emit_pick(3, bf);
+            emit_bf(Sub, bf);
+            negative(bf); // a b na-nb nd
+            emit_bf(Add, bf);
+            // a < b iff na - nb + nd + 1 >= 2 (the sum is 0 to 3)
+            bf.extend(BF::parse(
+                "
+                +
+                <[-]<[-]+>>     // return value = true
+                [-[[-]<<->>]]   // Clear it if the sum is at least 2
+                <<
+                ",
+            ));
+        }
         LAnd => bf.extend(BF::parse(
             "
             >++<            // Place 2
@@ -284,20 +397,252 @@ pub fn emit_bf(inst: StackInst, bf: &mut Vec<BF>) {
         )),
         Branch(t, f) => {
             bf.push(Right);
-            bf.extend(repeat_n(Inc, f as _));
+            add_constant(f, bf);
             bf.push(Left);
             bf.extend(BF::parse("[[-]>"));
-            if t >= f {
-                bf.extend(repeat_n(Inc, t.wrapping_sub(f) as _));
-            } else {
-                bf.extend(repeat_n(Dec, f.wrapping_sub(t) as _));
-            }
+            add_constant(t.wrapping_sub(f), bf);
             bf.extend(BF::parse("<]>[-<+>]<"));
-            bf.extend(BF::parse(">]"));
+            emit_bf(Goto, bf);
         }
-        Goto => bf.extend(BF::parse(">]")),
+        // Leave the block just above the new label, on an empty cell
+        Goto => bf.push(Right),
         PutChar => bf.extend(BF::parse(".[-]<")),
-        Label(0) | Nop | Debug(_) | Comment(_) => {}
+        Label(_) | Nop | Debug(_) | Comment(_) => {}
         i => todo!("{:?}", i),
     }
 }
+
+// 上から depth 番目の値を複製して積む depth が1なら Copy と同じ
+fn emit_pick(depth: usize, bf: &mut Vec<BF>) {
+    let back = "<".repeat(depth - 1);
+    let to = ">".repeat(depth);
+    let down = "<".repeat(depth + 1);
+    let up = ">".repeat(depth + 1);
+    bf.extend(BF::parse(&format!(
+        "{back}[-{to}+>+{down}]{up}[-{down}+{up}]<"
+    )));
+}
+
+/// 定数の作り方 step が0なら + を並べるだけ
+/// そうでなければ右のセルに count を作って [<(step 個の +)>-]< で掛け，残りを足す
+#[derive(Clone, Copy)]
+struct Plan {
+    cost: usize,
+    count: Word,
+    step: Word,
+}
+
+const HALF: Word = Word::MAX / 2 + 1;
+
+// 0から HALF までの一番短い作り方 最初に使うときにまとめて求める
+fn plans() -> &'static [Plan] {
+    static PLANS: OnceLock<Vec<Plan>> = OnceLock::new();
+    PLANS.get_or_init(|| {
+        let mut plans: Vec<Plan> = Vec::with_capacity(HALF as usize + 1);
+        for n in 0..=HALF as usize {
+            let mut best = Plan {
+                cost: n,
+                count: 0,
+                step: 0,
+            };
+            // step と count を入れ替えた形は count のほうで見るので step <= count だけ調べる
+            for step in (2..).take_while(|step| step * step <= n + step) {
+                for count in [n / step, n / step + 1] {
+                    if count <= 1 || count >= n {
+                        continue;
+                    }
+                    let product = count * step;
+                    // > count [< step >-] < 残り
+                    let cost = plans[count].cost + step + product.abs_diff(n) + 7;
+                    if cost < best.cost {
+                        best = Plan {
+                            cost,
+                            count: count as Word,
+                            step: step as Word,
+                        };
+                    }
+                }
+            }
+            plans.push(best);
+        }
+        plans
+    })
+}
+
+/// add_constant が出す命令の数
+pub fn constant_cost(n: Word) -> usize {
+    plans()[n.min(n.wrapping_neg()) as usize].cost
+}
+
+/// 今のセルに n を足す 右側のセルが0であることを使う
+fn add_constant(n: Word, bf: &mut Vec<BF>) {
+    if n <= HALF {
+        emit_plan(n, BF::Inc, BF::Dec, bf);
+    } else {
+        emit_plan(n.wrapping_neg(), BF::Dec, BF::Inc, bf);
+    }
+}
+
+// up を n 回した結果になる 右のセルは数えるのに使うので必ず + で作る
+fn emit_plan(n: Word, up: BF, down: BF, bf: &mut Vec<BF>) {
+    use BF::*;
+    let plan = plans()[n as usize];
+    if plan.step == 0 {
+        bf.extend(repeat_n(up, n as _));
+        return;
+    }
+
+    bf.push(Right);
+    emit_plan(plan.count, Inc, Dec, bf);
+    bf.extend([LBrac, Left]);
+    bf.extend(repeat_n(up.clone(), plan.step as _));
+    bf.extend([Right, Dec, RBrac, Left]);
+
+    let product = plan.count * plan.step;
+    if n >= product {
+        bf.extend(repeat_n(up, (n - product) as _));
+    } else {
+        bf.extend(repeat_n(down, (product - n) as _));
+    }
+}
+
+#[test]
+fn test_add_constant() {
+    // 16bit セルのテープで実行してテープを返す
+    fn tape(code: &[BF]) -> Vec<Word> {
+        let mut tape = vec![0 as Word; 8];
+        let (mut ptr, mut ip) = (0, 0);
+        while ip < code.len() {
+            match code[ip] {
+                BF::Left => ptr -= 1,
+                BF::Right => ptr += 1,
+                BF::Inc => tape[ptr] = tape[ptr].wrapping_add(1),
+                BF::Dec => tape[ptr] = tape[ptr].wrapping_sub(1),
+                BF::LBrac if tape[ptr] == 0 => {
+                    let mut depth = 0;
+                    while !(code[ip] == BF::RBrac && depth == 1) {
+                        match code[ip] {
+                            BF::LBrac => depth += 1,
+                            BF::RBrac => depth -= 1,
+                            _ => {}
+                        }
+                        ip += 1;
+                    }
+                }
+                BF::RBrac if tape[ptr] != 0 => {
+                    let mut depth = 0;
+                    while !(code[ip] == BF::LBrac && depth == 1) {
+                        match code[ip] {
+                            BF::RBrac => depth += 1,
+                            BF::LBrac => depth -= 1,
+                            _ => {}
+                        }
+                        ip -= 1;
+                    }
+                }
+                _ => {}
+            }
+            ip += 1;
+        }
+        assert_eq!(ptr, 0);
+        tape
+    }
+
+    for n in (0..=Word::MAX)
+        .step_by(97)
+        .chain([30, 1000, 3000, 32768, 65535])
+    {
+        let mut code = vec![];
+        add_constant(n, &mut code);
+        let mut expected = vec![0; 8];
+        expected[0] = n;
+        assert_eq!(tape(&code), expected, "{}", n);
+        // + を並べるよりは短い
+        assert!(code.len() <= n.min(n.wrapping_neg()) as usize, "{}", n);
+        assert_eq!(code.len(), constant_cost(n), "{}", n);
+    }
+    let mut code = vec![];
+    add_constant(3000, &mut code);
+    assert!(code.len() < 80);
+}
+
+#[test]
+fn test_dispatch() {
+    use StackInst::*;
+
+    let run = |code: &[StackInst]| {
+        let mut output = Vec::new();
+        let config = super::RunConfig {
+            step_limit: Some(1_000_000),
+            ..Default::default()
+        };
+        super::run(&translate(code), &config, &mut &b""[..], &mut output).unwrap();
+        output
+    };
+
+    // 後ろのラベルにも前のラベルにも飛べる 番号は飛び飛びでもよい
+    let code = [
+        Label(1),
+        Push(b'a' as Word),
+        Push(7),
+        Goto,
+        Label(3),
+        Push(b'c' as Word),
+        PutChar,
+        Exit,
+        Label(7),
+        Copy,
+        PutChar,
+        Push(1),
+        Add,
+        Copy,
+        Push(b'c' as Word),
+        Eq,
+        Branch(3, 7),
+    ];
+    assert_eq!(run(&code), b"abc");
+
+    // 大きい番号も混ざった深い木でも，並び順によらずすべてのラベルに届く
+    let labels: Vec<Word> = (1..=40).map(|i| (i * 997 % 65521) as Word).collect();
+    let mut code = vec![Label(1), Push(labels[0]), Goto];
+    for (i, pair) in labels.windows(2).enumerate().rev() {
+        code.extend([
+            Label(pair[0]),
+            Push(b'a' as Word + i as Word % 26),
+            PutChar,
+            Push(pair[1]),
+            Goto,
+        ]);
+    }
+    code.extend([Label(labels[39]), Exit]);
+    let expected: Vec<u8> = (0..39).map(|i| b'a' + i % 26).collect();
+    assert_eq!(run(&code), expected);
+}
+
+#[test]
+fn test_signed_compare() {
+    use StackInst::*;
+
+    let values: [Word; 8] = [0, 1, 2, 100, 32767, 32768, 65436, 65535];
+    let mut code = vec![Label(1), Push(b'x' as Word)];
+    let mut expected = vec![];
+    for &a in &values {
+        for &b in &values {
+            for (op, result) in [
+                (SLt, (a as i16) < (b as i16)),
+                (SGrEq, a as i16 >= b as i16),
+            ] {
+                code.extend([Push(a), Push(b), op, Push(b'0' as Word), Add, PutChar]);
+                expected.push(b'0' + result as u8);
+            }
+        }
+    }
+    // 下の値を壊していない
+    code.extend([PutChar, Exit]);
+    expected.push(b'x');
+
+    let mut output = Vec::new();
+    let config = super::RunConfig::default();
+    super::run(&translate(&code), &config, &mut &b""[..], &mut output).unwrap();
+    assert_eq!(output, expected);
+}
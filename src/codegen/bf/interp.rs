@@ -0,0 +1,341 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use super::BF;
+use crate::codegen::stack::Word;
+
+/// 入力が尽きたときにセルをどうするか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofMode {
+    /// セルを変更しない（hydrogen.c と同じ）
+    #[default]
+    Unchanged,
+    Zero,
+    /// 全ビット1（-1）
+    Max,
+}
+
+impl FromStr for EofMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unchanged" => Ok(EofMode::Unchanged),
+            "zero" | "0" => Ok(EofMode::Zero),
+            "max" | "-1" => Ok(EofMode::Max),
+            _ => Err(format!("unknown EOF mode '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    pub tape_size: usize,
+    pub eof: EofMode,
+    /// 実行する命令数の上限 まとめた命令は1つと数える
+    pub step_limit: Option<u64>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            tape_size: 100_000,
+            eof: EofMode::Unchanged,
+            step_limit: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    UnmatchedOpen(usize),
+    UnmatchedClose(usize),
+    OutOfTape { cell: isize, tape_size: usize },
+    StepLimit(u64),
+    Io(io::Error),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::UnmatchedOpen(at) => write!(f, "Unmatched '[' at instruction {}", at),
+            RunError::UnmatchedClose(at) => write!(f, "Unmatched ']' at instruction {}", at),
+            RunError::OutOfTape { cell, tape_size } => write!(
+                f,
+                "Tape pointer moved to cell {}, outside the tape of {} cells",
+                cell, tape_size
+            ),
+            RunError::StepLimit(limit) => write!(f, "Step limit of {} exceeded", limit),
+            RunError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        RunError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Op {
+    Add(Word),
+    Move(isize),
+    Clear,
+    /// `[->+>++<<]` の形 原点の値を倍してそれぞれに足し，原点を0にする
+    MulAdd(Box<[(isize, Word)]>),
+    /// `[>]` の形 0のセルまで進む
+    Scan(isize),
+    Output,
+    Input,
+    /// 対応する Close の位置
+    Open(usize),
+    /// 対応する Open の位置
+    Close(usize),
+}
+
+/// 実行用にまとめ直したBFプログラム セル幅は hydrogen.c の -DMASK=2 と同じ16bit
+pub struct Executable {
+    ops: Vec<Op>,
+}
+
+impl Executable {
+    pub fn compile(code: &[BF]) -> Result<Self, RunError> {
+        let mut ops = Vec::new();
+        let mut opens = Vec::new();
+
+        for (i, inst) in code.iter().enumerate() {
+            match inst {
+                BF::Profile(_) => {}
+                BF::Inc => push_add(&mut ops, 1),
+                BF::Dec => push_add(&mut ops, Word::MAX),
+                BF::Right => push_move(&mut ops, 1),
+                BF::Left => push_move(&mut ops, -1),
+                BF::Output => ops.push(Op::Output),
+                BF::Input => ops.push(Op::Input),
+                BF::LBrac => {
+                    opens.push((ops.len(), i));
+                    ops.push(Op::Open(0));
+                }
+                BF::RBrac => {
+                    let (start, _) = opens.pop().ok_or(RunError::UnmatchedClose(i))?;
+                    if let Some(op) = simple_loop(&ops[start + 1..]) {
+                        ops.truncate(start);
+                        ops.push(op);
+                    } else {
+                        let end = ops.len();
+                        ops[start] = Op::Open(end);
+                        ops.push(Op::Close(start));
+                    }
+                }
+            }
+        }
+
+        match opens.first() {
+            Some(&(_, i)) => Err(RunError::UnmatchedOpen(i)),
+            None => Ok(Self { ops }),
+        }
+    }
+
+    pub fn run(
+        &self,
+        config: &RunConfig,
+        input: &mut impl Read,
+        output: &mut impl Write,
+    ) -> Result<(), RunError> {
+        let mut tape: Vec<Word> = vec![0; config.tape_size.max(1)];
+        let limit = config.step_limit.unwrap_or(u64::MAX);
+        let mut steps = 0u64;
+        let mut ptr = 0usize;
+        let mut ip = 0usize;
+
+        while let Some(op) = self.ops.get(ip) {
+            steps += 1;
+            if steps > limit {
+                return Err(RunError::StepLimit(limit));
+            }
+
+            match op {
+                Op::Add(n) => tape[ptr] = tape[ptr].wrapping_add(*n),
+                Op::Move(n) => ptr = shift(ptr, *n, tape.len())?,
+                Op::Clear => tape[ptr] = 0,
+                Op::MulAdd(targets) => {
+                    let value = tape[ptr];
+                    if value != 0 {
+                        for &(offset, factor) in targets.iter() {
+                            let at = shift(ptr, offset, tape.len())?;
+                            tape[at] = tape[at].wrapping_add(value.wrapping_mul(factor));
+                        }
+                        tape[ptr] = 0;
+                    }
+                }
+                Op::Scan(n) => {
+                    while tape[ptr] != 0 {
+                        steps += 1;
+                        if steps > limit {
+                            return Err(RunError::StepLimit(limit));
+                        }
+                        ptr = shift(ptr, *n, tape.len())?;
+                    }
+                }
+                Op::Output => output.write_all(&[tape[ptr] as u8])?,
+                Op::Input => {
+                    // 対話的なプログラムのためにプロンプトを先に出しておく
+                    output.flush()?;
+                    let mut byte = [0u8];
+                    match input.read_exact(&mut byte) {
+                        Ok(()) => tape[ptr] = byte[0] as Word,
+                        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => match config.eof {
+                            EofMode::Unchanged => {}
+                            EofMode::Zero => tape[ptr] = 0,
+                            EofMode::Max => tape[ptr] = Word::MAX,
+                        },
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Op::Open(end) => {
+                    if tape[ptr] == 0 {
+                        ip = *end;
+                    }
+                }
+                Op::Close(start) => {
+                    if tape[ptr] != 0 {
+                        ip = *start;
+                    }
+                }
+            }
+            ip += 1;
+        }
+
+        output.flush()?;
+        Ok(())
+    }
+}
+
+pub fn run(
+    code: &[BF],
+    config: &RunConfig,
+    input: &mut impl Read,
+    output: &mut impl Write,
+) -> Result<(), RunError> {
+    Executable::compile(code)?.run(config, input, output)
+}
+
+fn push_add(ops: &mut Vec<Op>, n: Word) {
+    match ops.last_mut() {
+        Some(Op::Add(m)) => {
+            *m = m.wrapping_add(n);
+            if *m == 0 {
+                ops.pop();
+            }
+        }
+        _ => ops.push(Op::Add(n)),
+    }
+}
+
+fn push_move(ops: &mut Vec<Op>, n: isize) {
+    match ops.last_mut() {
+        Some(Op::Move(m)) => {
+            *m += n;
+            if *m == 0 {
+                ops.pop();
+            }
+        }
+        _ => ops.push(Op::Move(n)),
+    }
+}
+
+fn shift(ptr: usize, n: isize, tape_size: usize) -> Result<usize, RunError> {
+    ptr.checked_add_signed(n)
+        .filter(|&p| p < tape_size)
+        .ok_or(RunError::OutOfTape {
+            cell: ptr as isize + n,
+            tape_size,
+        })
+}
+
+/// 内側に分岐も入出力もないループを1命令にする
+fn simple_loop(body: &[Op]) -> Option<Op> {
+    if let [Op::Move(n)] = body {
+        return Some(Op::Scan(*n));
+    }
+    if let [Op::Add(1)] = body {
+        return Some(Op::Clear);
+    }
+
+    let mut offset = 0isize;
+    let mut changes: Vec<(isize, Word)> = Vec::new();
+    for op in body {
+        match op {
+            Op::Move(n) => offset += n,
+            Op::Add(n) => match changes.iter_mut().find(|(o, _)| *o == offset) {
+                Some((_, m)) => *m = m.wrapping_add(*n),
+                None => changes.push((offset, *n)),
+            },
+            _ => return None,
+        }
+    }
+
+    // 原点が1ずつ減るなら，ちょうど原点の値の回数だけ回る
+    let origin = changes.iter().position(|(o, _)| *o == 0)?;
+    if offset != 0 || changes[origin].1 != Word::MAX {
+        return None;
+    }
+    changes.remove(origin);
+    changes.retain(|(_, n)| *n != 0);
+
+    if changes.is_empty() {
+        Some(Op::Clear)
+    } else {
+        Some(Op::MulAdd(changes.into_boxed_slice()))
+    }
+}
+
+#[test]
+fn test_interpreter() {
+    fn exec(code: &str, config: &RunConfig, input: &[u8]) -> Result<Vec<u8>, RunError> {
+        let mut output = Vec::new();
+        run(&BF::parse(code), config, &mut &input[..], &mut output)?;
+        Ok(output)
+    }
+    let config = RunConfig::default();
+
+    // 8 * 8 + 1 = 'A'，[-] と [->+<] をまとめても結果は同じ
+    let out = exec("++++++++[->++++++++<]>+.[-]+++[>+>+<<-]>>.", &config, b"").unwrap();
+    assert_eq!(out, b"A\x03");
+
+    // 16bit セルなので 256 で桁あふれしない
+    let code = format!("{}[[-]>+<]>.", "+".repeat(256));
+    let out = exec(&code, &config, b"").unwrap();
+    assert_eq!(out, [1]);
+
+    let out = exec(",.,.", &config, b"x").unwrap();
+    assert_eq!(out, b"xx");
+    let eof = RunConfig {
+        eof: EofMode::Zero,
+        ..RunConfig::default()
+    };
+    assert_eq!(exec(",.,.", &eof, b"x").unwrap(), b"x\0");
+
+    let limited = RunConfig {
+        step_limit: Some(1000),
+        ..RunConfig::default()
+    };
+    assert!(matches!(
+        exec("+[]", &limited, b""),
+        Err(RunError::StepLimit(1000))
+    ));
+    assert!(matches!(
+        exec("<", &config, b""),
+        Err(RunError::OutOfTape { cell: -1, .. })
+    ));
+    assert!(matches!(
+        exec("+[>[<]", &config, b""),
+        Err(RunError::UnmatchedOpen(1))
+    ));
+    assert!(matches!(
+        exec("+]", &config, b""),
+        Err(RunError::UnmatchedClose(1))
+    ));
+}
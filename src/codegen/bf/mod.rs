@@ -1,3 +1,7 @@
 mod inst;
+mod interp;
+mod opt;
 
 pub use inst::*;
+pub use interp::*;
+pub use opt::*;
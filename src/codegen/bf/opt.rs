@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use super::BF;
+use crate::codegen::stack::Word;
+
+/// translate の出力から打ち消し合う命令や意味のない [-] を取り除く
+/// Profile は数も順序もそのまま残す
+/// ただし前後の命令をまたいで打ち消すので，Profile の位置のポインタは元と違うことがある
+pub fn optimize(code: Vec<BF>) -> Vec<BF> {
+    // 出力は読んだ分より長くならないので，同じ Vec の前から詰めて書く
+    let mut opt = Optimizer {
+        code,
+        len: 0,
+        profiles: Vec::new(),
+        pos: 0,
+        known: BTreeMap::new(),
+        // 実行開始時のテープはすべて0
+        rest_zero: true,
+    };
+
+    let mut i = 0;
+    while i < opt.code.len() {
+        match opt.code[i] {
+            BF::LBrac if matches!(opt.code[i + 1..], [BF::Inc | BF::Dec, BF::RBrac, ..]) => {
+                opt.clear();
+                i += 2;
+            }
+            // 0のセルでは一度も回らない
+            BF::LBrac
+                if opt.value() == Some(0)
+                    && let Some(end) = find_end(&opt.code[i..]) =>
+            {
+                for j in i..i + end {
+                    if matches!(opt.code[j], BF::Profile(_)) {
+                        opt.profile(j);
+                    }
+                }
+                i += end;
+            }
+            BF::LBrac => {
+                // 何回目のループかわからないので何も仮定しない
+                opt.push(BF::LBrac);
+                opt.forget();
+            }
+            BF::RBrac => {
+                opt.push(BF::RBrac);
+                opt.forget();
+                opt.set_value(Some(0));
+            }
+            BF::Inc | BF::Dec => {
+                let (n, len) = run(&opt.code[i..], BF::Inc, BF::Dec);
+                opt.add(n);
+                i += len - 1;
+            }
+            BF::Right | BF::Left => {
+                let (n, len) = run(&opt.code[i..], BF::Right, BF::Left);
+                opt.shift(n);
+                i += len - 1;
+            }
+            BF::Input => {
+                opt.push(BF::Input);
+                opt.set_value(None);
+            }
+            BF::Output => opt.push(BF::Output),
+            BF::Profile(_) => opt.profile(i),
+        }
+        i += 1;
+    }
+
+    opt.finish()
+}
+
+struct Optimizer {
+    /// code[..len] が Profile を除いた出力
+    code: Vec<BF>,
+    len: usize,
+    /// (出力の何番目の命令の前か, Profile) 位置は後から取り除いた命令の分だけ前にずれる
+    profiles: Vec<(usize, BF)>,
+    /// 直前の [ か ] からの相対位置
+    pos: isize,
+    /// 書き換えたセルの値 None はわからない
+    known: BTreeMap<isize, Option<Word>>,
+    /// known にないセルが0か
+    rest_zero: bool,
+}
+
+impl Optimizer {
+    fn value(&self) -> Option<Word> {
+        match self.known.get(&self.pos) {
+            Some(value) => *value,
+            None if self.rest_zero => Some(0),
+            None => None,
+        }
+    }
+
+    fn set_value(&mut self, value: Option<Word>) {
+        self.known.insert(self.pos, value);
+    }
+
+    fn forget(&mut self) {
+        self.known.clear();
+        self.rest_zero = false;
+    }
+
+    // 書き込み位置は読んでいる位置より前なので上書きしてよい
+    fn push(&mut self, inst: BF) {
+        self.code[self.len] = inst;
+        self.len += 1;
+    }
+
+    // 読み終えた code[i] の Profile を取っておく
+    fn profile(&mut self, i: usize) {
+        let inst = std::mem::replace(&mut self.code[i], BF::Output);
+        self.profiles.push((self.len, inst));
+    }
+
+    // 直前の命令が pred を満たせば取り除く Profile は別に持っている
+    fn pop_if(&mut self, pred: impl Fn(&BF) -> bool) -> Option<BF> {
+        if self.len == 0 || !pred(&self.code[self.len - 1]) {
+            return None;
+        }
+        self.len -= 1;
+        // 後ろの Profile は finish でこれより前に揃える
+        if let Some((at, _)) = self.profiles.last_mut() {
+            *at = (*at).min(self.len);
+        }
+        Some(self.code[self.len].clone())
+    }
+
+    // n が正なら forward を n 個 直前の backward とは打ち消し合う
+    fn push_signed(&mut self, mut n: isize, forward: BF, backward: BF) {
+        let (inst, inverse) = if n > 0 {
+            (forward, backward)
+        } else {
+            (backward, forward)
+        };
+        while n != 0 && self.pop_if(|x| *x == inverse).is_some() {
+            n -= n.signum();
+        }
+        for _ in 0..n.unsigned_abs() {
+            self.push(inst.clone());
+        }
+    }
+
+    fn add(&mut self, n: isize) {
+        self.push_signed(n, BF::Inc, BF::Dec);
+        self.set_value(self.value().map(|x| x.wrapping_add(n as Word)));
+    }
+
+    fn shift(&mut self, n: isize) {
+        self.push_signed(n, BF::Right, BF::Left);
+        self.pos += n;
+    }
+
+    fn clear(&mut self) {
+        // 直前の +- は結果に影響しない 消すと元から0だとわかることもある
+        while let Some(inst) = self.pop_if(|x| matches!(x, BF::Inc | BF::Dec)) {
+            let undo = if inst == BF::Inc { Word::MAX } else { 1 };
+            self.set_value(self.value().map(|x| x.wrapping_add(undo)));
+        }
+        if self.value() != Some(0) {
+            self.push(BF::LBrac);
+            self.push(BF::Dec);
+            self.push(BF::RBrac);
+            self.set_value(Some(0));
+        }
+    }
+
+    // Profile を元の順序のまま命令の間に戻す
+    fn finish(self) -> Vec<BF> {
+        let Optimizer {
+            mut code,
+            len,
+            profiles,
+            ..
+        } = self;
+        // 後ろから詰めて書く Profile の数だけ読んだ位置より後ろなので上書きしてよい
+        let mut k = len;
+        let mut w = len + profiles.len();
+        code.truncate(w);
+        // 命令を取り除いたときは最後の Profile しか動かしていないので，前の Profile も揃える
+        let mut bound = len;
+        for (at, profile) in profiles.into_iter().rev() {
+            bound = bound.min(at);
+            while k > bound {
+                k -= 1;
+                w -= 1;
+                code.swap(k, w);
+            }
+            w -= 1;
+            code[w] = profile;
+        }
+        code
+    }
+}
+
+// 先頭から続く forward と backward の (個数の差, 長さ)
+fn run(code: &[BF], forward: BF, backward: BF) -> (isize, usize) {
+    let mut n = 0;
+    let len = code
+        .iter()
+        .take_while(|x| {
+            match x {
+                x if **x == forward => n += 1,
+                x if **x == backward => n -= 1,
+                _ => return false,
+            }
+            true
+        })
+        .count();
+    (n, len)
+}
+
+// 先頭の [ に対応する ] の位置 対応が取れなければ最適化しない
+fn find_end(code: &[BF]) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, inst) in code.iter().enumerate() {
+        match inst {
+            BF::LBrac => depth += 1,
+            BF::RBrac => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[test]
+fn test_optimize() {
+    use crate::codegen::stack::StackInst;
+
+    let show = |code: &str| super::show_bf(&optimize(BF::parse(code)));
+
+    assert_eq!(show("+><-+>>-+<"), "+>");
+    // 値のわかっているセルの [-] と入らないループ 最初はすべて0
+    assert_eq!(show(",>+++[-]<[-]>[->+<]."), ",[-]>.");
+    assert_eq!(show(",[>]+[-]"), ",[>]");
+    // ループの中では何も仮定しない
+    assert_eq!(show("[-]+[>[-]<-]"), "+[>[-]<-]");
+    // 対応しない括弧はそのまま
+    assert_eq!(show("[-]<[><"), "<[");
+
+    let mut code = BF::parse(">");
+    code.push(BF::Profile(StackInst::Nop));
+    code.extend(BF::parse("<[-]["));
+    code.push(BF::Profile(StackInst::Goto));
+    code.extend(BF::parse("]"));
+    assert_eq!(
+        optimize(code),
+        [BF::Profile(StackInst::Nop), BF::Profile(StackInst::Goto)]
+    );
+}
+
+#[test]
+fn test_optimize_large() {
+    use crate::codegen::stack::StackInst;
+    use std::time::{Duration, Instant};
+
+    // 大きな配列の初期化のように [ ] を挟まずに多くのセルを [-] する
+    let n = 100_000;
+    let mut code = BF::parse(&">+[-]".repeat(n));
+    // 打ち消し合う命令の間に Profile が溜まる
+    code.extend(BF::parse(&"+".repeat(n)));
+    for _ in 0..n {
+        code.push(BF::Profile(StackInst::Nop));
+        code.push(BF::Dec);
+    }
+    code.push(BF::Output);
+
+    let start = Instant::now();
+    let code = optimize(code);
+    // 以前は二乗の時間がかかっていた
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(code.len(), 2 * n + 1);
+    assert!(
+        code[n..2 * n]
+            .iter()
+            .all(|x| *x == BF::Profile(StackInst::Nop))
+    );
+    assert_eq!(code[2 * n], BF::Output);
+}
@@ -14,13 +14,13 @@ fn store(ty: &Type) -> Vec<StackCommand> {
 
 impl BinaryOp {
     fn insert_map(&self, ty: &Type) -> Option<InsertFunction> {
+        // enumはintとして扱う
+        let ty = match ty {
+            Type::Enum(_) => &Type::Int,
+            _ => ty,
+        };
         match self {
             Self::Comparison(com) => match com {
-                Comparison::Greater if ty == &Type::Int => InsertFunction::Greater.into(),
-                Comparison::Less if ty == &Type::Int => InsertFunction::Less.into(),
-                Comparison::GreaterEqual if ty == &Type::Int => InsertFunction::GreaterEqual.into(),
-                Comparison::LessEqual if ty == &Type::Int => InsertFunction::LessEqual.into(),
-
                 Comparison::EqualEqual if ty == &Type::Double => InsertFunction::DoubleEqual.into(),
                 Comparison::Greater if ty == &Type::Double => InsertFunction::DoubleGreater.into(),
                 Comparison::Less if ty == &Type::Double => InsertFunction::DoubleLess.into(),
@@ -34,14 +34,28 @@ impl BinaryOp {
                 Arithmetic::Asterisk if ty == &Type::Double => InsertFunction::DoubleMul.into(),
                 _ => None,
             },
-            BinaryOp::Logical(logical) => match logical {
-                Logical::AmpersandAmpersand => InsertFunction::Land.into(),
-                _ => None,
-            },
+            BinaryOp::Logical(_) => None,
         }
     }
 }
 
+// int の大小比較は符号付きの命令にする 等号は符号によらない
+// const int や typedef した int も同じ
+fn signed_comparison(op: &BinaryOp, ty: &Type) -> Option<Comparison> {
+    match (op, ty.unqualified()) {
+        (
+            BinaryOp::Comparison(
+                com @ (Comparison::Less
+                | Comparison::LessEqual
+                | Comparison::Greater
+                | Comparison::GreaterEqual),
+            ),
+            Type::Int | Type::Enum(_),
+        ) => Some(*com),
+        _ => None,
+    }
+}
+
 fn try_codegen_binop(cgs: &mut CodeGenStatus, key: InsertFunction, binary: Binary) -> bool {
     if let Some(func) = cgs.insert_function.get(&key) {
         codegen_call_fn(
@@ -57,15 +71,29 @@ fn try_codegen_binop(cgs: &mut CodeGenStatus, key: InsertFunction, binary: Binar
 pub fn gen_expr(typed_expr: TypedExpr, cgs: &mut CodeGenStatus) {
     match typed_expr.expr {
         SemaExpr::Binary(binary) => {
+            if let BinaryOp::Logical(logical) = binary.op {
+                let Binary { lhs, rhs, .. } = binary;
+                codegen_logical_fn(
+                    logical,
+                    move |cgs: &mut CodeGenStatus| gen_expr(*lhs, cgs),
+                    move |cgs: &mut CodeGenStatus| gen_expr(*rhs, cgs),
+                    cgs,
+                );
+                return;
+            }
             let inset_fn = binary.op.insert_map(&binary.lhs.r#type);
             if let Some(key) = inset_fn {
                 if try_codegen_binop(cgs, key.clone(), binary.clone()) {
                     return;
                 }
             }
+            let signed = signed_comparison(&binary.op, &binary.lhs.r#type);
             gen_expr(*binary.lhs, cgs);
             gen_expr(*binary.rhs, cgs);
-            cgs.outputs.push(binary.op.into());
+            match signed {
+                Some(comparison) => cgs.outputs.push(StackCommand::SignedCompare(comparison)),
+                None => cgs.outputs.push(binary.op.into()),
+            }
         }
         SemaExpr::Assign(assign) => match assign.op {
             AssignOp::Equal => {
@@ -100,6 +128,10 @@ pub fn gen_expr(typed_expr: TypedExpr, cgs: &mut CodeGenStatus) {
             let ty = symbol.get_type().unwrap();
             match ty {
                 Type::Func(_) => cgs.outputs.push(StackCommand::Symbol(symbol.clone())),
+                _ if cgs.is_global(&symbol) => {
+                    cgs.outputs.push(StackCommand::GlobalSymbol(symbol.clone()));
+                    cgs.outputs.extend(load(&ty));
+                }
                 _ => {
                     cgs.outputs.push(StackCommand::Symbol(symbol.clone()));
                     cgs.outputs.push(StackCommand::AcsessUseLa);
@@ -195,7 +227,11 @@ pub fn gen_expr(typed_expr: TypedExpr, cgs: &mut CodeGenStatus) {
         }
         SemaExpr::MemberAccess(member_access) => match member_access.kind {
             MemberAccessOp::Dot => match &member_access.base.r#type {
-                Type::Union(_) => {}
+                Type::Union(_) => {
+                    // 共用体のメンバは全て先頭から重なって配置される
+                    gen_expr_left(*member_access.base.clone(), cgs);
+                    cgs.outputs.extend(load(&typed_expr.r#type));
+                }
                 Type::Struct(st) => {
                     gen_expr_left(*member_access.base.clone(), cgs);
                     let pos = st
@@ -239,6 +275,7 @@ pub fn gen_expr_left(typed_expr: TypedExpr, cgs: &mut CodeGenStatus) {
     match typed_expr.expr {
         SemaExpr::Symbol(ident) => match ident.get_type().unwrap() {
             Type::Func(_) => cgs.outputs.push(StackCommand::Symbol(ident)),
+            _ if cgs.is_global(&ident) => cgs.outputs.push(StackCommand::GlobalSymbol(ident)),
             _ => {
                 cgs.outputs.push(StackCommand::Symbol(ident));
                 cgs.outputs.push(StackCommand::La2GaAddress);
@@ -269,7 +306,10 @@ pub fn gen_expr_left(typed_expr: TypedExpr, cgs: &mut CodeGenStatus) {
         }
         SemaExpr::MemberAccess(member_access) => match member_access.kind {
             MemberAccessOp::Dot => match &member_access.base.r#type {
-                Type::Union(_) => {}
+                Type::Union(_) => {
+                    // オフセットは常に0なのでベースのアドレスそのまま
+                    gen_expr_left(*member_access.base.clone(), cgs);
+                }
                 Type::Struct(st) => {
                     gen_expr_left(*member_access.base.clone(), cgs);
                     let pos = st
@@ -1,4 +1,5 @@
-use crate::sema::ast::Call;
+use crate::op::Logical;
+use crate::sema::ast::{Call, Type};
 
 use super::*;
 
@@ -40,6 +41,44 @@ where
     }
 }
 
+// 短絡評価 結果の置き場を先に積み，右辺が不要ならそのまま終了へ飛ぶ
+// どの経路でも置き場一つ分だけ積まれた状態で合流する
+pub fn codegen_logical_fn<L, R>(logical: Logical, lhs: L, rhs: R, cgs: &mut CodeGenStatus)
+where
+    L: FnOnce(&mut CodeGenStatus),
+    R: FnOnce(&mut CodeGenStatus),
+{
+    let label_rhs = cgs.name_gen.slabel();
+    let label_flip = cgs.name_gen.slabel();
+    let label_end = cgs.name_gen.slabel();
+
+    // &&は偽，||は真が左辺だけで決まる値
+    let (decided, other) = match logical {
+        Logical::AmpersandAmpersand => (0, 1),
+        Logical::PipePipe => (1, 0),
+    };
+    let branch = |to_rhs: SLabel, to_end: SLabel| match logical {
+        Logical::AmpersandAmpersand => StackCommand::Branch(to_rhs, to_end),
+        Logical::PipePipe => StackCommand::Branch(to_end, to_rhs),
+    };
+
+    cgs.outputs.push(StackCommand::Push(decided.into()));
+    lhs(cgs);
+    cgs.outputs.push(branch(label_rhs, label_end));
+
+    cgs.outputs.push(label_rhs.into());
+    rhs(cgs);
+    cgs.outputs.push(branch(label_flip, label_end));
+
+    // 右辺まで見て結果が変わる場合は置き場を書き換える
+    cgs.outputs.push(label_flip.into());
+    cgs.outputs.push(StackCommand::Pop(Type::Int));
+    cgs.outputs.push(StackCommand::Push(other.into()));
+    cgs.outputs.push(StackCommand::Goto(label_end));
+
+    cgs.outputs.push(label_end.into());
+}
+
 pub fn codegen_while_fn<C, B>(cond: C, body: B, cgs: &mut CodeGenStatus)
 where
     C: FnOnce(&mut CodeGenStatus),
@@ -8,7 +8,7 @@ int int2bool(int);
 int sgn(int a)
 {
     int r = 1;
-    if (32767 <= a)
+    if (a < 0)
     {
         r = 0; // 負数
     }
@@ -36,39 +36,6 @@ int Not(int a)
         return 0;
 }
 
-// a > b
-int Greater(int a, int b)
-{
-    int a_abs = abs(a);
-    int a_sgn = sgn(a);
-
-    int b_abs = abs(b);
-    int b_sgn = sgn(b);
-
-    if (b_sgn > a_sgn)
-    {
-        return 0;
-    }
-    else if (a_sgn > b_sgn)
-    {
-        return 1;
-    }
-    else
-    {
-        if (a_sgn == 0)
-        {
-            return Not(a_abs > b_abs);
-        }
-        return (a_abs > b_abs);
-    }
-}
-
-int Less(int a, int b) { return Greater(b, a); }
-
-int GreaterEqual(int a, int b) { return Greater(a, b) || a == b; }
-
-int LessEqual(int a, int b) { return Less(a, b) || a == b; }
-
 int Ternary(int a, int b, int c)
 {
     if (a != 0)
@@ -130,8 +97,6 @@ int Mod(int a, int b)
     return r;
 }
 
-int Land(int a, int b) { return (int2bool(a) * int2bool(b)); }
-
 void print_error(char (*s)[0])
 {
     int i = 0;
@@ -132,7 +132,7 @@ Double DoubleSub(Double a, Double b) {
         // a - b
         if (DoubleGreater(a, b)) {
             // a > b
-            return DoubleSubAGeBPos(a, b)
+            return DoubleSubAGeBPos(a, b);
         } else {
             // a<=b => -(b-a)
             Double tmp = DoubleSubAGeBPos(b, a);
@@ -239,7 +239,7 @@ int(mul_help(int a[5], int b))[5] {
       a[2] * b,
       a[3] * b,
       a[4] * b,
-    }
+    };
 
     return result;
 }
@@ -1,19 +1,25 @@
+use std::collections::HashMap;
+use std::path::Path;
 use std::rc::Rc;
+use strum::IntoEnumIterator;
 
 use super::bf::*;
 use super::stack::*;
 use super::stmt as gen_stmt;
 use super::{CodeGenStatus, StackCommand};
-use crate::codegen::SFunc;
-use crate::codegen::second::SeStackCommand;
+use crate::codegen::second::{Origin, SeStackCommand, Trace};
+use crate::codegen::{SFunc, jump_targets};
 use crate::op::{Arithmetic, BinaryOp, Comparison, Logical, UnaryOp};
 use crate::sema::ast::*;
 
 fn function_def(function: FunctionDef, cgs: &mut CodeGenStatus) {
     cgs.outputs.clear();
+    cgs.statements.clear();
 
     let func_end = cgs.name_gen.slabel();
     cgs.func_end = Some(func_end);
+    cgs.func_scope = Some(function.sig.symbol.scope.clone());
+    cgs.user_labels.clear();
 
     for s in function.body.into_vec() {
         gen_stmt::stmt(*s, cgs);
@@ -25,12 +31,20 @@ fn function_def(function: FunctionDef, cgs: &mut CodeGenStatus) {
     cgs.outputs
         .push(StackCommand::Goto(SLabelReserved::Exit.into())); //絶対に到達しないけど構造上必要
 
-    let func = SFunc::new(
+    let name = &function.sig.symbol.ident.name;
+    cgs.label_names.insert(func_end, format!("{}.return", name));
+    for (label, this) in &cgs.user_labels {
+        cgs.label_names
+            .insert(*this, format!("{}.{}", name, label.name));
+    }
+
+    let mut func = SFunc::new(
         function.sig,
         function.param_names,
         cgs.outputs.clone(),
         cgs.name_gen.slabel(),
     );
+    func.statements = std::mem::take(&mut cgs.statements);
 
     cgs.funcs.push(func);
     cgs.outputs.clear();
@@ -155,93 +169,230 @@ fn gen_top_level(top_level: TopLevel, cgs: &mut CodeGenStatus) {
     match top_level {
         TopLevel::FunctionDef(function) => function_def(function, cgs),
         TopLevel::FunctionProto(function) => function_proto(function, cgs), // 関数プロトタイプは無視
-        TopLevel::Stmt(stmt) => super::stmt::stmt(stmt, cgs),
+        TopLevel::Stmt(stmt) => super::stmt::global(stmt, cgs),
     }
 }
 
-pub fn generate_program(program: Program) {
-    let mut cgs = CodeGenStatus::new();
+/// 組み込み関数の定義 この順にコンパイルする
+const PRELUDE: [(&str, &str); 3] = [
+    ("base.c", include_str!("insert_c/base.c")),
+    ("int.c", include_str!("insert_c/int.c")),
+    ("float.c", include_str!("insert_c/float.c")),
+];
+
+/// 組み込み関数のコンパイルに失敗した
+#[derive(Debug)]
+pub struct PreludeError {
+    pub file: String,
+    pub message: String,
+}
 
-    let (fine_base, _session_base) = fine_expr("src/codegen/insert_c/base.c");
-    // sessionは必要，
-    {
-        for item in fine_base.items {
-            gen_top_level(item, &mut cgs);
+impl std::fmt::Display for PreludeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to compile the runtime prelude '{}'\n{}",
+            self.file, self.message
+        )
+    }
+}
+
+/// コード生成の各段階の出力
+pub struct Generated {
+    /// 組み込み関数も含めた関数ごとの StackCommand
+    pub funcs: Vec<SFunc>,
+    pub second: Vec<SeStackCommand>,
+    /// second の各命令の出どころと関数内の深さ
+    pub trace: Trace,
+    pub stack: Vec<StackInst>,
+    /// stack を最適化して translate に渡したもの
+    pub optimized: Vec<StackInst>,
+    pub code: Vec<BF>,
+    /// 関数の入口や goto のラベルなど名前の分かるもの
+    pub labels: HashMap<usize, String>,
+    // sessionは必要，funcsのシンボルが組み込み関数のスコープを弱参照している
+    _sessions: Vec<Session>,
+}
+
+impl Generated {
+    pub fn bf(&self) -> String {
+        show_bf(&self.code)
+    }
+
+    /// 関数ごとの StackCommand の一覧
+    /// [ ] の中は命令の直後のスタックの深さ，# の行はその命令から始まる文 (Options::statements のときだけ)
+    pub fn dump_stack(&self) -> String {
+        let mut out = String::new();
+        for func in 0..self.funcs.len() {
+            self.dump_header(&mut out, Some(func), true);
+            for index in 0..self.funcs[func].body.len() {
+                self.dump_command(&mut out, func, index);
+            }
         }
+        out
+    }
 
-        cgs.funcs.iter().for_each(|x| {
-            if x.sig
-                .symbol
-                .ident
-                .get_name()
-                .parse::<InsertFunction>()
-                .is_ok()
-            {
-                cgs.insert_function.insert(
-                    x.sig
-                        .symbol
-                        .ident
-                        .get_name()
-                        .parse::<InsertFunction>()
-                        .unwrap(),
-                    x.sig.symbol.clone(),
-                );
+    /// SeStackCommand を元の StackCommand の下に並べる
+    pub fn dump_sestack(&self) -> String {
+        self.dump_stream(|i| format!("{:?}", self.second[i]))
+    }
+
+    /// StackInst を元の StackCommand の下に並べる SeStackCommand と1対1
+    pub fn dump_stack_inst(&self) -> String {
+        self.dump_stream(|i| format!("{:?}", self.stack[i]))
+    }
+
+    /// 最適化後の StackInst 元の命令との対応はないのでラベルごとに区切る
+    pub fn dump_optimized(&self) -> String {
+        let mut out = String::new();
+        for (i, inst) in self.optimized.iter().enumerate() {
+            let labels = match (inst, self.optimized.get(i + 1)) {
+                (StackInst::Label(x), _) => vec![*x as usize],
+                (StackInst::Branch(a, b), _) => vec![*a as usize, *b as usize],
+                (StackInst::Push(x), Some(StackInst::Goto)) => vec![*x as usize],
+                _ => vec![],
+            };
+            let indent = if matches!(inst, StackInst::Label(_)) {
+                ""
+            } else {
+                "    "
+            };
+            out += &format!("{}{:?}{}\n", indent, inst, self.label_note(&labels));
+        }
+        out
+    }
+
+    fn dump_stream(&self, line: impl Fn(usize) -> String) -> String {
+        let mut out = String::new();
+        // (表示中の関数, 表示済みの StackCommand の数)
+        let mut shown: Option<(usize, usize)> = None;
+
+        for (i, origin) in self.trace.origins.iter().enumerate() {
+            let func = match *origin {
+                Origin::Outside => None,
+                Origin::Prologue(func) | Origin::Command(func, _) => Some(func),
+            };
+            let changed = match func {
+                Some(_) => shown.map(|(f, _)| f) != func,
+                None => i == 0 || self.trace.origins[i - 1] != Origin::Outside,
+            };
+            if changed {
+                self.dump_rest(&mut out, shown);
+                self.dump_header(&mut out, func, i == 0);
+                shown = func.map(|f| (f, 0));
             }
-        });
+            if let (Origin::Command(func, index), Some((_, done))) = (*origin, shown) {
+                (done..=index).for_each(|x| self.dump_command(&mut out, func, x));
+                shown = Some((func, done.max(index + 1)));
+            }
+
+            let labels = second_labels(&self.second[i], self.second.get(i + 1));
+            out += &format!("            {}{}\n", line(i), self.label_note(&labels));
+        }
+        self.dump_rest(&mut out, shown);
+        out
     }
 
-    let (fine_int, _session_int) = fine_expr("src/codegen/insert_c/int.c");
-    // sessionは必要，
-    {
-        for item in fine_int.items {
-            gen_top_level(item, &mut cgs);
+    // 何も出力しなかった関数末尾の StackCommand
+    fn dump_rest(&self, out: &mut String, shown: Option<(usize, usize)>) {
+        if let Some((func, done)) = shown {
+            (done..self.funcs[func].body.len()).for_each(|x| self.dump_command(out, func, x));
         }
+    }
 
-        cgs.funcs.iter().for_each(|x| {
-            if x.sig
-                .symbol
-                .ident
-                .get_name()
-                .parse::<InsertFunction>()
-                .is_ok()
-            {
-                cgs.insert_function.insert(
-                    x.sig
-                        .symbol
-                        .ident
-                        .get_name()
-                        .parse::<InsertFunction>()
-                        .unwrap(),
-                    x.sig.symbol.clone(),
-                );
+    // None は関数の外 (入口なら true)
+    fn dump_header(&self, out: &mut String, func: Option<usize>, entry: bool) {
+        match func {
+            Some(func) => {
+                let func = &self.funcs[func];
+                *out += &format!("{} ({:?}):\n", func.sig.symbol.ident.name, func.entry);
             }
-        });
+            None if entry => *out += "(entry):\n",
+            None => *out += "(exit):\n",
+        }
     }
 
-    let (fine_float, _session_float) = fine_expr("src/codegen/insert_c/float.c");
-    // sessionは必要，
-    {
-        for item in fine_float.items {
+    fn dump_command(&self, out: &mut String, func: usize, index: usize) {
+        let sfunc = &self.funcs[func];
+        for (_, statement) in sfunc.statements.iter().filter(|(at, _)| *at == index) {
+            *out += &format!("  # {}\n", statement);
+        }
+        let command = &sfunc.body[index];
+        *out += &format!(
+            "    [{:>3}] {:?}{}\n",
+            self.trace.depths[func][index],
+            command,
+            self.label_note(&stack_labels(command))
+        );
+    }
+
+    fn label_note(&self, labels: &[usize]) -> String {
+        let names: Vec<String> = labels
+            .iter()
+            .filter_map(|x| self.labels.get(x).map(|name| format!("{} = {}", x, name)))
+            .collect();
+        if names.is_empty() {
+            String::new()
+        } else {
+            format!("  ; {}", names.join(", "))
+        }
+    }
+}
+
+// 命令が参照しているラベル
+fn stack_labels(command: &StackCommand) -> Vec<usize> {
+    match command {
+        StackCommand::Label(x) | StackCommand::Goto(x) | StackCommand::ReturnPoint(x) => {
+            vec![x.0]
+        }
+        StackCommand::Branch(a, b) => vec![a.0, b.0],
+        _ => vec![],
+    }
+}
+
+// Goto の直前の Push もラベル
+fn second_labels(command: &SeStackCommand, next: Option<&SeStackCommand>) -> Vec<usize> {
+    match (command, next) {
+        (SeStackCommand::Label(x), _) => vec![*x],
+        (SeStackCommand::Branch(a, b), _) => vec![*a, *b],
+        (SeStackCommand::Push(x), Some(SeStackCommand::Goto)) => vec![*x],
+        _ => vec![],
+    }
+}
+
+/// prelude_dir を渡すと同梱のものの代わりにそのディレクトリの base.c / int.c / float.c を使う
+pub fn generate_program(
+    program: Program,
+    prelude_dir: Option<&Path>,
+    statements: bool,
+) -> Result<Generated, PreludeError> {
+    let mut cgs = CodeGenStatus::new();
+    cgs.record_statements = statements;
+
+    let mut sessions = Vec::new();
+    for (name, bundled) in PRELUDE {
+        let (file, source) = match prelude_dir {
+            Some(dir) => {
+                let path = dir.join(name);
+                let source = std::fs::read_to_string(&path).map_err(|e| PreludeError {
+                    file: path.display().to_string(),
+                    message: e.to_string(),
+                })?;
+                (path.display().to_string(), source)
+            }
+            None => (name.to_string(), bundled.to_string()),
+        };
+
+        let (fine, session) = fine_expr(&file, &source)?;
+        sessions.push(session);
+
+        for item in fine.items {
             gen_top_level(item, &mut cgs);
         }
 
         cgs.funcs.iter().for_each(|x| {
-            if x.sig
-                .symbol
-                .ident
-                .get_name()
-                .parse::<InsertFunction>()
-                .is_ok()
-            {
-                cgs.insert_function.insert(
-                    x.sig
-                        .symbol
-                        .ident
-                        .get_name()
-                        .parse::<InsertFunction>()
-                        .unwrap(),
-                    x.sig.symbol.clone(),
-                );
+            if let Ok(insert) = x.sig.symbol.ident.get_name().parse::<InsertFunction>() {
+                cgs.insert_function.insert(insert, x.sig.symbol.clone());
             }
         });
     }
@@ -250,6 +401,8 @@ pub fn generate_program(program: Program) {
         gen_top_level(item, &mut cgs);
     }
 
+    renumber_labels(&mut cgs);
+
     // cgs.funcs.iter().for_each(|x| {
     //     eprintln!("{}:{:?}", x.sig.symbol.ident.name, x.entry);
     //     dbg!(&x.body);
@@ -257,50 +410,169 @@ pub fn generate_program(program: Program) {
 
     // eprintln!("===");
 
-    let s = super::second::start(cgs.funcs, &mut cgs.name_gen);
+    let mut labels: HashMap<usize, String> = cgs
+        .label_names
+        .iter()
+        .map(|(label, name)| (label.0, name.clone()))
+        .collect();
+    // 組み込み関数の exit と区別する
+    labels.insert(SLabelReserved::Entry as usize, "(entry)".to_string());
+    labels.insert(SLabelReserved::Exit as usize, "(exit)".to_string());
+    for func in &cgs.funcs {
+        labels.insert(func.entry.0, func.sig.symbol.ident.name.clone());
+    }
+
+    let funcs = cgs.funcs.clone();
+    let (s, trace) = super::second::start(cgs.funcs, cgs.globals, &mut cgs.name_gen);
 
     let stream = s
         .iter()
         .map(|x| convert(x.clone()))
         .collect::<Vec<StackInst>>();
 
-    let transpilation = translate(&stream);
-
-    println!("{}", show_bf(&transpilation));
+    let optimized = optimize_stack(stream.clone());
+    let code = optimize(translate(&optimized));
+    Ok(Generated {
+        funcs,
+        second: s,
+        trace,
+        stack: stream,
+        optimized,
+        code,
+        labels,
+        _sessions: sessions,
+    })
 }
 
-fn fine_expr(filename: impl ToString) -> (Program, Session) {
-    use crate::*;
+// ラベルの番号は BF で定数として作るので，参照の多いものほど小さい番号にする
+// 飛び先になるラベルは予約済みのラベルの後に隙間なく並べ，ブロックの id などはその後ろに置く
+// 予約済みのラベルはそのまま
+fn renumber_labels(cgs: &mut CodeGenStatus) {
+    let reserved = SLabelReserved::iter().map(|x| x as usize).max().unwrap();
+    let targets = jump_targets(&cgs.funcs);
+    let entries: HashMap<Symbol, SLabel> = cgs
+        .funcs
+        .iter()
+        .map(|func| (func.sig.symbol.clone(), func.entry))
+        .collect();
+
+    // (参照の数, 初出の順)
+    let mut uses: HashMap<SLabel, (usize, usize)> = HashMap::new();
+    let mut count = |label: SLabel, refs: usize| {
+        let next = uses.len();
+        uses.entry(label).or_insert((0, next)).0 += refs;
+    };
+    for func in &mut cgs.funcs {
+        count(func.entry, 0);
+        for command in &mut func.body {
+            let refs = match command {
+                StackCommand::Goto(_) | StackCommand::ReturnPoint(_) | StackCommand::Branch(..) => {
+                    1
+                }
+                StackCommand::Symbol(symbol) => {
+                    if let Some(&entry) = entries.get(symbol) {
+                        count(entry, 1);
+                    }
+                    0
+                }
+                _ => 0,
+            };
+            command
+                .labels_mut()
+                .into_iter()
+                .for_each(|x| count(*x, refs));
+        }
+    }
 
-    let mut input = fs::read_to_string(filename.to_string()).unwrap();
+    let mut order: Vec<(SLabel, (usize, usize))> = uses
+        .into_iter()
+        .filter(|(label, _)| label.0 > reserved)
+        .collect();
+    order.sort_by_key(|&(label, (refs, first))| {
+        (!targets.contains(&label), std::cmp::Reverse(refs), first)
+    });
+    let map: HashMap<SLabel, SLabel> = order
+        .iter()
+        .enumerate()
+        .map(|(i, (label, _))| (*label, SLabel(reserved + 1 + i)))
+        .collect();
+    let renumber = |label: &mut SLabel| {
+        if let Some(new) = map.get(label) {
+            *label = *new;
+        }
+    };
 
-    input = String::from_iter(normalized(input.chars()));
+    for func in &mut cgs.funcs {
+        renumber(&mut func.entry);
+        for command in &mut func.body {
+            command.labels_mut().into_iter().for_each(renumber);
+        }
+    }
+    cgs.label_names = std::mem::take(&mut cgs.label_names)
+        .into_iter()
+        .map(|(mut label, name)| {
+            renumber(&mut label);
+            (label, name)
+        })
+        .collect();
+}
 
-    preprocessor::remove_comments(&mut input);
-    preprocessor::unescape_char_literals(&mut input);
+fn fine_expr(file: &str, source: &str) -> Result<(Program, Session), PreludeError> {
+    use crate::*;
 
-    let mut token = lexer::tokenize(&input);
-    let mut session = parser::ParseSession::new();
-    let mut program: ast::Program = parser::program(&mut session, &mut token);
+    let fail = |messages: Vec<String>| PreludeError {
+        file: file.to_string(),
+        message: messages.join("\n"),
+    };
+
+    let preprocessed = preprocessor::preprocess(file, source, &[]);
+    if !preprocessed.errors.is_empty() {
+        return Err(fail(
+            preprocessed
+                .errors
+                .iter()
+                .map(|e| preprocessed.render(e.span, &e.to_string()))
+                .collect(),
+        ));
+    }
 
-    let mut simp_session = Session::new();
+    let (token, spans) = lexer::tokenize(&preprocessed.text).map_err(|errors| {
+        fail(
+            errors
+                .iter()
+                .map(|e| preprocessed.render(Some(e.span), &e.to_string()))
+                .collect(),
+        )
+    })?;
+    let mut session = parser::ParseSession::with_spans(spans);
+    let mut program: ast::Program =
+        parser::program(&mut session, &mut parser::TokenCursor::new(&token)).map_err(|errors| {
+            fail(
+                errors
+                    .iter()
+                    .map(|e| preprocessed.render(e.span, &e.to_string()))
+                    .collect(),
+            )
+        })?;
+
+    let mut simp_session = sema::simplification::Session::new();
     sema::simplification::program(&mut program, &mut simp_session);
 
     let mut sema_session = sema::ast::Session::new();
     let new_program = sema::convert::program(&program, &mut sema_session);
     let type_check_result = sema::r#type::program(&new_program, &mut sema_session);
 
-    let type_errors = type_check_result.errors;
-
-    // 型エラーを表示
-    if !type_errors.is_empty() {
-        eprintln!("=== Type Errors ===");
-        for error in &type_errors {
-            eprintln!("型エラー: {}", error);
-        }
+    if !type_check_result.errors.is_empty() {
+        return Err(fail(
+            type_check_result
+                .errors
+                .iter()
+                .map(|e| preprocessed.render(e.span(), &e.to_string()))
+                .collect(),
+        ));
     }
 
-    (type_check_result.result, sema_session)
+    Ok((type_check_result.result, sema_session))
 }
 
 pub fn convert(b: SeStackCommand) -> StackInst {
@@ -333,6 +605,13 @@ pub fn convert(b: SeStackCommand) -> StackInst {
                 Logical::PipePipe => StackInst::LOr,
             },
         }, // 二項演算子
+        SeStackCommand::SignedCompare(a) => match a {
+            Comparison::Greater => StackInst::SGr,
+            Comparison::GreaterEqual => StackInst::SGrEq,
+            Comparison::Less => StackInst::SLt,
+            Comparison::LessEqual => StackInst::SLtEq,
+            _ => unreachable!("{:?}", a),
+        },
         SeStackCommand::UnaryOp(op) => match op {
             UnaryOp::Minus => StackInst::Negate,
             UnaryOp::Bang => StackInst::Not,
@@ -1,9 +1,9 @@
-use crate::OneLine;
-use std::collections::HashMap;
+use crate::visualize::OneLine;
+use std::collections::{HashMap, HashSet};
 use std::usize;
 
 use super::StackCommand;
-use super::utils::SFunc;
+use super::utils::{SFunc, jump_targets};
 use super::{SLabel, SLabelReserved};
 use crate::codegen::NameGenerator;
 use crate::codegen::second::extended_commands::load_n_by_pointer;
@@ -30,6 +30,7 @@ pub enum SeStackCommand {
     Copy,
     SellOut,
     Input,
+    SignedCompare(Comparison), // 符号付きの大小比較
 }
 
 impl From<SLabel> for Address {
@@ -38,6 +39,25 @@ impl From<SLabel> for Address {
     }
 }
 
+/// SeStackCommand がどこから来たか 関数は start に渡した順の添字
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// エントリーポイントと終了処理
+    Outside,
+    /// 関数の入口のラベル
+    Prologue(usize),
+    /// (関数, StackCommand の添字)
+    Command(usize, usize),
+}
+
+/// ダンプ用
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub origins: Vec<Origin>,
+    /// 関数ごと StackCommand ごとの直後の stack_size_func
+    pub depths: Vec<Vec<usize>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CodeGenStatus {
     pub outpus: Vec<SeStackCommand>,
@@ -46,6 +66,10 @@ pub struct CodeGenStatus {
     pub label_stack: HashMap<SLabel, usize>,
     pub alloced: Vec<usize>,
     pub symbol_table: HashMap<Symbol, Address>,
+    pub global_table: HashMap<Symbol, Address>,
+    pub label_depth: HashMap<SLabel, usize>,
+    pub pending_clear: Vec<(usize, usize, SLabel)>, // (outpusの位置, その時の深さ, 飛び先)
+    pub trace: Trace,
 }
 
 impl CodeGenStatus {
@@ -57,6 +81,10 @@ impl CodeGenStatus {
             label_stack: HashMap::new(),
             alloced: vec![0],
             symbol_table: HashMap::new(),
+            global_table: HashMap::new(),
+            label_depth: HashMap::new(),
+            pending_clear: Vec::new(),
+            trace: Trace::default(),
         }
     }
     fn add_stck(&mut self, size: usize) {
@@ -98,6 +126,11 @@ impl CodeGenStatus {
         }
     }
 
+    // まだ出どころのない出力をoriginのものとする
+    fn mark(&mut self, origin: Origin) {
+        self.trace.origins.resize(self.outpus.len(), origin);
+    }
+
     fn label_stack_push(&mut self, label: SLabel) {
         self.alloced.push(0);
         self.label_stack
@@ -105,8 +138,16 @@ impl CodeGenStatus {
     }
 }
 
-pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCommand> {
+pub fn start(
+    inputs: Vec<SFunc>,
+    globals: Vec<(Symbol, Vec<usize>)>,
+    name_gen: &mut NameGenerator,
+) -> (Vec<SeStackCommand>, Trace) {
     let mut cgs = CodeGenStatus::new();
+    let targets: HashSet<Address> = jump_targets(&inputs)
+        .into_iter()
+        .map(Address::from)
+        .collect();
 
     let mut entry: Option<Symbol> = None;
     for func in &inputs {
@@ -121,14 +162,25 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
     {
         cgs.outpus
             .insert(0, SeStackCommand::Label(SLabelReserved::Entry as usize));
+
+        // mainのフレームより下にグローバル領域を置く
+        // グローバルアドレス = スタックの底からの位置 0はNULLと区別するため空けておく
+        cgs.push_usize(0);
+        for (symbol, cells) in globals {
+            cells.into_iter().for_each(|x| cgs.push_usize(x));
+            cgs.global_table.insert(symbol, cgs.head_sack_func() - 1);
+        }
+
         cgs.push_label(SLabelReserved::Exit.into());
-        cgs.push_grobal(0); // Grobal address for main
+        // mainのGrobal address 自身の位置-1
+        cgs.push_grobal(cgs.head_sack_func() - 1);
         cgs.push_label(SLabel(cgs.symbol_table[&entry.unwrap()]));
         cgs.outpus.push(SeStackCommand::Goto);
         cgs.sub_stack(1);
+        cgs.mark(Origin::Outside);
     }
 
-    for func in inputs {
+    for (func_index, func) in inputs.into_iter().enumerate() {
         cgs.head_sack_func_reset();
         cgs.reset_alloc();
         let palam_size = func
@@ -139,6 +191,7 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
         cgs.outpus
             .push(SeStackCommand::Comment(func.sig.symbol.ident.name.clone()));
         cgs.outpus.push(SeStackCommand::Label(func.entry.into()));
+        cgs.mark(Origin::Prologue(func_index));
 
         {
             {
@@ -153,7 +206,8 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
             });
         }
 
-        for cmd in func.body {
+        cgs.trace.depths.push(Vec::new());
+        for (index, cmd) in func.body.into_iter().enumerate() {
             match cmd {
                 StackCommand::Push(TypedExpr { expr, .. }) => cgs.push_expr(expr),
                 StackCommand::Input => {
@@ -164,6 +218,10 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
                     cgs.outpus.push(SeStackCommand::BinaryOP(binary_op));
                     cgs.sub_stack(1);
                 }
+                StackCommand::SignedCompare(comparison) => {
+                    cgs.outpus.push(SeStackCommand::SignedCompare(comparison));
+                    cgs.sub_stack(1);
+                }
                 StackCommand::UnaryOp(unary_op) => {
                     cgs.outpus.push(SeStackCommand::UnaryOp(unary_op))
                 }
@@ -172,6 +230,7 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
                         .get(&symbol)
                         .expect(&format!("symbol not found: {}", symbol.oneline())),
                 ),
+                StackCommand::GlobalSymbol(symbol) => cgs.push_usize(cgs.global_table[&symbol]),
                 StackCommand::Name(symbol) => {
                     // つまり配列の場合は先頭のアドレスが一番下になる．
                     _ = cgs.symbol_table.insert(symbol, cgs.head_sack_func())
@@ -192,6 +251,7 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
                 StackCommand::Label(this) => {
                     cgs.outpus.push(SeStackCommand::Label(this.into()));
                     cgs.label_stack_push(this);
+                    cgs.label_depth.insert(this, cgs.head_sack_func());
                 }
                 StackCommand::Goto(this) => cgs.outpus.extend(extended_commands::goto(this)),
                 StackCommand::Branch(label_true, label_false) => {
@@ -265,6 +325,12 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
                     cgs.outpus.push(SeStackCommand::DeAlloc(dealloc_size));
                     // sub_stackはしない　分岐する可能性があるので
                 }
+                StackCommand::ClearStackTo(this) => {
+                    // 前方へのジャンプは飛び先の深さが未確定なので後で埋める
+                    cgs.pending_clear
+                        .push((cgs.outpus.len(), cgs.head_sack_func(), this));
+                    cgs.outpus.push(SeStackCommand::DeAlloc(0));
+                }
                 StackCommand::Pop(ty) => {
                     cgs.outpus.push(SeStackCommand::Comment("Pop_start".into()));
                     let size = ty.size();
@@ -278,6 +344,13 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
                     cgs.add();
                 }
             }
+            cgs.mark(Origin::Command(func_index, index));
+            let depth = cgs.head_sack_func();
+            cgs.trace.depths.last_mut().unwrap().push(depth);
+        }
+
+        for (index, depth, label) in std::mem::take(&mut cgs.pending_clear) {
+            cgs.outpus[index] = SeStackCommand::DeAlloc(depth - cgs.label_depth[&label]);
         }
     }
 
@@ -285,34 +358,51 @@ pub fn start(inputs: Vec<SFunc>, name_gen: &mut NameGenerator) -> Vec<SeStackCom
         cgs.outpus
             .push(SeStackCommand::Label(SLabelReserved::Exit as usize));
         cgs.outpus.push(SeStackCommand::Exit);
+        cgs.mark(Origin::Outside);
     }
 
-    clea_dedspace(&mut cgs.outpus);
+    clea_dedspace(&mut cgs.outpus, &mut cgs.trace.origins, &targets);
 
-    cgs.outpus
+    (cgs.outpus, cgs.trace)
 }
 
-fn clea_dedspace(inputs: &mut Vec<SeStackCommand>) {
+// 飛び先にならないラベルは外して前のブロックに続ける 飛んだ後の届かない命令も消す
+// 飛ばずに次のラベルへ進むブロックには Goto を足す 足したものの出どころは直前の命令と同じにする
+fn clea_dedspace(
+    inputs: &mut Vec<SeStackCommand>,
+    origins: &mut Vec<Origin>,
+    targets: &HashSet<Address>,
+) {
     let mut result = Vec::new();
-
-    for window in inputs.windows(2) {
-        result.push(window[0].clone());
-
-        if matches!(window[0], SeStackCommand::Label(_))
-            && let SeStackCommand::Label(ref second) = window[1]
-        {
-            result.push(SeStackCommand::Push(*second));
-            result.push(SeStackCommand::Goto);
+    let mut result_origins: Vec<Origin> = Vec::new();
+    // ラベルから続いていて，まだ飛んでいないか
+    let mut falling = false;
+
+    for (command, origin) in inputs.drain(..).zip(origins.iter()) {
+        match command {
+            SeStackCommand::Label(label) if targets.contains(&label) => {
+                if falling {
+                    let last = *result_origins.last().unwrap();
+                    result.push(SeStackCommand::Push(label));
+                    result.push(SeStackCommand::Goto);
+                    result_origins.extend([last; 2]);
+                }
+                falling = true;
+            }
+            SeStackCommand::Label(_) => continue,
+            SeStackCommand::Comment(_) => {}
+            _ if !falling => continue,
+            SeStackCommand::Goto | SeStackCommand::Exit | SeStackCommand::Branch(..) => {
+                falling = false;
+            }
+            _ => {}
         }
+        result.push(command);
+        result_origins.push(*origin);
     }
 
-    // 最後の要素を忘れず追加
-    if let Some(last) = inputs.last() {
-        result.push(last.clone());
-    }
-
-    inputs.clear();
     inputs.extend(result);
+    *origins = result_origins;
 }
 
 impl CodeGenStatus {
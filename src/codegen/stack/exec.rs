@@ -137,7 +137,7 @@ impl StackMachine {
                     self.stack.push(word.wrapping_neg());
                 }
 
-                o @ (Eq | Neq | Lt | LtEq | Gr | GrEq | LAnd | LOr) => {
+                o @ (Eq | Neq | Lt | LtEq | Gr | GrEq | SLt | SLtEq | SGr | SGrEq | LAnd | LOr) => {
                     let b = self.stack.pop().unwrap();
                     let a = self.stack.pop().unwrap();
                     let cmp = match o {
@@ -147,6 +147,10 @@ impl StackMachine {
                         LtEq => a <= b,
                         Gr => a > b,
                         GrEq => a >= b,
+                        SLt => (a as i16) < (b as i16),
+                        SLtEq => a as i16 <= b as i16,
+                        SGr => a as i16 > b as i16,
+                        SGrEq => a as i16 >= b as i16,
                         LAnd => a != 0 && b != 0,
                         LOr => a != 0 || b != 0,
                         _ => unreachable!(),
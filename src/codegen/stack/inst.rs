@@ -36,6 +36,11 @@ pub enum StackInst {
     LtEq,
     Gr,
     GrEq,
+    // Signed (two's complement)
+    SLt,
+    SLtEq,
+    SGr,
+    SGrEq,
 
     // Logical ops
     LNot,
@@ -74,6 +79,9 @@ impl StackInst {
                 LtEq => &[Swap, GrEq],
                 Lt => &[GrEq, LNot],
                 Gr => &[LtEq, LNot],
+                SLtEq => &[Swap, SGrEq],
+                SLt => &[SGrEq, LNot],
+                SGr => &[SLtEq, LNot],
                 _ => {
                     out.push(inst);
                     continue;
@@ -98,8 +106,8 @@ impl StackInst {
             Copy => (1, Some(2)),
             Swap => (2, Some(2)),
             LNot | Not => (1, Some(1)),
-            Add | Sub | Mul | Div | Eq | Neq | Lt | LtEq | Gr | GrEq | LAnd | LOr | LShift
-            | RShift | And | Or | Xor => (2, Some(1)),
+            Add | Sub | Mul | Div | Eq | Neq | Lt | LtEq | Gr | GrEq | SLt | SLtEq | SGr
+            | SGrEq | LAnd | LOr | LShift | RShift | And | Or | Xor => (2, Some(1)),
             Alloc(n) => (0, Some(*n)),
             Dealloc(n) => (*n, Some(0)),
             Negate => (1, Some(1)),
@@ -147,6 +155,10 @@ impl std::fmt::Debug for StackInst {
             LtEq => write!(f, "LtEq"),
             Gr => write!(f, "Gr"),
             GrEq => write!(f, "GrEq"),
+            SLt => write!(f, "SLt"),
+            SLtEq => write!(f, "SLtEq"),
+            SGr => write!(f, "SGr"),
+            SGrEq => write!(f, "SGrEq"),
             LNot => write!(f, "LNot"),
             LAnd => write!(f, "LAnd"),
             LOr => write!(f, "LOr"),
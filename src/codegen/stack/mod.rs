@@ -1,11 +1,13 @@
 #[cfg(test)]
 pub mod exec;
 pub mod inst;
+pub mod opt;
 
 #[cfg(test)]
 pub use exec::*;
 
 pub use inst::*;
+pub use opt::*;
 
 pub use super::*;
 
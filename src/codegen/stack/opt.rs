@@ -0,0 +1,327 @@
+use super::*;
+use crate::codegen::bf::constant_cost;
+
+/// convert の出力の覗き穴最適化
+/// 定数の畳み込み，意味のない演算の削除，決まった形の読み書きをまとめる
+/// Comment は命令の間にあっても無視して残す Label と飛び先の命令はまたがない
+pub fn optimize_stack(code: Vec<StackInst>) -> Vec<StackInst> {
+    let mut out = Vec::with_capacity(code.len());
+    for inst in code {
+        out.push(inst);
+        if !matches!(out.last(), Some(StackInst::Comment(_))) {
+            while rewrite(&mut out) {}
+        }
+    }
+    out
+}
+
+// 書き換えを見る命令の最大の個数
+const WINDOW: usize = 8;
+
+// 末尾の命令を一度書き換える
+fn rewrite(out: &mut Vec<StackInst>) -> bool {
+    let mut at = Vec::with_capacity(WINDOW);
+    for (i, inst) in out.iter().enumerate().rev() {
+        if at.len() == WINDOW {
+            break;
+        }
+        if !matches!(inst, StackInst::Comment(_)) {
+            at.push(i);
+        }
+    }
+    at.reverse();
+    let tail = at.iter().map(|&i| out[i].clone()).collect::<Vec<_>>();
+
+    let Some((len, replace)) = peephole(&tail) else {
+        return false;
+    };
+    // 後ろから消せば前の位置はずれない
+    for &i in at[at.len() - len..].iter().rev() {
+        out.remove(i);
+    }
+    out.extend(replace);
+    true
+}
+
+// (末尾から取り除く命令の数, 代わりに置く命令)
+fn peephole(tail: &[StackInst]) -> Option<(usize, Vec<StackInst>)> {
+    use StackInst::*;
+    let found = match tail {
+        // extended_commands::swap
+        [
+            ..,
+            Push(2),
+            StkRead,
+            Push(2),
+            StkRead,
+            Push(3),
+            StkStr,
+            Push(1),
+            StkStr,
+        ] => (8, vec![Swap]),
+        // [x] → [0 x]
+        [.., Copy, Push(0), Push(2), StkStr] => (4, vec![Push(0), Swap]),
+        // 上の Copy が先に Push に置き換わったとき
+        [.., Push(_), Push(b), Push(c), Push(2), StkStr] => (5, vec![Push(*c), Push(*b)]),
+        [.., Push(0), Swap, Sub] => (3, vec![Negate]),
+        [.., Push(1), StkRead] => (2, vec![Copy]),
+
+        [.., Push(a), Push(b), op]
+            if let Some(value) = fold_binary(op, *a, *b)
+                && let Some(found) = cheaper(&tail[tail.len() - 3..], vec![Push(value)]) =>
+        {
+            found
+        }
+        [.., Push(a), op]
+            if let Some(value) = fold_unary(op, *a)
+                && let Some(found) = cheaper(&tail[tail.len() - 2..], vec![Push(value)]) =>
+        {
+            found
+        }
+
+        // 0 との比較は符号だけ見ればよい x は x >= !x のときに負
+        [.., Push(0), SLt] => (2, vec![Copy, Not, GrEq]),
+        [.., Push(0), SGrEq] => (2, vec![Copy, Not, GrEq, LNot]),
+
+        [.., Push(a), Copy] if cost(&[Push(*a)]) < cost(&[Copy]) => (1, vec![Push(*a)]),
+        [.., Push(0), Add | Sub | Or | Xor | LShift | RShift] => (2, vec![]),
+        [.., Push(1), Mul | Div] => (2, vec![]),
+        [.., Push(a), x @ (Add | Sub), Push(b), y @ (Add | Sub)] => {
+            let a = if *x == Add { *a } else { a.wrapping_neg() };
+            let b = if *y == Add { *b } else { b.wrapping_neg() };
+            (4, offset(a.wrapping_add(b)))
+        }
+        [.., Negate, Negate] | [.., Not, Not] | [.., Swap, Swap] => (2, vec![]),
+        [.., Negate, Add] => (2, vec![Sub]),
+        [.., Negate, Sub] => (2, vec![Add]),
+
+        // 積んですぐ捨てる
+        [.., Push(_) | Copy, Dealloc(n)] if *n > 0 => (2, vec![Dealloc(n - 1)]),
+        [.., Dealloc(a), Dealloc(b)] => (2, vec![Dealloc(a + b)]),
+        [.., Alloc(a), Alloc(b)] => (2, vec![Alloc(a + b)]),
+        [.., Alloc(a), Dealloc(b)] if a <= b => (2, vec![Dealloc(b - a)]),
+        [.., Alloc(a), Dealloc(b)] => (2, vec![Alloc(a - b)]),
+        [.., Alloc(0) | Dealloc(0)] => (1, vec![]),
+        _ => return None,
+    };
+    Some(found)
+}
+
+// Lt などは符号なし SLt などは符号付き 実行時と同じくここで畳む
+fn fold_binary(op: &StackInst, a: Word, b: Word) -> Option<Word> {
+    use StackInst::*;
+    let value = match op {
+        Add => a.wrapping_add(b),
+        Sub => a.wrapping_sub(b),
+        Mul => a.wrapping_mul(b),
+        Div if b != 0 => a / b,
+        // BF では16以上ずらすと0になる
+        LShift if b < 16 => a << b,
+        RShift if b < 16 => a >> b,
+        And => a & b,
+        Or => a | b,
+        Xor => a ^ b,
+        Eq => (a == b) as Word,
+        Neq => (a != b) as Word,
+        Lt => (a < b) as Word,
+        LtEq => (a <= b) as Word,
+        Gr => (a > b) as Word,
+        GrEq => (a >= b) as Word,
+        SLt => ((a as i16) < (b as i16)) as Word,
+        SLtEq => (a as i16 <= b as i16) as Word,
+        SGr => (a as i16 > b as i16) as Word,
+        SGrEq => (a as i16 >= b as i16) as Word,
+        LAnd => (a != 0 && b != 0) as Word,
+        LOr => (a != 0 || b != 0) as Word,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn fold_unary(op: &StackInst, a: Word) -> Option<Word> {
+    use StackInst::*;
+    let value = match op {
+        Negate => a.wrapping_neg(),
+        Not => !a,
+        LNot => (a == 0) as Word,
+        _ => return None,
+    };
+    Some(value)
+}
+
+// スタックの先頭に value を足す 負の値は引き算にする
+fn offset(value: Word) -> Vec<StackInst> {
+    use StackInst::*;
+    match value {
+        0 => vec![],
+        n if n <= Word::MAX / 2 + 1 => vec![Push(n), Add],
+        n => vec![Push(n.wrapping_neg()), Sub],
+    }
+}
+
+// 畳んだ結果の BF のほうが長くなるなら書き換えない
+fn cheaper(old: &[StackInst], new: Vec<StackInst>) -> Option<(usize, Vec<StackInst>)> {
+    (cost(&new) < cost(old)).then_some((old.len(), new))
+}
+
+// BF にしたときのおおよその長さ
+fn cost(insts: &[StackInst]) -> usize {
+    use StackInst::*;
+    insts
+        .iter()
+        .map(|inst| match inst {
+            Push(n) => 1 + constant_cost(*n),
+            Alloc(n) => *n,
+            Dealloc(n) => 4 * n,
+            Add | Sub | Neq => 7,
+            Negate | Not => 15,
+            _ => 20,
+        })
+        .sum()
+}
+
+#[test]
+fn test_optimize_stack() {
+    use StackInst::*;
+
+    // 実行に影響しない値をいくつか積んでおく
+    let prelude = [Push(7), Push(3), Push(65535), Push(9), Push(2)];
+    let run = |code: &[StackInst]| {
+        let mut program = prelude.to_vec();
+        program.extend_from_slice(code);
+        program.push(Exit);
+        let mut machine = StackMachine::default();
+        machine.exec(&program);
+        machine.stack
+    };
+    let check = |code: &[StackInst]| {
+        let optimized = optimize_stack(code.to_vec());
+        assert_eq!(run(code), run(&optimized), "{:?} → {:?}", code, optimized);
+        optimized
+    };
+
+    let swap = [
+        Push(2),
+        StkRead,
+        Push(2),
+        StkRead,
+        Push(3),
+        StkStr,
+        Push(1),
+        StkStr,
+    ];
+    assert_eq!(check(&swap), [Swap]);
+    // mul(-1)
+    let negate = [
+        Comment("mul_start".to_string()),
+        Copy,
+        Push(0),
+        Push(2),
+        StkStr,
+        Push(1),
+        Mul,
+        Sub,
+        Comment("mul_end".to_string()),
+    ];
+    assert_eq!(
+        check(&negate),
+        [
+            Comment("mul_start".to_string()),
+            Negate,
+            Comment("mul_end".to_string())
+        ]
+    );
+    assert_eq!(check(&[Push(5), Push(3), Sub]), [Push(2)]);
+    // 負の値は - を並べて作る
+    assert_eq!(check(&[Push(2), Push(3), Sub]), [Push(65535)]);
+    assert_eq!(check(&[Push(3), Negate, Negate]), [Push(3)]);
+    assert!(check(&[Push(5), Push(0), Dealloc(2)]).is_empty());
+    assert_eq!(
+        check(&[Push(1), Add, Copy, Push(0), Add]),
+        [Push(1), Add, Copy]
+    );
+    assert_eq!(check(&[Push(1), Add, Push(3), Sub]), [Push(2), Sub]);
+    assert_eq!(check(&[Copy, Push(0), SLt]), [Copy, Copy, Not, GrEq]);
+    for a in [0, 1, 32767, 32768, 65535] {
+        check(&[Push(a), Copy, Push(0), SLt, Swap, Push(0), SGrEq]);
+    }
+    assert_eq!(check(&[Push(1), Copy, Push(1), Add]), [Push(1), Push(2)]);
+    assert_eq!(
+        check(&[Push(4), Copy, Push(0), Push(2), StkStr]),
+        [Push(0), Push(4)]
+    );
+    assert_eq!(
+        check(&[Push(3), StkStr, Dealloc(1), Dealloc(0)]),
+        [Push(3), StkStr, Dealloc(1)]
+    );
+    // 大きな値も掛け算で作るので短い
+    assert_eq!(check(&[Push(300), Push(300), Mul]), [Push(24464)]);
+    // 0除算は実行時まで残す
+    assert_eq!(
+        optimize_stack(vec![Push(1), Push(0), Div]),
+        [Push(1), Push(0), Div]
+    );
+    // 飛び先はまたがない
+    assert_eq!(check(&[Push(1), Label(5), Add]), [Push(1), Label(5), Add]);
+
+    // 合法なスタック操作を適当に並べて比べる
+    let mut seed = 1u32;
+    let mut random = |n: u32| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) % n
+    };
+    for _ in 0..200 {
+        let mut code = vec![];
+        let mut depth = prelude.len();
+        for _ in 0..30 {
+            match random(13) {
+                0 => code.push(Push(random(4) as Word)),
+                1 => code.push(Push(65535 - random(3) as Word)),
+                2 => code.push(Copy),
+                3 if depth >= 2 => {
+                    code.extend_from_slice(&swap);
+                    depth -= 1;
+                }
+                4 if depth >= 2 => {
+                    let ops = [Add, Sub, Mul, Eq, Lt, GrEq, SLt, SGrEq, LAnd, LOr, Xor];
+                    code.push(ops[random(ops.len() as u32) as usize].clone());
+                    depth -= 2;
+                }
+                5 => {
+                    code.extend([Push(1 + random(3) as Word), Div]);
+                    depth -= 1;
+                }
+                6 => {
+                    code.push([Negate, Not, LNot][random(3) as usize].clone());
+                    depth -= 1;
+                }
+                7 => code.extend([Push(1 + random(depth as u32) as Word), StkRead]),
+                8 if depth >= 3 => {
+                    code.extend([Push(1 + random(depth as u32 - 1) as Word), StkStr]);
+                    depth -= 2;
+                }
+                9 if depth >= 3 => {
+                    let n = random(3) as usize;
+                    code.push(Dealloc(n));
+                    depth -= n + 1;
+                }
+                10 => {
+                    let n = random(3) as usize;
+                    code.push(Alloc(n));
+                    depth = depth + n - 1;
+                }
+                11 => code.extend([Copy, Push(0), Push(2), StkStr]),
+                12 => {
+                    code.extend([
+                        Push(random(4) as Word),
+                        [Add, Sub][random(2) as usize].clone(),
+                    ]);
+                    depth -= 1;
+                }
+                _ => depth -= 1,
+            }
+            depth += 1;
+        }
+        check(&code);
+    }
+}
@@ -1,12 +1,18 @@
 use super::*;
+use crate::codegen::r#type::Size;
+use crate::op::BinaryOp;
 use crate::sema::ast::*;
+use crate::visualize::OneLine;
 pub fn stmt(stmt: Stmt, cgs: &mut CodeGenStatus) {
+    if cgs.record_statements && !matches!(stmt, Stmt::Block(_)) {
+        cgs.statements.push((cgs.outputs.len(), stmt.oneline()));
+    }
     match stmt {
         Stmt::Block(block) => self::block(block, cgs),
         Stmt::DeclStmt(declstmt) => self::declstmt(declstmt, cgs),
         Stmt::Control(control) => self::control(control, cgs),
-        Stmt::Break => r#break(cgs),
-        Stmt::Continue => r#continue(cgs),
+        Stmt::Break(_) => r#break(cgs),
+        Stmt::Continue(_) => r#continue(cgs),
         Stmt::Return(ret) => r#return(ret, cgs),
         Stmt::Goto(goto) => self::goto(goto, cgs),
         Stmt::Label(label) => self::label(label, cgs),
@@ -35,6 +41,13 @@ fn declstmt(declstmt: DeclStmt, cgs: &mut CodeGenStatus) {
                 declare_variable(init, cgs);
             }
         }
+        DeclStmt::Static(inits) => {
+            // 関数を抜けても値を保つためグローバル領域に置く
+            // Symbolはスコープごとに区別されるので関数間で衝突しない
+            inits
+                .into_iter()
+                .for_each(|init| register_global(init, cgs));
+        }
         DeclStmt::Typedef(_) => {}
         _ => {}
     }
@@ -63,7 +76,7 @@ fn initialize_variable(init_data: InitData, var_type: &Type, cgs: &mut CodeGenSt
         }
         InitData::Compound(com) => {
             // 複合初期化子 {1, 2, 3}
-            match var_type {
+            match &var_type.unqualified() {
                 Type::Array(arr) => {
                     (0..com.len())
                         .rev()
@@ -74,8 +87,17 @@ fn initialize_variable(init_data: InitData, var_type: &Type, cgs: &mut CodeGenSt
                         initialize_variable(com[i].clone(), &st.member[i].get_type().unwrap(), cgs)
                     });
                 }
-                Type::Union(_) => {
-                    panic!("共用体の複合初期化は未対応");
+                Type::Union(un) => {
+                    // 先頭メンバだけを初期化し，残りは0で埋める
+                    // 先頭メンバがオフセット0(一番上)に来るように余りを先に積む
+                    let first = com.first().map(|_| un.member[0].get_type().unwrap());
+                    let padding = un.size() - first.as_ref().map_or(0, |ty| ty.size());
+                    for _ in 0..padding {
+                        cgs.outputs.push(StackCommand::Push(0.into()));
+                    }
+                    if let Some(ty) = first {
+                        initialize_variable(com[0].clone(), &ty, cgs);
+                    }
                 }
                 _ => {
                     panic!("複合初期化子が使用できない型です");
@@ -85,6 +107,64 @@ fn initialize_variable(init_data: InitData, var_type: &Type, cgs: &mut CodeGenSt
     }
 }
 
+// ファイルスコープの宣言 初期値は型検査で定数と確認済みなので畳み込んでおく
+pub fn global(stmt: Stmt, cgs: &mut CodeGenStatus) {
+    if let Stmt::DeclStmt(DeclStmt::InitVec(inits) | DeclStmt::Static(inits)) = stmt {
+        inits
+            .into_iter()
+            .for_each(|init| register_global(init, cgs));
+    }
+}
+
+fn register_global(init: Init, cgs: &mut CodeGenStatus) {
+    let var_type = init.l.get_type().unwrap();
+    let cells = match &init.r {
+        Some(init_data) => fold_initializer(init_data, &var_type),
+        None => vec![0; var_type.size()],
+    };
+    cgs.global_symbols.insert(init.l.clone());
+    cgs.globals.push((init.l, cells));
+}
+
+// initialize_variableがスタックに積むのと同じ並び(下から順)で値を返す
+// 要素が足りない分は下側を0で埋める
+fn fold_initializer(init_data: &InitData, var_type: &Type) -> Vec<usize> {
+    let mut cells = match init_data {
+        InitData::Expr(typed_expr) => match &typed_expr.expr {
+            SemaExpr::String(s) => s.iter().rev().map(|&c| c as usize).collect(),
+            _ => vec![
+                typed_expr
+                    .clone()
+                    .eval_const()
+                    .expect("グローバル変数の初期化子が定数ではありません")
+                    .rem_euclid(1 << 16) as usize,
+            ],
+        },
+        InitData::Compound(com) => match var_type.unqualified() {
+            Type::Array(arr) => com
+                .iter()
+                .rev()
+                .flat_map(|x| fold_initializer(x, &arr.array_of))
+                .collect(),
+            Type::Struct(st) => com
+                .iter()
+                .zip(&st.member)
+                .rev()
+                .flat_map(|(x, m)| fold_initializer(x, &m.get_type().unwrap()))
+                .collect(),
+            Type::Union(un) => com
+                .first()
+                .map(|x| fold_initializer(x, &un.member[0].get_type().unwrap()))
+                .unwrap_or_default(),
+            _ => panic!("複合初期化子が使用できない型です"),
+        },
+    };
+
+    let padding = var_type.size().saturating_sub(cells.len());
+    cells.splice(0..0, std::iter::repeat_n(0, padding));
+    cells
+}
+
 fn control(control: Control, cgs: &mut CodeGenStatus) {
     match control {
         Control::If(if_stmt) => controls::r#if(if_stmt, cgs),
@@ -123,13 +203,18 @@ fn r#return(ret: Return, cgs: &mut CodeGenStatus) {
     cgs.outputs.push(StackCommand::FramePop);
 }
 
-fn goto(goto: Goto, _cgs: &mut CodeGenStatus) {
-    println!("  br label %{}", goto.label.get_name());
+fn goto(goto: Goto, cgs: &mut CodeGenStatus) {
+    // 宣言を飛び越えるジャンプは型検査で弾いているので，解放だけで深さが合う
+    let target = cgs.user_label(&goto.label);
+    cgs.outputs.push(StackCommand::ClearStackTo(target));
+    cgs.outputs.push(StackCommand::Goto(target));
+    cgs.outputs.push(StackCommand::Label(cgs.name_gen.slabel())); //未到達空間回避
 }
 
 fn label(label: Label, cgs: &mut CodeGenStatus) {
-    println!("br label %{}", label.name.get_name());
-    println!("{}:", label.name.get_name());
+    let this = cgs.user_label(&label.name);
+    cgs.outputs.push(StackCommand::Goto(this));
+    cgs.outputs.push(StackCommand::Label(this));
     stmt(*label.stmt, cgs);
 }
 
@@ -160,8 +245,33 @@ mod controls {
         );
     }
 
-    pub fn r#do_while(_do_while_stmt: DoWhile, _cgs: &mut CodeGenStatus) {
-        // TODO
+    pub fn r#do_while(do_while_stmt: DoWhile, cgs: &mut CodeGenStatus) {
+        let DoWhile { body, cond } = do_while_stmt;
+        let label_start = cgs.name_gen.slabel();
+        let label_cond = cgs.name_gen.slabel();
+        let label_end = cgs.name_gen.slabel();
+        cgs.break_stack.push((label_end, label_start));
+        cgs.continue_stack.push((label_start, label_cond));
+
+        {
+            cgs.outputs.push(StackCommand::Goto(label_start));
+        }
+
+        // ループ本体 (最初の一回は無条件に実行)
+        cgs.outputs.push(label_start.into());
+        stmt(*body, cgs);
+        cgs.outputs.push(StackCommand::Goto(label_cond));
+
+        // 条件が真なら本体へ、偽なら終了
+        cgs.outputs.push(label_cond.into());
+        gen_expr(*cond, cgs);
+        cgs.outputs
+            .push(StackCommand::Branch(label_start, label_end));
+
+        // ループ終了
+        cgs.outputs.push(label_end.into());
+        cgs.break_stack.pop();
+        cgs.continue_stack.pop();
     }
 
     pub fn r#for(for_stmt: For, cgs: &mut CodeGenStatus) {
@@ -220,7 +330,81 @@ mod controls {
         cgs.continue_stack.pop();
     }
 
-    pub fn r#switch(_switch_stmt: Switch, _cgs: &mut CodeGenStatus) {
-        // TODO
+    pub fn r#switch(switch_stmt: Switch, cgs: &mut CodeGenStatus) {
+        let Switch { cond, cases } = switch_stmt;
+        let ty = cond.r#type.clone();
+
+        let label_block = cgs.name_gen.slabel();
+        let label_dispatch = cgs.name_gen.slabel();
+        let label_end = cgs.name_gen.slabel();
+        let case_labels: Vec<SLabel> = cases.iter().map(|_| cgs.name_gen.slabel()).collect();
+
+        // 条件式は一度だけ評価して隠れ変数に退避する
+        cgs.outputs.push(StackCommand::BlockStart(label_block));
+        let tmp = cgs.temp_symbol(ty.clone());
+        cgs.outputs.push(StackCommand::Alloc(ty.clone()));
+        cgs.outputs.push(StackCommand::Name(tmp.clone()));
+        gen_expr(*cond, cgs);
+        cgs.outputs.push(StackCommand::Symbol(tmp.clone()));
+        cgs.outputs.push(StackCommand::AcsessUseLa);
+        cgs.outputs.push(StackCommand::Store(ty.clone()));
+
+        // breakではここより後に確保したものだけを削除する
+        cgs.outputs.push(StackCommand::Goto(label_dispatch));
+        cgs.outputs.push(StackCommand::Label(label_dispatch));
+
+        // 上から順に比較して一致したcaseへ飛ぶ
+        let mut label_default = None;
+        for (case, label) in cases.iter().zip(&case_labels) {
+            match case {
+                SwitchCase::Case(case) => {
+                    let label_next = cgs.name_gen.slabel();
+                    cgs.outputs.push(StackCommand::Symbol(tmp.clone()));
+                    cgs.outputs.push(StackCommand::AcsessUseLa);
+                    cgs.outputs.push(StackCommand::Load(ty.clone()));
+                    gen_expr(case.const_expr.clone(), cgs);
+                    cgs.outputs.push(BinaryOp::equal_equal().into());
+                    cgs.outputs.push(StackCommand::Branch(*label, label_next));
+                    cgs.outputs.push(StackCommand::Label(label_next));
+                }
+                SwitchCase::Default(_) => label_default = Some(*label),
+            }
+        }
+        cgs.outputs
+            .push(StackCommand::Goto(label_default.unwrap_or(label_end)));
+
+        cgs.break_stack.push((label_end, label_dispatch));
+
+        let next_labels: Vec<SLabel> = case_labels
+            .iter()
+            .skip(1)
+            .copied()
+            .chain(std::iter::once(label_end))
+            .collect();
+
+        for ((case, label), next) in cases.into_iter().zip(case_labels).zip(next_labels) {
+            let stmts = match case {
+                SwitchCase::Case(case) => case.stmts,
+                SwitchCase::Default(default) => default.stmts,
+            };
+
+            cgs.outputs.push(StackCommand::Label(label));
+
+            // 次のcaseへ落ちる前に，このcase内で宣言したものを解放する
+            let label_body = cgs.name_gen.slabel();
+            cgs.outputs.push(StackCommand::BlockStart(label_body));
+            for s in stmts {
+                stmt(*s, cgs);
+            }
+            cgs.outputs.push(StackCommand::BlockEnd(label_body));
+
+            // fall-through
+            cgs.outputs.push(StackCommand::Goto(next));
+        }
+
+        cgs.outputs.push(StackCommand::Label(label_end));
+        cgs.break_stack.pop();
+
+        cgs.outputs.push(StackCommand::BlockEnd(label_block));
     }
 }
@@ -22,6 +22,7 @@ impl Size for Type {
             Type::Union(u) => u.size(),     // 共用体のサイズ取得
             Type::Enum(e) => e.size(),      // enumのサイズ取得
             Type::Typedef(this) => this.size(),
+            Type::Const(inner) => inner.size(),
         }
     }
 }
@@ -2,7 +2,8 @@ use crate::op::*;
 use crate::sema::ast::*;
 use core::str;
 use ordered_float::OrderedFloat;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
@@ -26,8 +27,11 @@ pub enum StackCommand {
     Comment(String),    // コメント
     Push(TypedExpr),    // スタックに値を乗せる
     BinaryOP(BinaryOp), // 二項演算子
+    // int 同士の大小比較 符号付きで比べる
+    SignedCompare(Comparison),
     UnaryOp(UnaryOp),
     Symbol(Symbol),                 //変数のアドレスをスタックに乗せる
+    GlobalSymbol(Symbol),           //グローバル変数のグローバルアドレスをスタックに乗せる
     Name(Symbol),                   // 変数名をスタックに乗せる下のAlloca命令と組み合わせて使う
     Alloc(Type),                    //型のサイズだけメモリ確保
     Store(Type),                    //　計算結果が下　対象は上
@@ -50,6 +54,7 @@ pub enum StackCommand {
     BlockEnd(SLabel),               //ブロック終了
     Pop(Type),                      //型のサイズだけスタックを削除
     ClearStackFrom(SLabel),         // Slabelまでのsatckを削除
+    ClearStackTo(SLabel),           // Slabel地点の深さになるまでstackを削除 goto用
     MemberAccess(Vec<Type>, usize), // メンバアクセス 型リストとメンバのインデックス
 }
 
@@ -59,8 +64,10 @@ impl std::fmt::Debug for StackCommand {
         match self {
             StackCommand::Push(this) => write!(f, "Push {}", this.oneline()),
             StackCommand::BinaryOP(this) => write!(f, "BinaryOP {:?}", this),
+            StackCommand::SignedCompare(this) => write!(f, "SignedCompare {:?}", this),
             StackCommand::UnaryOp(this) => write!(f, "UnaryOp{:?}", this),
             StackCommand::Symbol(this) => write!(f, "Symbol {}", this.oneline()),
+            StackCommand::GlobalSymbol(this) => write!(f, "GlobalSymbol {}", this.oneline()),
             StackCommand::Alloc(this) => write!(f, "Alloca {}", this.to_rust_format()),
             StackCommand::Store(this) => write!(f, "Store {}", this.to_rust_format()),
             StackCommand::Load(ty) => write!(f, "Load {}", ty.to_rust_format()),
@@ -84,6 +91,7 @@ impl std::fmt::Debug for StackCommand {
             StackCommand::BlockEnd(this) => write!(f, "BlockEnd {:?}", this),
             StackCommand::Pop(ty) => write!(f, "Pop {}", ty.to_rust_format()),
             StackCommand::ClearStackFrom(this) => write!(f, "ClearStackFrom {:?}", this),
+            StackCommand::ClearStackTo(this) => write!(f, "ClearStackTo {:?}", this),
             StackCommand::MemberAccess(ty, id) => {
                 write!(f, "MemberAccess (types: {:?}, id: {})", ty, id)
             }
@@ -97,6 +105,7 @@ pub struct SFunc {
     pub param_names: Vec<Symbol>,
     pub body: Vec<StackCommand>,
     pub entry: SLabel,
+    pub statements: Vec<(usize, String)>, // 文が始まるbodyの位置と文 ダンプ用
 }
 
 impl SFunc {
@@ -111,6 +120,43 @@ impl SFunc {
             param_names,
             body,
             entry,
+            statements: Vec::new(),
+        }
+    }
+}
+
+/// Goto や Branch や関数呼び出しで飛んでくるラベル 予約済みのラベルも含む
+/// これ以外のラベルは前のブロックから続けて入るだけ
+pub fn jump_targets(funcs: &[SFunc]) -> HashSet<SLabel> {
+    let mut targets: HashSet<SLabel> = SLabelReserved::iter().map(SLabel::from).collect();
+    for func in funcs {
+        targets.insert(func.entry);
+        for command in &func.body {
+            match command {
+                StackCommand::Goto(x) | StackCommand::ReturnPoint(x) => {
+                    targets.insert(*x);
+                }
+                StackCommand::Branch(a, b) => targets.extend([*a, *b]),
+                _ => {}
+            }
+        }
+    }
+    targets
+}
+
+impl StackCommand {
+    /// 命令が持っている SLabel 飛び先だけでなくブロックの id も含む
+    pub fn labels_mut(&mut self) -> Vec<&mut SLabel> {
+        match self {
+            StackCommand::Label(x)
+            | StackCommand::Goto(x)
+            | StackCommand::ReturnPoint(x)
+            | StackCommand::BlockStart(x)
+            | StackCommand::BlockEnd(x)
+            | StackCommand::ClearStackFrom(x)
+            | StackCommand::ClearStackTo(x) => vec![x],
+            StackCommand::Branch(a, b) => vec![a, b],
+            _ => vec![],
         }
     }
 }
@@ -135,14 +181,6 @@ use strum_macros::EnumString;
 
 #[derive(EnumString, Debug, Eq, Hash, PartialEq, Clone)]
 pub enum InsertFunction {
-    #[strum(serialize = "Greater")]
-    Greater,
-    #[strum(serialize = "Less")]
-    Less,
-    #[strum(serialize = "LessEqual")]
-    LessEqual,
-    #[strum(serialize = "GreaterEqual")]
-    GreaterEqual,
     #[strum(serialize = "Ternary")]
     Ternary,
     #[strum(serialize = "Slash")]
@@ -151,8 +189,6 @@ pub enum InsertFunction {
     Mod,
     #[strum(serialize = "Not")]
     Not,
-    #[strum(serialize = "Land")]
-    Land,
     #[strum(serialize = "print_int")]
     PrintInt,
     #[strum(serialize = "print_double")]
@@ -180,10 +216,17 @@ pub struct CodeGenStatus {
     pub name_gen: NameGenerator,
     pub outputs: Vec<StackCommand>,
     pub func_end: Option<SLabel>,
+    pub func_scope: Option<ScopePtr>,
     pub funcs: Vec<SFunc>,
     pub break_stack: Vec<(SLabel, SLabel)>, // (delete from, goto)
     pub continue_stack: Vec<(SLabel, SLabel)>, // (delete from, goto)
+    pub user_labels: HashMap<Ident, SLabel>, // gotoで使うラベル 関数ごと
+    pub globals: Vec<(Symbol, Vec<usize>)>, // グローバル変数と初期値(下から順)
+    pub global_symbols: HashSet<Symbol>,    // globals の変数 引くとき用
     pub insert_function: HashMap<InsertFunction, Symbol>,
+    pub statements: Vec<(usize, String)>, // 関数内の文の開始位置 ダンプ用
+    pub record_statements: bool,          // statements を残すか
+    pub label_names: HashMap<SLabel, String>, // ダンプ用のラベル名
 }
 
 impl Block {
@@ -192,24 +235,66 @@ impl Block {
     }
 }
 
+impl Default for CodeGenStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeGenStatus {
     pub fn new() -> Self {
         Self {
             name_gen: NameGenerator::new(),
             outputs: Vec::new(),
             func_end: None,
+            func_scope: None,
             funcs: Vec::new(),
             break_stack: Vec::new(),
             continue_stack: Vec::new(),
+            user_labels: HashMap::new(),
+            globals: Vec::new(),
+            global_symbols: HashSet::new(),
             insert_function: HashMap::new(),
+            statements: Vec::new(),
+            record_statements: false,
+            label_names: HashMap::new(),
         }
     }
 }
 
+impl CodeGenStatus {
+    // コード生成用の隠れ変数　関数スコープの子に登録するので他と衝突しない
+    pub fn temp_symbol(&mut self, ty: Type) -> Symbol {
+        let parent = self.func_scope.as_ref().unwrap().get_scope().unwrap();
+        let child = ScopeNode::add_child(&parent);
+        let ident: Ident = format!("tmp..{}", self.name_gen.slabel().0).into();
+        child.borrow_mut().register_symbols(ident.clone(), ty);
+        Symbol::new(ident, ScopePtr::new(Rc::downgrade(&child)))
+    }
+
+    pub fn is_global(&self, symbol: &Symbol) -> bool {
+        self.global_symbols.contains(symbol)
+    }
+
+    // ラベル名に対応するSLabel 前方参照もあるので初出時に作る
+    pub fn user_label(&mut self, ident: &Ident) -> SLabel {
+        *self
+            .user_labels
+            .entry(ident.clone())
+            .or_insert_with(|| self.name_gen.slabel())
+    }
+}
+
 pub struct NameGenerator {
     counter: usize,
 }
 
+impl Default for NameGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NameGenerator {
     // 1はentry_point
     // 2はexit_point
@@ -285,6 +370,7 @@ impl From<Symbol> for TypedExpr {
         Self {
             r#type: this.get_type().unwrap(),
             expr: SemaExpr::Symbol(this),
+            span: None,
         }
     }
 }
@@ -1,50 +1,219 @@
+use crate::codegen::stack::Word;
+use crate::span::{LineMap, Span};
 use crate::token::{self, Keyword};
 use token::Token;
 
-fn parse_c_string_literal(s: &str) -> Vec<char> {
+/// 字句解析のエラー その部分を読み飛ばして続けるので複数出ることがある
+#[derive(Debug, Clone)]
+pub struct LexError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// 引用符の中身のエスケープを解く 不正なエスケープはメッセージを返す
+fn parse_c_string_literal(s: &str) -> Result<Vec<char>, String> {
     let mut result = Vec::new();
     let mut chars = s.chars().peekable();
 
     while let Some(c) = chars.next() {
-        if c == '\\' {
-            // エスケープ文字
-            if let Some(next) = chars.next() {
-                let esc_char = match next {
-                    'n' => '\n',
-                    't' => '\t',
-                    'r' => '\r',
-                    '0' => '\0',
-                    '\\' => '\\',
-                    '\'' => '\'',
-                    '\"' => '\"',
-                    'x' => {
-                        // \xNN の処理
-                        let mut hex = String::new();
-                        while let Some(&c) = chars.peek() {
-                            if c.is_ascii_hexdigit() {
-                                hex.push(c);
-                                chars.next(); // 消費
-                            } else {
-                                break;
-                            }
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        // エスケープ文字
+        let Some(next) = chars.next() else {
+            break;
+        };
+        let esc_char = match next {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            'e' => '\x1b',
+            '0'..='7' => {
+                // \ooo 8進数は3桁まで
+                let mut val = next.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            val = val * 8 + d;
+                            chars.next();
                         }
-                        let val = u32::from_str_radix(&hex, 16).expect("invalid hex escape");
-                        std::char::from_u32(val).expect("invalid codepoint")
+                        None => break,
                     }
-                    other => other, // 未知のエスケープ → そのまま
-                };
-                result.push(esc_char);
+                }
+                std::char::from_u32(val).unwrap() // 3桁までなので必ず収まる
+            }
+            'x' => {
+                // \xNN の処理
+                let mut hex = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_hexdigit() {
+                        hex.push(c);
+                        chars.next(); // 消費
+                    } else {
+                        break;
+                    }
+                }
+                if hex.is_empty() {
+                    return Err("\\x used with no following hex digits".to_string());
+                }
+                match u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                {
+                    Some(c) => c,
+                    None => return Err(format!("hex escape sequence \\x{} out of range", hex)),
+                }
             }
+            other => other, // \\ \' \" \? と未知のエスケープ → そのまま
+        };
+        result.push(esc_char);
+    }
+
+    Ok(result)
+}
+
+/// 引用符で始まるリテラルの閉じ引用符までの長さ（バイト単位）
+/// 行末までに閉じていなければNone
+pub fn literal_len(input: &str) -> Option<usize> {
+    let mut chars = input.char_indices();
+    let (_, quote) = chars.next()?;
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\n' => return None,
+            c if c == quote => return Some(i + c.len_utf8()),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 数値リテラルの長さ 1e-3 のような指数の符号も含める
+fn number_len(input: &str) -> usize {
+    let bytes = input.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if matches!(bytes[i], b'e' | b'E') && matches!(bytes.get(i + 1), Some(b'+' | b'-')) {
+            i += 2;
+        } else if bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.' {
+            i += 1;
         } else {
-            result.push(c);
+            break;
         }
     }
+    i
+}
+
+/// 数値リテラルを読む
+/// 0x 0b 0o / 0 で始まる8進数を受け付ける 16bitに収まらない値はここで弾く
+/// intしか整数型がないので u l の接尾辞は型に反映できない 黙って無視せずエラーにする
+fn parse_number(text: &str) -> Result<Token, String> {
+    let lower = text.to_ascii_lowercase();
+    let (radix, body) = if let Some(rest) = lower.strip_prefix("0x") {
+        (16, rest)
+    } else if let Some(rest) = lower.strip_prefix("0b") {
+        (2, rest)
+    } else if let Some(rest) = lower.strip_prefix("0o") {
+        (8, rest)
+    } else if lower.contains(['.', 'e']) {
+        // 浮動小数点数 接尾辞は f か l
+        let body = lower.strip_suffix(['f', 'l']).unwrap_or(&lower);
+        return match body.parse::<f64>() {
+            Ok(value) => Ok(Token::NumFloat(value.into())),
+            Err(_) => Err(format!("invalid floating constant '{}'", text)),
+        };
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        (8, &lower[1..])
+    } else {
+        (10, lower.as_str())
+    };
+
+    let digits_len = body
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(body.len());
+    let (digits, suffix) = body.split_at(digits_len);
+    if let Some(digit) = suffix.chars().next().filter(|c| c.is_ascii_digit()) {
+        let kind = if radix == 2 { "binary" } else { "octal" };
+        return Err(format!("invalid digit '{}' in {} constant", digit, kind));
+    }
+    let valid_suffix = matches!(suffix, "" | "u" | "l" | "ll" | "ul" | "lu" | "ull" | "llu");
+    if (digits.is_empty() && radix != 8) || !valid_suffix {
+        return Err(format!("invalid integer constant '{}'", text));
+    }
+    if !suffix.is_empty() {
+        return Err(format!(
+            "unsupported suffix '{}' on integer constant",
+            &text[text.len() - suffix.len()..]
+        ));
+    }
 
-    result
+    let value = digits.chars().try_fold(0usize, |acc, c| {
+        acc.checked_mul(radix as usize)?
+            .checked_add(c.to_digit(radix).unwrap() as usize)
+            .filter(|&value| value <= Word::MAX as usize)
+    });
+    match value {
+        Some(value) => Ok(Token::NumInt(value)),
+        None => Err(format!(
+            "integer constant '{}' does not fit in 16 bits",
+            text
+        )),
+    }
 }
 
-pub fn tokenize(input: &str) -> Vec<Token> {
+/// 空白とコメントを読み飛ばす
+/// 閉じていないコメントがあればその開始位置を Err で返す
+fn skip_trivia(mut input: &str) -> Result<&str, &str> {
+    loop {
+        input = input.trim_start();
+        if let Some(rest) = input.strip_prefix("//") {
+            input = rest.find('\n').map_or("", |i| &rest[i..]);
+        } else if let Some(rest) = input.strip_prefix("/*") {
+            let Some(end) = rest.find("*/") else {
+                return Err(input);
+            };
+            input = &rest[end + 2..];
+        } else {
+            return Ok(input);
+        }
+    }
+}
+
+/// トークン列と，それぞれのトークンのソース上の位置を返す
+/// エラーがあっても最後まで読んで全部返す
+pub fn tokenize(input: &str) -> Result<(Vec<Token>, Vec<Span>), Vec<LexError>> {
     let mut tokens = Vec::new();
+    let mut spans = Vec::new();
+    let mut errors = Vec::new();
+
+    // inputは常にsourceの末尾部分なので，残りの長さから位置がわかる
+    let source = input.trim_end();
+    let line_map = LineMap::new(source);
+    let span_at = |rest: &str, len: usize| line_map.span(source.len() - rest.len(), len);
+    let mut push = |token: Token, rest: &str, len: usize| {
+        tokens.push(token);
+        spans.push(span_at(rest, len));
+    };
+    let mut error = |message: String, rest: &str, len: usize| {
+        errors.push(LexError {
+            message,
+            span: span_at(rest, len),
+        });
+    };
 
     let symbols_sorted: Vec<&str> = {
         let mut syms: Vec<_> = Token::SYMBOLS.iter().map(|x| x.0).collect();
@@ -52,28 +221,26 @@ pub fn tokenize(input: &str) -> Vec<Token> {
         syms
     };
 
-    let mut input = input.trim();
+    let mut input = source;
     while !input.is_empty() {
-        input = input.trim_start();
+        input = match skip_trivia(input) {
+            Ok(rest) => rest,
+            Err(comment) => {
+                error("unterminated comment".to_string(), comment, 2);
+                break;
+            }
+        };
         if let Some(first) = input.chars().next() {
-            // 数字
-            if first.is_ascii_digit() {
-                let num_str: String = input.chars().take_while(|c| c.is_ascii_digit()).collect();
-                input = &input[num_str.len()..];
-
-                if input.starts_with('.') {
-                    input = &input[1..];
-                    let num_str2: String =
-                        input.chars().take_while(|c| c.is_ascii_digit()).collect();
-                    input = &input[num_str2.len()..];
-
-                    tokens.push(Token::NumFloat(
-                        format!("{}.{}", num_str, num_str2).parse().unwrap(),
-                    ));
-                } else {
-                    tokens.push(Token::NumInt(num_str.parse().unwrap()));
+            // 数字 .5 のように小数点から始まるものも
+            if first.is_ascii_digit()
+                || (first == '.' && input[1..].starts_with(|c: char| c.is_ascii_digit()))
+            {
+                let len = number_len(input);
+                match parse_number(&input[..len]) {
+                    Ok(token) => push(token, input, len),
+                    Err(message) => error(message, input, len),
                 }
-
+                input = &input[len..];
                 continue;
             }
 
@@ -81,7 +248,7 @@ pub fn tokenize(input: &str) -> Vec<Token> {
             let mut matched = false;
             for &sym in &symbols_sorted {
                 if input.starts_with(sym) {
-                    tokens.push(Token::classify(&sym.to_string()).unwrap());
+                    push(Token::classify(&sym.to_string()).unwrap(), input, sym.len());
                     input = &input[sym.len()..];
                     matched = true;
                     break;
@@ -91,90 +258,202 @@ pub fn tokenize(input: &str) -> Vec<Token> {
                 continue;
             }
 
-            // 文字リテラル
-            if input.starts_with('\'') {
-                let mut end = 1;
-                let mut escaped = false;
-
-                while end < input.len() {
-                    let c = input.chars().nth(end).unwrap();
-
-                    if escaped {
-                        escaped = false;
-                    } else if c == '\\' {
-                        escaped = true;
-                    } else if c == '\'' {
-                        break;
-                    }
-
-                    end += 1;
-                }
-
-                if end >= input.len() || input.chars().nth(end).unwrap() != '\'' {
-                    panic!("unterminated string literal");
-                }
-
-                let content = parse_c_string_literal(&input[1..end]);
-                tokens.push(Token::Char(content[0]));
-                input = &input[end + 1..];
-                continue;
-            }
-
-            if input.starts_with('"') {
-                let mut end = 1;
-                let mut escaped = false;
-
-                while end < input.len() {
-                    let c = input.chars().nth(end).unwrap();
-
-                    if escaped {
-                        escaped = false;
-                    } else if c == '\\' {
-                        escaped = true;
-                    } else if c == '"' {
-                        break;
-                    }
-
-                    end += 1;
-                }
-
-                if end >= input.len() || input.chars().nth(end).unwrap() != '"' {
-                    panic!("unterminated string literal");
+            // 文字リテラルと文字列リテラル
+            if first == '\'' || first == '"' {
+                let Some(len) = literal_len(input) else {
+                    // 行の残りを読み飛ばす
+                    let len = input.find('\n').unwrap_or(input.len());
+                    let kind = if first == '"' { "string" } else { "character" };
+                    error(
+                        format!("missing terminating {} for {} literal", first, kind),
+                        input,
+                        len,
+                    );
+                    input = &input[len..];
+                    continue;
+                };
+                match parse_c_string_literal(&input[1..len - 1]) {
+                    Ok(content) if first == '"' => push(Token::String(content), input, len),
+                    Ok(content) if content.len() == 1 => push(Token::Char(content[0]), input, len),
+                    Ok(_) => error(
+                        "character literal must contain exactly one character".to_string(),
+                        input,
+                        len,
+                    ),
+                    Err(message) => error(message, input, len),
                 }
-
-                let content = parse_c_string_literal(&input[1..end]);
-                tokens.push(Token::String(content));
-                input = &input[end + 1..];
+                input = &input[len..];
                 continue;
             }
 
             // 識別子
-            if first.is_alphabetic() {
+            if first.is_ascii_alphabetic() || first == '_' {
                 let can_ident =
                     |c: &char| c.is_ascii_alphabetic() || c.is_ascii_digit() || *c == '_';
                 let ident_str: String = input.chars().take_while(|c| can_ident(c)).collect();
                 let str_len = ident_str.len();
 
                 if let Some(a) = Keyword::classify(&ident_str) {
-                    tokens.push(Token::Keyword(a));
+                    push(Token::Keyword(a), input, str_len);
                 } else {
-                    tokens.push(Token::Ident(ident_str.to_string()));
+                    push(Token::Ident(ident_str.to_string()), input, str_len);
                 }
                 input = &input[str_len..];
                 continue;
             }
 
-            panic!("Unexpected character: {}", first);
+            error(
+                format!("unexpected character '{}'", first),
+                input,
+                first.len_utf8(),
+            );
+            input = &input[first.len_utf8()..];
         }
     }
 
-    tokens
+    if errors.is_empty() {
+        Ok((tokens, spans))
+    } else {
+        Err(errors)
+    }
 }
 
 #[test]
 fn test_tokenize() {
     println!(
         "{:?}",
-        tokenize("int main() { char c = '\\x41'; char d = '\\n'; char e = 'a'; }")
+        tokenize("int main() { char c = '\\x41'; char d = '\\n'; char e = 'a'; }").unwrap()
+    );
+}
+
+#[test]
+fn test_comments_and_escapes() {
+    let (tokens, _) = tokenize(
+        "\"http://x/*y*/\" // c\n/* '\"' */ '\\101' '\\x41' '\\a' '\\e' '\\0' \"\\12\\x7e\\b\"",
+    )
+    .unwrap();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::String("http://x/*y*/".chars().collect()),
+            Token::Char('A'),
+            Token::Char('A'),
+            Token::Char('\x07'),
+            Token::Char('\x1b'),
+            Token::Char('\0'),
+            Token::String(vec!['\n', '~', '\x08']),
+        ]
+    );
+}
+
+#[test]
+fn test_numbers() {
+    let (tokens, _) = tokenize("0xFF 0o17 017 0b1010 0 1.5e2 .5 2e-1f 0xFFFF").unwrap();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::NumInt(255),
+            Token::NumInt(15),
+            Token::NumInt(15),
+            Token::NumInt(10),
+            Token::NumInt(0),
+            Token::NumFloat(150.0.into()),
+            Token::NumFloat(0.5.into()),
+            Token::NumFloat(0.2.into()),
+            Token::NumInt(65535),
+        ]
+    );
+
+    let errors =
+        tokenize("09 0b12 0b 12abc 1.2.3 0x10000 99999999999999999999 42u 0x7UL 0l").unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span.column, e.span.len))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("invalid digit '9' in octal constant", 1, 2),
+            ("invalid digit '2' in binary constant", 4, 4),
+            ("invalid integer constant '0b'", 9, 2),
+            ("invalid integer constant '12abc'", 12, 5),
+            ("invalid floating constant '1.2.3'", 18, 5),
+            ("integer constant '0x10000' does not fit in 16 bits", 24, 7),
+            (
+                "integer constant '99999999999999999999' does not fit in 16 bits",
+                32,
+                20
+            ),
+            ("unsupported suffix 'u' on integer constant", 53, 3),
+            ("unsupported suffix 'UL' on integer constant", 57, 5),
+            ("unsupported suffix 'l' on integer constant", 63, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_lex_errors() {
+    // 識別子は ASCII の英字か _ で始まる それ以外の文字は読み飛ばして報告する
+    let (tokens, _) = tokenize("int _x = __y1;").unwrap();
+    assert_eq!(tokens[1], Token::Ident("_x".to_string()));
+    assert_eq!(tokens[3], Token::Ident("__y1".to_string()));
+    let errors = tokenize("int é; int ü1 = 0;").unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span.column, e.span.len))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("unexpected character 'é'", 5, 1),
+            ("unexpected character 'ü'", 12, 1)
+        ]
+    );
+
+    let errors = tokenize("int a @;\n/* ").unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.span.line.get(), e.span.column))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("unexpected character '@'", 1, 7),
+            ("unterminated comment", 2, 1)
+        ]
+    );
+
+    let errors = tokenize("'ab' '' '\\x' \"\\x110000\" 'c\n\"abc").unwrap_err();
+    let found: Vec<_> = errors
+        .iter()
+        .map(|e| {
+            (
+                e.message.as_str(),
+                e.span.line.get(),
+                e.span.column,
+                e.span.len,
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (
+                "character literal must contain exactly one character",
+                1,
+                1,
+                4
+            ),
+            (
+                "character literal must contain exactly one character",
+                1,
+                6,
+                2
+            ),
+            ("\\x used with no following hex digits", 1, 9, 4),
+            ("hex escape sequence \\x110000 out of range", 1, 14, 10),
+            ("missing terminating ' for character literal", 1, 25, 2),
+            ("missing terminating \" for string literal", 2, 1, 4),
+        ]
     );
 }
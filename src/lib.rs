@@ -0,0 +1,252 @@
+//! C風の言語からBrainfuckへのコンパイラ
+//!
+//! ```
+//! use cynops::codegen::bf::{self, RunConfig};
+//!
+//! let source = "#include <print.h>\nint main(void) { print_int(6 * 7); return 0; }\n";
+//! let artifact = match cynops::compile(source, &cynops::Options::default()) {
+//!     Ok(artifact) => artifact,
+//!     Err(diagnostics) => panic!("{}", diagnostics),
+//! };
+//!
+//! let generated = artifact.codegen.unwrap();
+//! let mut output = Vec::new();
+//! bf::run(&generated.code, &RunConfig::default(), &mut &b""[..], &mut output).unwrap();
+//! assert_eq!(output, b"42");
+//! ```
+use std::fmt;
+use std::path::PathBuf;
+
+pub mod ast;
+pub mod codegen;
+mod lexer;
+pub mod op;
+mod parser;
+pub mod preprocessor;
+pub mod sema;
+pub mod span;
+mod token;
+mod typelib;
+pub mod visualize;
+
+use codegen::Generated;
+use preprocessor::Preprocessed;
+use span::Span;
+
+/// コンパイルの段階 Options::stop_after でどこまで進めるかを決める
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Stage {
+    Preprocess,
+    Parse,
+    Simplify,
+    Convert,
+    Type,
+    #[default]
+    Codegen,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// 診断に出すファイル名 相対パスの #include もここから探す
+    pub name: String,
+    /// 同梱の include/ は最後に探すので含めなくてよい
+    pub include_paths: Vec<PathBuf>,
+    /// 組み込み関数を差し替えるディレクトリ
+    pub prelude_dir: Option<PathBuf>,
+    pub stop_after: Stage,
+    /// スタックのダンプに元の文を添えるか 文を1行に整形するので要るときだけにする
+    pub statements: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            name: "<input>".to_string(),
+            include_paths: Vec::new(),
+            prelude_dir: None,
+            stop_after: Stage::Codegen,
+            statements: false,
+        }
+    }
+}
+
+/// 各段階の出力 stop_after より後の段階は None
+pub struct Artifact {
+    pub preprocessed: String,
+    pub parsed: Option<ast::Program>,
+    pub simplified: Option<ast::Program>,
+    pub converted: Option<sema::ast::Program>,
+    /// 型エラーがあってもError型を伝播した結果が入る
+    pub typed: Option<sema::ast::Program>,
+    pub session: Option<sema::ast::Session>,
+    pub codegen: Option<Generated>,
+}
+
+impl Artifact {
+    fn new(preprocessed: String) -> Self {
+        Self {
+            preprocessed,
+            parsed: None,
+            simplified: None,
+            converted: None,
+            typed: None,
+            session: None,
+            codegen: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub stage: Stage,
+    pub message: String,
+    /// 前処理前のファイル名とそのファイル上の位置
+    pub location: Option<(String, Span)>,
+    rendered: String,
+}
+
+impl Diagnostic {
+    fn new(stage: Stage, preprocessed: &Preprocessed, span: Option<Span>, message: String) -> Self {
+        let location = span
+            .and_then(|span| preprocessed.locate(span))
+            .map(|(file, span)| (file.name.clone(), span));
+        Self {
+            stage,
+            rendered: preprocessed.render(span, &message),
+            message,
+            location,
+        }
+    }
+}
+
+/// gccと同じ形式
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.rendered)
+    }
+}
+
+pub struct Diagnostics {
+    pub errors: Vec<Diagnostic>,
+    /// 失敗した段階より前の出力 型エラーのときは typed と session も入る
+    pub partial: Option<Box<Artifact>>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", errors.join("\n"))
+    }
+}
+
+/// 前処理からコード生成までを通す
+pub fn compile(source: &str, options: &Options) -> Result<Artifact, Diagnostics> {
+    // 以降の位置は展開後のテキスト上のもの 診断にするときにpreprocessedで元のファイルに戻す
+    let preprocessed = preprocessor::preprocess(&options.name, source, &options.include_paths);
+    if !preprocessed.errors.is_empty() {
+        let errors = preprocessed
+            .errors
+            .iter()
+            .map(|e| Diagnostic::new(Stage::Preprocess, &preprocessed, e.span, e.to_string()))
+            .collect();
+        return Err(Diagnostics {
+            errors,
+            partial: None,
+        });
+    }
+
+    let mut artifact = Artifact::new(preprocessed.text.clone());
+    if options.stop_after == Stage::Preprocess {
+        return Ok(artifact);
+    }
+
+    let (token, spans) = match lexer::tokenize(&preprocessed.text) {
+        Ok(lexed) => lexed,
+        Err(errors) => {
+            let errors = errors
+                .iter()
+                .map(|e| Diagnostic::new(Stage::Parse, &preprocessed, Some(e.span), e.to_string()))
+                .collect();
+            return Err(Diagnostics {
+                errors,
+                partial: Some(Box::new(artifact)),
+            });
+        }
+    };
+    let mut session = parser::ParseSession::with_spans(spans);
+    let mut program = match parser::program(&mut session, &mut parser::TokenCursor::new(&token)) {
+        Ok(program) => program,
+        Err(errors) => {
+            let errors = errors
+                .iter()
+                .map(|e| Diagnostic::new(Stage::Parse, &preprocessed, e.span, e.to_string()))
+                .collect();
+            return Err(Diagnostics {
+                errors,
+                partial: Some(Box::new(artifact)),
+            });
+        }
+    };
+    artifact.parsed = Some(program.clone());
+    if options.stop_after == Stage::Parse {
+        return Ok(artifact);
+    }
+
+    let mut simp_session = sema::simplification::Session::new();
+    sema::simplification::program(&mut program, &mut simp_session);
+    if options.stop_after == Stage::Simplify {
+        artifact.simplified = Some(program);
+        return Ok(artifact);
+    }
+
+    let mut sema_session = sema::ast::Session::new();
+    let new_program = sema::convert::program(&program, &mut sema_session);
+    artifact.simplified = Some(program);
+    if options.stop_after == Stage::Convert {
+        artifact.converted = Some(new_program);
+        artifact.session = Some(sema_session);
+        return Ok(artifact);
+    }
+
+    let type_check_result = sema::r#type::program(&new_program, &mut sema_session);
+    artifact.converted = Some(new_program);
+    let typed = type_check_result.result;
+    if !type_check_result.errors.is_empty() {
+        let errors = type_check_result
+            .errors
+            .iter()
+            .map(|e| Diagnostic::new(Stage::Type, &preprocessed, e.span(), e.to_string()))
+            .collect();
+        artifact.typed = Some(typed);
+        artifact.session = Some(sema_session);
+        return Err(Diagnostics {
+            errors,
+            partial: Some(Box::new(artifact)),
+        });
+    }
+    if options.stop_after == Stage::Type {
+        artifact.typed = Some(typed);
+        artifact.session = Some(sema_session);
+        return Ok(artifact);
+    }
+
+    artifact.typed = Some(typed.clone());
+    let generated =
+        codegen::generate_program(typed, options.prelude_dir.as_deref(), options.statements);
+    artifact.session = Some(sema_session);
+    match generated {
+        Ok(generated) => {
+            artifact.codegen = Some(generated);
+            Ok(artifact)
+        }
+        Err(e) => Err(Diagnostics {
+            errors: vec![Diagnostic {
+                stage: Stage::Codegen,
+                rendered: format!("internal error: {}", e),
+                message: e.to_string(),
+                location: None,
+            }],
+            partial: Some(Box::new(artifact)),
+        }),
+    }
+}
@@ -1,20 +1,23 @@
-// mod codegen;
-use std::{env, fs, process};
-
-mod ast;
-mod codegen;
-mod lexer;
-mod op;
-mod parser;
-mod preprocessor;
-mod sema;
-mod token;
-mod typelib;
-use normalize_line_endings::normalized;
-mod visualize;
-use visualize::*;
-
-use crate::sema::simplification::Session;
+use std::{
+    env, fs,
+    io::{self, BufWriter, Write},
+    panic,
+    path::PathBuf,
+    process,
+    str::FromStr,
+};
+
+use cynops::codegen::bf::{self, EofMode, RunConfig};
+use cynops::visualize::*;
+use cynops::{Artifact, Options, Stage};
+
+// 終了コード
+const EXIT_SYNTAX_ERROR: i32 = 1;
+const EXIT_TYPE_ERROR: i32 = 2;
+const EXIT_RUNTIME_ERROR: i32 = 3;
+const EXIT_IO_ERROR: i32 = 4;
+const EXIT_USAGE: i32 = 64;
+const EXIT_INTERNAL_ERROR: i32 = 70;
 
 #[derive(Debug, Clone)]
 struct CompilerOptions {
@@ -23,7 +26,12 @@ struct CompilerOptions {
     show_convert: bool,
     show_typed: bool,
     show_session: bool,
+    show_stack: bool,
+    show_sestack: bool,
+    show_stackinst: bool,
+    show_stackopt: bool,
     run_codegen: bool,
+    run_program: bool,
 }
 
 impl CompilerOptions {
@@ -34,28 +42,22 @@ impl CompilerOptions {
             show_convert: false,
             show_typed: false,
             show_session: false,
+            show_stack: false,
+            show_sestack: false,
+            show_stackinst: false,
+            show_stackopt: false,
             run_codegen: false,
+            run_program: false,
         }
     }
 
     fn from_modes(modes: &[&str]) -> Result<Self, String> {
         let mut options = Self::new();
 
-        if modes.is_empty() {
-            return Err("モードが指定されていません".to_string());
-        }
-
         for mode in modes {
             match *mode {
                 // 複合モード
-                "ast" => {
-                    options.show_parse = true;
-                    options.show_simplification = true;
-                    options.show_convert = true;
-                    options.show_typed = true;
-                    options.show_session = true;
-                }
-                "all" => {
+                "ast" | "all" => {
                     options.show_parse = true;
                     options.show_simplification = true;
                     options.show_convert = true;
@@ -68,152 +70,483 @@ impl CompilerOptions {
                 "convert" | "conv" => options.show_convert = true,
                 "typed" | "type" => options.show_typed = true,
                 "session" | "sess" => options.show_session = true,
+                "stack" => options.show_stack = true,
+                "sestack" => options.show_sestack = true,
+                "stackinst" => options.show_stackinst = true,
+                "stackopt" => options.show_stackopt = true,
                 "codegen" | "code" => options.run_codegen = true,
-                _ => return Err(format!("不明なモード: {}", mode)),
+                "run" => options.run_program = true,
+                _ => return Err(format!("unknown mode '{}'", mode)),
             }
         }
 
         Ok(options)
     }
+
+    fn is_empty(&self) -> bool {
+        !(self.show_parse
+            || self.show_simplification
+            || self.show_convert
+            || self.show_typed
+            || self.show_session
+            || self.needs_codegen()
+            || self.run_program)
+    }
+
+    fn needs_codegen(&self) -> bool {
+        self.show_stack
+            || self.show_sestack
+            || self.show_stackinst
+            || self.show_stackopt
+            || self.run_codegen
+            || self.run_program
+    }
+
+    /// 指定されたモードに必要な段階
+    fn stage(&self) -> Stage {
+        if self.needs_codegen() {
+            Stage::Codegen
+        } else if self.show_typed || self.show_session {
+            Stage::Type
+        } else {
+            Stage::Convert
+        }
+    }
+}
+
+/// --emit で書き出す中間表現
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Emit {
+    Ast,
+    Typed,
+    Stack,
+    SeStack,
+    StackInst,
+    StackOpt,
+    Bf,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ast" => Ok(Emit::Ast),
+            "typed" => Ok(Emit::Typed),
+            "stack" => Ok(Emit::Stack),
+            "sestack" => Ok(Emit::SeStack),
+            "stackinst" => Ok(Emit::StackInst),
+            "stackopt" => Ok(Emit::StackOpt),
+            "bf" => Ok(Emit::Bf),
+            _ => Err(format!(
+                "unknown --emit kind '{}' (expected ast, typed, stack, sestack, stackinst, stackopt or bf)",
+                s
+            )),
+        }
+    }
+}
+
+impl Emit {
+    fn stage(self) -> Stage {
+        match self {
+            Emit::Ast => Stage::Parse,
+            Emit::Typed => Stage::Type,
+            Emit::Stack | Emit::SeStack | Emit::StackInst | Emit::StackOpt | Emit::Bf => {
+                Stage::Codegen
+            }
+        }
+    }
+
+    fn render(self, artifact: &Artifact) -> String {
+        match self {
+            Emit::Ast => capture(|| artifact.parsed.as_ref().unwrap().visualize()),
+            Emit::Typed => capture(|| artifact.typed.as_ref().unwrap().visualize()),
+            Emit::Stack => artifact.codegen.as_ref().unwrap().dump_stack(),
+            Emit::SeStack => artifact.codegen.as_ref().unwrap().dump_sestack(),
+            Emit::StackInst => artifact.codegen.as_ref().unwrap().dump_stack_inst(),
+            Emit::StackOpt => artifact.codegen.as_ref().unwrap().dump_optimized(),
+            Emit::Bf => artifact.codegen.as_ref().unwrap().bf() + "\n",
+        }
+    }
+}
+
+struct Args {
+    /// "-" は標準入力
+    input: String,
+    options: CompilerOptions,
+    /// 出力先が None なら -o か標準出力
+    emits: Vec<(Emit, Option<PathBuf>)>,
+    include_paths: Vec<PathBuf>,
+    prelude_dir: Option<PathBuf>,
+    run_config: RunConfig,
+}
+
+enum Command {
+    Compile(Args),
+    Help,
+    Version,
 }
 
 fn main() {
-    let mut args: Vec<String> = env::args().collect();
-    process(&mut args);
+    let args: Vec<String> = env::args().collect();
+    let program_name = args.first().map(String::as_str).unwrap_or("Cynops");
+
+    match parse_args(args.get(1..).unwrap_or_default()) {
+        Ok(Command::Compile(args)) => process::exit(compile(args)),
+        Ok(Command::Help) => print_usage(program_name),
+        Ok(Command::Version) => println!("Cynops {}", env!("CARGO_PKG_VERSION")),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("Try '{} --help' for more information.", program_name);
+            process::exit(EXIT_USAGE);
+        }
+    }
 }
 
-fn process(args: &mut Vec<String>) {
-    if args.len() < 3 {
-        print_usage(&args[0]);
-        process::exit(1);
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut input = None;
+    let mut modes: Vec<&str> = Vec::new();
+    let mut emits = Vec::new();
+    let mut output = None;
+    let mut include_paths = Vec::new();
+    let mut prelude_dir = None;
+    let mut run_config = RunConfig::default();
+
+    // 最初の位置引数が入力ファイル，残りはモード
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        } else if arg == "-V" || arg == "--version" {
+            return Ok(Command::Version);
+        } else if arg == "-I" || arg == "-o" {
+            let value = rest
+                .next()
+                .ok_or_else(|| format!("{} requires an argument", arg))?;
+            if arg == "-I" {
+                include_paths.push(PathBuf::from(value));
+            } else {
+                output = Some(PathBuf::from(value));
+            }
+        } else if let Some(dir) = arg.strip_prefix("-I") {
+            include_paths.push(PathBuf::from(dir));
+        } else if let Some(file) = arg.strip_prefix("-o") {
+            output = Some(PathBuf::from(file));
+        } else if let Some(kinds) = arg.strip_prefix("--emit=") {
+            // --emit=kind[=path],...
+            for kind in kinds.split(',') {
+                let (kind, path) = match kind.split_once('=') {
+                    Some((kind, path)) => (kind, Some(PathBuf::from(path))),
+                    None => (kind, None),
+                };
+                emits.push((kind.parse::<Emit>()?, path));
+            }
+        } else if let Some(dir) = arg.strip_prefix("--prelude=") {
+            prelude_dir = Some(PathBuf::from(dir));
+        } else if let Some(option) = arg.strip_prefix("--") {
+            run_option(option, &mut run_config)?;
+        } else if arg.starts_with('-') && arg != "-" {
+            return Err(format!("unknown option '{}'", arg));
+        } else if input.is_none() {
+            input = Some(arg.clone());
+        } else {
+            modes.push(arg);
+        }
     }
 
-    let filename = &args[1];
-    let modes: Vec<&str> = args[2..].iter().map(|s| s.as_str()).collect();
+    let input = input.ok_or("no input file")?;
+    let options = CompilerOptions::from_modes(&modes)?;
+
+    // モードも --emit も無ければBFを出力する -o の書き出すものがないときも同じ
+    let unnamed = emits.iter().filter(|(_, path)| path.is_none()).count();
+    if (emits.is_empty() && options.is_empty()) || (output.is_some() && unnamed == 0) {
+        emits.push((Emit::Bf, None));
+    }
+    if output.is_some() {
+        if unnamed > 1 {
+            return Err("-o can only be used with a single --emit kind; use --emit=<kind>=<file> for the others".to_string());
+        }
+        for (_, path) in emits.iter_mut() {
+            if path.is_none() {
+                *path = output.clone();
+            }
+        }
+    }
 
-    let options = match CompilerOptions::from_modes(&modes) {
-        Ok(opts) => opts,
+    Ok(Command::Compile(Args {
+        input,
+        options,
+        emits,
+        include_paths,
+        prelude_dir,
+        run_config,
+    }))
+}
+
+fn run_option(option: &str, config: &mut RunConfig) -> Result<(), String> {
+    let (name, value) = option
+        .split_once('=')
+        .ok_or_else(|| format!("unknown option '--{}'", option))?;
+    match name {
+        "tape-size" => match value.parse::<usize>() {
+            Ok(size) if size > 0 => config.tape_size = size,
+            _ => return Err(format!("invalid tape size '{}'", value)),
+        },
+        "eof" => config.eof = value.parse::<EofMode>()?,
+        "step-limit" => {
+            let limit = value
+                .parse::<u64>()
+                .map_err(|_| format!("invalid step limit '{}'", value))?;
+            config.step_limit = Some(limit);
+        }
+        _ => return Err(format!("unknown option '--{}'", name)),
+    }
+    Ok(())
+}
+
+/// 終了コードを返す
+fn compile(args: Args) -> i32 {
+    let Args {
+        input,
+        options,
+        emits,
+        include_paths,
+        prelude_dir,
+        run_config,
+    } = args;
+
+    let (name, source) = if input == "-" {
+        ("<stdin>".to_string(), io::read_to_string(io::stdin()))
+    } else {
+        (input.clone(), fs::read_to_string(&input))
+    };
+    let source = match source {
+        Ok(source) => source,
         Err(e) => {
-            eprintln!("エラー: {}", e);
-            print_usage(&args[0]);
-            process::exit(1);
+            eprintln!("error: cannot read '{}': {}", input, e);
+            return EXIT_IO_ERROR;
         }
     };
 
-    let mut input = fs::read_to_string(filename).unwrap();
-    input = String::from_iter(normalized(input.chars()));
-
-    preprocessor::remove_comments(&mut input);
-    preprocessor::unescape_char_literals(&mut input);
+    // 実行順序は固定 指定されたモードと --emit に必要な段階まで進める
+    let stop_after = emits
+        .iter()
+        .map(|(emit, _)| emit.stage())
+        .chain(std::iter::once(options.stage()))
+        .max()
+        .unwrap();
+    let compile_options = Options {
+        name: name.clone(),
+        include_paths,
+        prelude_dir,
+        stop_after,
+        // 元の文を添えるダンプがあるときだけ
+        statements: options.show_stack
+            || options.show_sestack
+            || options.show_stackinst
+            || emits
+                .iter()
+                .any(|(emit, _)| matches!(emit, Emit::Stack | Emit::SeStack | Emit::StackInst)),
+    };
 
-    let mut token = lexer::tokenize(&input);
-    let mut session = parser::ParseSession::new();
-    let mut program: ast::Program = parser::program(&mut session, &mut token);
+    // コンパイラ内部の panic はバックトレースを出さずに内部エラーとして報告する
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        match info.location() {
+            Some(location) => eprintln!(
+                "error: internal compiler error: {} (at {})",
+                message, location
+            ),
+            None => eprintln!("error: internal compiler error: {}", message),
+        }
+    }));
+    let result = panic::catch_unwind(|| cynops::compile(&source, &compile_options));
+    panic::set_hook(default_hook);
+    let Ok(result) = result else {
+        return EXIT_INTERNAL_ERROR;
+    };
 
-    // 実行順序に従って処理（順序は固定、指定されたもののみ実行）
+    let (artifact, type_failed) = match result {
+        Ok(artifact) => (artifact, false),
+        Err(diagnostics) => {
+            for error in &diagnostics.errors {
+                eprintln!("{}", error);
+            }
+            // 型エラーならError型を伝播した結果を表示する それ以外はここで終了
+            match diagnostics.partial {
+                Some(partial) if diagnostics.errors.iter().all(|e| e.stage == Stage::Type) => {
+                    (*partial, true)
+                }
+                _ => {
+                    return match diagnostics.errors.first().map(|e| e.stage) {
+                        Some(Stage::Codegen) => EXIT_INTERNAL_ERROR,
+                        _ => EXIT_SYNTAX_ERROR,
+                    };
+                }
+            }
+        }
+    };
 
     // 1. Parse フェーズ
     if options.show_parse {
         println!("=== Parse ===");
-        program.visualize();
+        artifact.parsed.as_ref().unwrap().visualize();
     }
 
-    // 2. Simplification フェーズ（必ず実行、表示は条件付き）
-    let mut simp_session = Session::new();
-    sema::simplification::program(&mut program, &mut simp_session);
+    // 2. Simplification フェーズ
     if options.show_simplification {
         println!("=== Simplification ===");
-        program.visualize();
+        artifact.simplified.as_ref().unwrap().visualize();
     }
 
-    // 3. Convert フェーズ（必ず実行、表示は条件付き）
-    let mut sema_session = sema::ast::Session::new();
-    let new_program = sema::convert::program(&program, &mut sema_session);
+    // 3. Convert フェーズ
     if options.show_convert {
         println!("=== Convert ===");
-        new_program.visualize();
+        artifact.converted.as_ref().unwrap().visualize();
     }
 
-    // 4. Type checking フェーズ（typed/session/codegenのいずれかが指定されている場合のみ実行）
-    if options.show_typed || options.show_session || options.run_codegen {
-        let type_check_result = sema::r#type::program(&new_program, &mut sema_session);
+    // 4. Typed結果の表示（エラーがあっても表示）
+    if options.show_typed {
+        println!("=== Typed (with Error propagation) ===");
+        artifact.typed.as_ref().unwrap().visualize();
+    }
 
-        // エラーがあっても結果を表示する
-        let typed_prog = type_check_result.result;
-        let type_errors = type_check_result.errors;
+    // 5. Session情報の表示
+    if options.show_session {
+        println!("=== Session ===");
+        artifact.session.as_ref().unwrap().visualize();
+    }
 
-        // 型エラーを表示
-        if !type_errors.is_empty() {
-            eprintln!("=== Type Errors ===");
-            for error in &type_errors {
-                eprintln!("型エラー: {}", error);
+    // 6. --emit（型エラーがあってもコード生成が要らないものは書き出す）
+    for (emit, path) in &emits {
+        if type_failed && emit.stage() == Stage::Codegen {
+            continue;
+        }
+        let text = emit.render(&artifact);
+        match path {
+            Some(path) => {
+                if let Err(e) = fs::write(path, text) {
+                    eprintln!("error: cannot write '{}': {}", path.display(), e);
+                    return EXIT_IO_ERROR;
+                }
             }
+            None => print!("{}", text),
         }
+    }
 
-        // 5. Typed結果の表示（エラーがあっても表示）
-        if options.show_typed {
-            println!("=== Typed (with Error propagation) ===");
-            typed_prog.visualize();
+    if type_failed {
+        if options.needs_codegen() || emits.iter().any(|(emit, _)| emit.stage() == Stage::Codegen) {
+            eprintln!("error: code generation skipped because of type errors");
         }
+        return EXIT_TYPE_ERROR;
+    }
 
-        // 6. Session情報の表示
-        if options.show_session {
-            println!("=== Session ===");
-            sema_session.visualize();
-        }
+    // 7. コード生成の中間表現
+    if options.show_stack {
+        println!("=== StackCommand ===");
+        print!("{}", artifact.codegen.as_ref().unwrap().dump_stack());
+    }
+    if options.show_sestack {
+        println!("=== SeStackCommand ===");
+        print!("{}", artifact.codegen.as_ref().unwrap().dump_sestack());
+    }
+    if options.show_stackinst {
+        println!("=== StackInst ===");
+        print!("{}", artifact.codegen.as_ref().unwrap().dump_stack_inst());
+    }
+    if options.show_stackopt {
+        println!("=== StackInst (optimized) ===");
+        print!("{}", artifact.codegen.as_ref().unwrap().dump_optimized());
+    }
 
-        // 7. Code generation（エラーがある場合は実行しない）
-        if options.run_codegen {
-            if type_errors.is_empty() {
-                // eprintln!("; === Code Generation ===");
-                codegen::generate_program(typed_prog);
-            } else {
-                eprintln!("コード生成をスキップします（型エラーがあるため）");
-                std::process::exit(1);
-            }
+    // 8. Code generation
+    if options.run_codegen {
+        println!("{}", artifact.codegen.as_ref().unwrap().bf());
+    }
+
+    // 9. 組み込みのインタプリタで実行
+    if options.run_program {
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        let result = bf::run(
+            &artifact.codegen.as_ref().unwrap().code,
+            &run_config,
+            &mut io::stdin().lock(),
+            &mut stdout,
+        );
+        if let Err(e) = result {
+            let _ = stdout.flush();
+            eprintln!("{}: runtime error: {}", name, e);
+            return EXIT_RUNTIME_ERROR;
         }
     }
+
+    0
 }
 
 fn print_usage(program_name: &str) {
-    eprintln!(
-        "使い方: {} <入力ファイル> <モード1> [モード2] [モード3] ...",
-        program_name
-    );
-    eprintln!();
-    eprintln!("利用可能なモード（指定順序に関係なく適切な実行順序で処理されます）:");
-    eprintln!("  ast                 - 全ての解析フェーズを表示");
-    eprintln!("  all                 - 全ての解析フェーズを表示（astと同じ）");
-    eprintln!("  parse               - パース結果を表示");
-    eprintln!("  simplification|simp - 簡約化結果を表示");
-    eprintln!("  convert|conv        - 変換結果を表示");
-    eprintln!("  typed|type          - 型チェック結果を表示");
-    eprintln!("  session|sess        - セッション情報を表示");
-    eprintln!("  codegen|code        - コード生成を実行");
-    eprintln!();
-    eprintln!("実行順序: parse → simplification → convert → typed → session → codegen");
-    eprintln!();
-    eprintln!("注意: typed結果では型エラーがあってもError型を伝播したASTが表示されます");
-    eprintln!();
-    eprintln!("例:");
-    eprintln!(
-        "  {} input.c parse typed               - パースと型チェック結果を表示",
-        program_name
-    );
-    eprintln!(
-        "  {} input.c session typed parse       - 順序関係なく指定可能（parse→typed→sessionの順で実行）",
-        program_name
-    );
-    eprintln!(
-        "  {} input.c all codegen               - 全フェーズ実行＋コード生成",
-        program_name
-    );
-    eprintln!(
-        "  {} input.c codegen                   - コード生成のみ",
-        program_name
-    );
-    eprintln!(
-        "  {} input.c ast                       - 従来のastモード",
+    println!(
+        "Usage: {} [options] <file> [modes...]
+
+Compiles a C-like source file to Brainfuck. <file> may be '-' to read standard input.
+Without modes or --emit, the Brainfuck code is written to standard output (or to -o).
+
+Options:
+  -o <file>                 Write the --emit output (default: bf) to <file>
+  --emit=<kind>[=<file>]    Write an intermediate representation; may be repeated or comma-separated
+                              ast        parsed AST
+                              typed      type-checked AST
+                              stack      StackCommand listing per function
+                              sestack    SeStackCommand stream under the StackCommand it came from
+                              stackinst  StackInst stream under the StackCommand it came from
+                              stackopt   StackInst stream after the peephole optimizer
+                              bf         Brainfuck code
+  -I <dir>                  Add a directory to the #include search path (the bundled include/ is searched last)
+  --prelude=<dir>           Compile base.c / int.c / float.c from <dir> instead of the bundled runtime prelude
+  -h, --help                Show this help
+  -V, --version             Show the version
+
+Options for the run mode:
+  --tape-size=<cells>       Length of the tape (default 100000)
+  --eof=unchanged|zero|max  Cell value when input is exhausted (default unchanged)
+  --step-limit=<n>          Stop after <n> instructions
+
+Modes (run in the order parse -> simplification -> convert -> typed -> session -> stack -> sestack
+       -> stackinst -> stackopt -> codegen -> run):
+  parse                     Show the parsed AST
+  simplification|simp       Show the AST after simplification
+  convert|conv              Show the AST after conversion
+  typed|type                Show the type-checked AST (errors are propagated as the Error type)
+  session|sess              Show the scope tree
+  ast|all                   All of the above
+  stack                     Show the StackCommand listing per function with the tracked stack depth,
+                            the source statements and label names
+  sestack                   Show the SeStackCommand stream in the same layout
+  stackinst                 Show the StackInst stream in the same layout
+  stackopt                  Show the StackInst stream passed to the Brainfuck translator after
+                            constant folding and the other peephole optimizations
+  codegen|code              Print the Brainfuck code
+  run                       Run the Brainfuck code with the built-in interpreter
+
+Exit status:
+  0   success
+  1   preprocessor or syntax error
+  2   type error
+  3   runtime error in the run mode
+  4   cannot read the input or write the output
+  64  invalid command line
+  70  internal compiler error
+
+Examples:
+  {0} input.c -o input.bf
+  {0} input.c run < input.txt
+  {0} input.c --emit=typed,stack=input.stack
+  {0} input.c parse typed",
         program_name
     );
 }
@@ -3,8 +3,78 @@ use std::collections::HashSet;
 use crate::ast::*;
 use crate::ast::{Enum, EnumMember};
 use crate::op::*;
+use crate::span::Span;
 use crate::token::{Keyword, Token};
 use crate::typelib;
+
+// 構文エラー 何を期待していて実際に何が来たかを持つ
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub expected: String,
+    pub found: String,
+    pub span: Option<Span>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+// 字句解析したトークン列を借りて先頭から読み進めるカーソル
+// 残りのトークンは[Token]として見えるので先読みは添字で行い，
+// 後戻りしたいときはカーソルをコピーしておく
+#[derive(Debug, Clone, Copy)]
+pub struct TokenCursor<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    end: usize,
+}
+
+impl<'a> TokenCursor<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            end: tokens.len(),
+        }
+    }
+
+    // トークン列全体での位置 spansの添字と揃っている
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    // 1つ読み進めて，読んだトークンを返す
+    pub fn bump(&mut self) -> Option<&'a Token> {
+        let token = self.tokens[..self.end].get(self.pos)?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    pub fn advance(&mut self, n: usize) {
+        self.pos = (self.pos + n).min(self.end);
+    }
+
+    // 先頭からlen個だけを読むカーソル 位置はそのまま
+    pub fn take(&self, len: usize) -> Self {
+        Self {
+            end: (self.pos + len).min(self.end),
+            ..*self
+        }
+    }
+}
+
+impl std::ops::Deref for TokenCursor<'_> {
+    type Target = [Token];
+
+    fn deref(&self) -> &[Token] {
+        &self.tokens[self.pos..self.end]
+    }
+}
+
 #[derive(Debug)]
 
 pub struct ParseSession {
@@ -12,6 +82,8 @@ pub struct ParseSession {
     pub composite_type_stack: Vec<HashSet<Ident>>,
     pub variable_stack: Vec<HashSet<Ident>>,
     pub function_map: HashSet<Ident>,
+    pub spans: Vec<Span>,        // 字句解析時のトークン位置
+    pub errors: Vec<ParseError>, // 読み飛ばして回復した構文エラー
 }
 
 impl ParseSession {
@@ -21,6 +93,40 @@ impl ParseSession {
             composite_type_stack: Vec::new(),
             variable_stack: Vec::new(),
             function_map: HashSet::new(),
+            spans: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn with_spans(spans: Vec<Span>) -> Self {
+        Self {
+            spans,
+            ..Self::new()
+        }
+    }
+
+    // 次のトークンの位置
+    pub fn span(&self, tokens: &TokenCursor) -> Option<Span> {
+        if tokens.is_empty() {
+            return None;
+        }
+        self.span_at(tokens.pos())
+    }
+
+    // トークン列全体でindex番目のトークンの位置
+    pub fn span_at(&self, index: usize) -> Option<Span> {
+        self.spans.get(index).copied()
+    }
+
+    // 次のトークンに対するエラーを作る 入力の終わりなら最後のトークンを指す
+    pub fn error(&self, expected: impl Into<String>, tokens: &TokenCursor) -> ParseError {
+        ParseError {
+            expected: expected.into(),
+            found: match tokens.first() {
+                Some(token) => format!("'{}'", token),
+                None => "end of input".to_string(),
+            },
+            span: self.span(tokens).or(self.spans.last().copied()),
         }
     }
 
@@ -52,9 +158,7 @@ impl ParseSession {
             | Token::DotDotDot
             | Token::Keyword(Keyword::Double) => true,
             Token::Ident(ident) => {
-                let ident = Ident {
-                    name: ident.clone(),
-                };
+                let ident = Ident::new(ident);
                 // typedef_stackから下向きに検索
                 for scope in self.typedef_stack.iter().rev() {
                     if scope.contains(&ident) {
@@ -86,8 +190,8 @@ impl ParseSession {
     }
 
     // Token を Type に変換
-    pub fn cast(&self, tokens: &Vec<Token>) -> Option<(Type, usize)> {
-        match &tokens[0] {
+    pub fn cast(&self, tokens: &[Token]) -> Option<(Type, usize)> {
+        match tokens.first()? {
             Token::Keyword(Keyword::Int) => Some((Type::Int, 1)),
             Token::DotDotDot => Some((Type::DotDotDot, 1)),
             Token::Keyword(Keyword::Void) => Some((Type::Void, 1)),
@@ -104,7 +208,7 @@ impl ParseSession {
                 None
             }
             _ => {
-                match &tokens[1] {
+                match tokens.get(1)? {
                     Token::Ident(this) => {
                         let ident = Ident::new(this);
                         // typedef_stackから下向きに検索
@@ -151,85 +255,118 @@ impl ParseSession {
     }
 }
 
-pub fn program(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Program {
-    let _is_next_composite_type_def = |tokens: &mut Vec<Token>| {
+pub fn program(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> Result<Program, Vec<ParseError>> {
+    _parse_session.push_scope();
+    let mut code = Program::new();
+    while !tokens.is_empty() {
+        let result = top_level(_parse_session, tokens);
+        match recover(_parse_session, tokens, result) {
+            Some(item) => code.items.push(item),
+            // トップレベルに余った '}' は閉じるブロックが無いので捨てる
+            None => {
+                consume(Token::RBrace, tokens);
+            }
+        }
+    }
+    _parse_session.pop_scope();
+
+    if _parse_session.errors.is_empty() {
+        Ok(code)
+    } else {
+        Err(std::mem::take(&mut _parse_session.errors))
+    }
+}
+
+fn top_level(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<TopLevel> {
+    let _is_next_composite_type_def = |tokens: &mut TokenCursor<'_>| {
         is_next_composite_type_def(tokens, Token::r#enum())
             || is_next_composite_type_def(tokens, Token::r#struct())
             || is_next_composite_type_def(tokens, Token::r#union())
     };
-    _parse_session.push_scope();
-    let mut code = Program::new();
-    while !tokens.is_empty() {
-        if is_next_type(&_parse_session, tokens)
-            && !_is_next_composite_type_def(tokens)
-            && matches!(typelib::get_type(_parse_session, tokens), Type::Func(_))
-        {
-            let sig = function_sig(_parse_session, tokens);
 
-            _parse_session.register_function(sig.0.ident.clone());
+    // 関数に付くstaticは翻訳単位が一つなので読み飛ばす
+    let mut after_static = *tokens;
+    after_static.advance(1);
+    if tokens.first() == Some(&Token::r#static())
+        && is_next_type(_parse_session, &after_static)
+        && matches!(
+            typelib::get_type(_parse_session, &after_static),
+            Ok(Type::Func(_))
+        )
+    {
+        tokens.advance(1);
+    }
 
-            if consume(Token::LBrace, tokens) {
-                _parse_session.push_scope();
+    if is_next_type(&_parse_session, tokens)
+        && !_is_next_composite_type_def(tokens)
+        && matches!(typelib::get_type(_parse_session, tokens), Ok(Type::Func(_)))
+    {
+        let sig = function_sig(_parse_session, tokens)?;
 
-                match sig.0.ty.clone() {
-                    Type::Func(_) => {
-                        (0..sig.1.len())
-                            .for_each(|i| _parse_session.register_variable(sig.1[i].clone()));
-                    }
-                    _ => panic!(),
-                };
-                // function definition
-                code.items.push(TopLevel::function_def(
-                    sig.0,
-                    sig.1,
-                    *block(_parse_session, tokens),
-                ));
-
-                _parse_session.pop_scope();
-            } else {
-                // function prototype
-                code.items.push(TopLevel::function_proto(sig.0));
-                consume(Token::Semicolon, tokens);
-            }
+        _parse_session.register_function(sig.0.ident.clone());
+
+        if consume(Token::LBrace, tokens) {
+            _parse_session.push_scope();
+
+            match sig.0.ty.clone() {
+                Type::Func(_) => {
+                    (0..sig.1.len())
+                        .for_each(|i| _parse_session.register_variable(sig.1[i].clone()));
+                }
+                _ => panic!(),
+            };
+            // function definition
+            let body = *block(_parse_session, tokens)?;
+
+            _parse_session.pop_scope();
+            Ok(TopLevel::function_def(sig.0, sig.1, body))
         } else {
-            code.items
-                .push(TopLevel::stmt(*stmt(_parse_session, tokens)));
+            // function prototype
+            expect(_parse_session, Token::Semicolon, tokens)?;
+            Ok(TopLevel::function_proto(sig.0))
         }
+    } else {
+        Ok(TopLevel::stmt(*stmt(_parse_session, tokens)?))
     }
-    _parse_session.pop_scope();
-    code
 }
 
 fn function_sig(
     _parse_session: &mut ParseSession,
-    tokens: &mut Vec<Token>,
-) -> (FunctionSig, Vec<Ident>) {
-    let (types, mut ident) = typelib::consume_and_extract_idents(_parse_session, tokens);
-    (FunctionSig::new(types, ident.remove(0)), ident)
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<(FunctionSig, Vec<Ident>)> {
+    let (types, mut ident) = typelib::consume_and_extract_idents(_parse_session, tokens)?;
+    Ok((FunctionSig::new(types, ident.remove(0)), ident))
 }
 
-fn stmt(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Stmt> {
-    if consume(Token::r#return(), tokens) {
+fn stmt(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Box<Stmt>> {
+    let span = _parse_session.span(tokens);
+    let node = if consume(Token::r#return(), tokens) {
         let expr_opt = if consume(Token::Semicolon, tokens) {
             None
         } else {
-            let tmp = expr(_parse_session, tokens);
-            consume(Token::Semicolon, tokens);
+            let tmp = expr(_parse_session, tokens)?;
+            expect(_parse_session, Token::Semicolon, tokens)?;
             Some(tmp)
         };
         Stmt::r#return(expr_opt)
     } else if consume(Token::r#if(), tokens) {
         Stmt::r#if(
             {
-                consume(Token::LParen, tokens);
-                let tmp = expr(_parse_session, tokens);
-                consume(Token::RParen, tokens);
+                expect(_parse_session, Token::LParen, tokens)?;
+                let tmp = expr(_parse_session, tokens)?;
+                expect(_parse_session, Token::RParen, tokens)?;
                 tmp
             },
-            *stmt(_parse_session, tokens),
+            *stmt(_parse_session, tokens)?,
             {
                 if consume(Token::r#else(), tokens) {
-                    Some(*stmt(_parse_session, tokens))
+                    Some(*stmt(_parse_session, tokens)?)
                 } else {
                     None
                 }
@@ -238,34 +375,30 @@ fn stmt(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Stmt>
     } else if consume(Token::r#while(), tokens) {
         Stmt::r#while(
             {
-                consume(Token::LParen, tokens);
-                let tmp = expr(_parse_session, tokens);
-                consume(Token::RParen, tokens);
+                expect(_parse_session, Token::LParen, tokens)?;
+                let tmp = expr(_parse_session, tokens)?;
+                expect(_parse_session, Token::RParen, tokens)?;
                 tmp
             },
-            *stmt(_parse_session, tokens),
+            *stmt(_parse_session, tokens)?,
         )
     } else if consume(Token::r#do(), tokens) {
-        let body = *stmt(_parse_session, tokens);
-        if !consume(Token::r#while(), tokens) {
-            panic!("expected 'while' after 'do' statement");
-        }
-        consume(Token::LParen, tokens);
-        let condition = expr(_parse_session, tokens);
-        consume(Token::RParen, tokens);
-        if !consume(Token::Semicolon, tokens) {
-            panic!("expected semicolon after do-while statement");
-        }
+        let body = *stmt(_parse_session, tokens)?;
+        expect(_parse_session, Token::r#while(), tokens)?;
+        expect(_parse_session, Token::LParen, tokens)?;
+        let condition = expr(_parse_session, tokens)?;
+        expect(_parse_session, Token::RParen, tokens)?;
+        expect(_parse_session, Token::Semicolon, tokens)?;
         Stmt::do_while(body, condition)
     } else if consume(Token::r#for(), tokens) {
-        consume(Token::LParen, tokens);
+        expect(_parse_session, Token::LParen, tokens)?;
         Stmt::r#for(
             {
                 if consume(Token::Semicolon, tokens) {
                     None
                 } else {
-                    let tmp = expr(_parse_session, tokens);
-                    consume(Token::Semicolon, tokens);
+                    let tmp = expr(_parse_session, tokens)?;
+                    expect(_parse_session, Token::Semicolon, tokens)?;
                     Some(tmp)
                 }
             },
@@ -273,8 +406,8 @@ fn stmt(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Stmt>
                 if consume(Token::Semicolon, tokens) {
                     Some(Expr::num_int(0))
                 } else {
-                    let tmp = expr(_parse_session, tokens);
-                    consume(Token::Semicolon, tokens);
+                    let tmp = expr(_parse_session, tokens)?;
+                    expect(_parse_session, Token::Semicolon, tokens)?;
                     Some(tmp)
                 }
             },
@@ -282,192 +415,208 @@ fn stmt(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Stmt>
                 if consume(Token::RParen, tokens) {
                     None
                 } else {
-                    let tmp = expr(_parse_session, tokens);
-                    consume(Token::RParen, tokens);
+                    let tmp = expr(_parse_session, tokens)?;
+                    expect(_parse_session, Token::RParen, tokens)?;
                     Some(tmp)
                 }
             },
-            *stmt(_parse_session, tokens),
+            *stmt(_parse_session, tokens)?,
         )
     } else if consume(Token::r#break(), tokens) {
-        if !consume(Token::Semicolon, tokens) {
-            panic!("expected semicolon after break statement");
-        }
-        Stmt::r#break()
+        expect(_parse_session, Token::Semicolon, tokens)?;
+        Stmt::r#break(span)
     } else if consume(Token::r#continue(), tokens) {
-        if !consume(Token::Semicolon, tokens) {
-            panic!("expected semicolon after continue statement");
-        }
-        Stmt::r#continue()
+        expect(_parse_session, Token::Semicolon, tokens)?;
+        Stmt::r#continue(span)
     } else if consume(Token::LBrace, tokens) {
-        Stmt::block(*block(_parse_session, tokens))
+        Stmt::block(*block(_parse_session, tokens)?)
     } else if is_next_decl_stmt(_parse_session, tokens) {
-        Stmt::decl_stmt(decl_stmt(_parse_session, tokens))
+        Stmt::decl_stmt(decl_stmt(_parse_session, tokens)?)
     } else if consume(Token::r#switch(), tokens) {
-        consume(Token::LParen, tokens);
-        let cond = expr(_parse_session, tokens);
-        consume(Token::RParen, tokens);
+        expect(_parse_session, Token::LParen, tokens)?;
+        let cond = expr(_parse_session, tokens)?;
+        expect(_parse_session, Token::RParen, tokens)?;
 
-        consume(Token::LBrace, tokens);
+        expect(_parse_session, Token::LBrace, tokens)?;
         let mut cases = Vec::new();
-        while !consume(Token::RBrace, tokens) {
-            let switch_case = case_clause(_parse_session, tokens);
+        while !closes(_parse_session, Token::RBrace, tokens)? {
+            let switch_case = case_clause(_parse_session, tokens)?;
             cases.push(switch_case);
         }
         Stmt::r#switch(cond, cases)
     } else if consume(Token::r#goto(), tokens) {
-        let label = consume_ident(tokens);
-        if !consume(Token::Semicolon, tokens) {
-            panic!("expected semicolon after goto statement");
-        }
+        let label = consume_ident(_parse_session, tokens)?;
+        expect(_parse_session, Token::Semicolon, tokens)?;
         Stmt::goto(label)
     } else if is_next_label(tokens) {
-        let name = consume_ident(tokens);
-        if !consume(Token::Colon, tokens) {
-            panic!("expected colon after label statement");
-        }
-        Stmt::label(name, *stmt(_parse_session, tokens))
+        let name = consume_ident(_parse_session, tokens)?;
+        expect(_parse_session, Token::Colon, tokens)?;
+        Stmt::label(name, *stmt(_parse_session, tokens)?)
     } else {
-        let tmp = expr(_parse_session, tokens);
-        if !consume(Token::Semicolon, tokens) {
-            panic!("{:?}", tokens);
-        }
+        let tmp = expr(_parse_session, tokens)?;
+        expect(_parse_session, Token::Semicolon, tokens)?;
         Stmt::expr(tmp)
-    }
+    };
+    Ok(node)
 }
 
-fn case_clause(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> SwitchCase {
-    let get_stmts = |_parse_session: &mut ParseSession, tokens: &mut Vec<Token>| {
-        if !consume(Token::Colon, tokens) {
-            panic!("expected ':' after case expression");
-        }
+fn case_clause(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<SwitchCase> {
+    let get_stmts = |_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>| {
+        expect(_parse_session, Token::Colon, tokens)?;
 
         let mut stmts = vec![];
         while !is_next_switch_stmt(tokens) {
-            stmts.push(stmt(_parse_session, tokens));
+            if tokens.is_empty() {
+                return Err(_parse_session.error("'}'", tokens));
+            }
+            let result = stmt(_parse_session, tokens);
+            stmts.extend(recover(_parse_session, tokens, result));
         }
 
-        stmts
+        Ok(stmts)
     };
 
     if consume(Token::case(), tokens) {
-        SwitchCase::case(
-            expr(_parse_session, tokens),
-            get_stmts(_parse_session, tokens),
-        )
+        Ok(SwitchCase::case(
+            expr(_parse_session, tokens)?,
+            get_stmts(_parse_session, tokens)?,
+        ))
     } else if consume(Token::default(), tokens) {
-        SwitchCase::default(get_stmts(_parse_session, tokens))
+        Ok(SwitchCase::default(get_stmts(_parse_session, tokens)?))
     } else {
-        panic!("expected 'case' or 'default' in switch statement");
+        Err(_parse_session.error("'case' or 'default'", tokens))
     }
 }
 
-fn decl_stmt(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> DeclStmt {
+fn decl_stmt(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<DeclStmt> {
     if is_next_composite_type_def(tokens, Token::r#struct()) {
         consume(Token::r#struct(), tokens);
-        DeclStmt::r#struct(struct_def(_parse_session, tokens))
+        Ok(DeclStmt::r#struct(struct_def(_parse_session, tokens)?))
     } else if is_next_composite_type_def(tokens, Token::r#union()) {
         consume(Token::r#union(), tokens);
-        DeclStmt::union(union_def(_parse_session, tokens))
+        Ok(DeclStmt::union(union_def(_parse_session, tokens)?))
     } else if is_next_composite_type_def(tokens, Token::r#enum()) {
         consume(Token::r#enum(), tokens);
-        DeclStmt::r#enum(enum_def(_parse_session, tokens))
+        Ok(DeclStmt::r#enum(enum_def(_parse_session, tokens)?))
     } else if consume(Token::typedef(), tokens) {
-        let tmp = DeclStmt::typedef(typedef_stmt(_parse_session, tokens));
-        consume(Token::Semicolon, tokens);
-        tmp
+        let tmp = DeclStmt::typedef(typedef_stmt(_parse_session, tokens)?);
+        expect(_parse_session, Token::Semicolon, tokens)?;
+        Ok(tmp)
+    } else if consume(Token::r#static(), tokens) {
+        let tmp = DeclStmt::r#static(init_vec(_parse_session, tokens)?);
+        expect(_parse_session, Token::Semicolon, tokens)?;
+        Ok(tmp)
     } else {
-        let tmp = DeclStmt::init_vec(init_vec(_parse_session, tokens));
-        consume(Token::Semicolon, tokens);
-        tmp
+        let tmp = DeclStmt::init_vec(init_vec(_parse_session, tokens)?);
+        expect(_parse_session, Token::Semicolon, tokens)?;
+        Ok(tmp)
     }
 }
 
-fn init_vec(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Vec<Init> {
+fn init_vec(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Vec<Init>> {
     let mut vec = vec![];
-    let base = tokens.remove(0);
+    // const int a, *b; のように基本型は全ての宣言子で共有する
+    let base = typelib::consume_base(_parse_session, tokens)?;
 
-    vec.push(init(_parse_session, {
-        tokens.insert(0, base.clone());
-        tokens
-    }));
+    vec.push(init(_parse_session, &base, tokens)?);
     while consume(Token::Comma, tokens) {
-        vec.push(init(_parse_session, {
-            tokens.insert(0, base.clone());
-            tokens
-        }));
+        vec.push(init(_parse_session, &base, tokens)?);
     }
-    vec
+    Ok(vec)
 }
 
-fn init(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Init {
-    Init::new(
+fn init(
+    _parse_session: &mut ParseSession,
+    base: &Type,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Init> {
+    Ok(Init::new(
         {
-            let (types, ident) = typelib::consume_and_extract_idents(_parse_session, tokens);
+            let (types, ident) = typelib::consume_declarator(_parse_session, base.clone(), tokens)?;
             _parse_session.register_variable(ident[0].clone());
             MemberDecl::new(ident[0].clone(), types)
         },
         {
             if consume(Token::Equal, tokens) {
-                Some(init_data(_parse_session, tokens))
+                Some(init_data(_parse_session, tokens)?)
             } else {
                 None
             }
         },
-    )
+    ))
 }
 
-fn init_data(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> InitData {
+fn init_data(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<InitData> {
     if consume(Token::LBrace, tokens) {
         let mut elements = vec![];
-        while !consume(Token::RBrace, tokens) {
-            elements.push(init_data(_parse_session, tokens));
-            consume(Token::Comma, tokens);
+        while !closes(_parse_session, Token::RBrace, tokens)? {
+            elements.push(init_data(_parse_session, tokens)?);
+            if !consume(Token::Comma, tokens) && tokens.first() != Some(&Token::RBrace) {
+                return Err(_parse_session.error("',' or '}'", tokens));
+            }
         }
-        InitData::Compound(elements)
+        Ok(InitData::Compound(elements))
     } else {
-        InitData::Expr(*assign(_parse_session, tokens))
+        Ok(InitData::Expr(*assign(_parse_session, tokens)?))
     }
 }
 
-fn typedef_stmt(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Typedef {
+fn typedef_stmt(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Typedef> {
     let ident;
     let ty = if is_next_composite_type_def(tokens, Token::r#struct()) {
         consume(Token::r#struct(), tokens);
-        let st = struct_def(_parse_session, tokens);
+        let st = struct_def(_parse_session, tokens)?;
         if st.ident.is_some() {
             _parse_session.register_composite_type(st.ident.as_ref().unwrap().clone());
         }
-        ident = consume_ident(tokens);
+        ident = consume_ident(_parse_session, tokens)?;
         Type::r#struct(st)
     } else if is_next_composite_type_def(tokens, Token::r#union()) {
         consume(Token::r#union(), tokens);
-        let un = union_def(_parse_session, tokens);
+        let un = union_def(_parse_session, tokens)?;
         if un.ident.is_some() {
             _parse_session.register_composite_type(un.ident.as_ref().unwrap().clone());
         }
-        ident = consume_ident(tokens);
+        ident = consume_ident(_parse_session, tokens)?;
         Type::union(un)
     } else if is_next_composite_type_def(tokens, Token::r#enum()) {
         consume(Token::r#enum(), tokens);
-        let en = enum_def(_parse_session, tokens);
+        let en = enum_def(_parse_session, tokens)?;
         if en.ident.is_some() {
             _parse_session.register_composite_type(en.ident.as_ref().unwrap().clone());
         }
-        ident = consume_ident(tokens);
+        ident = consume_ident(_parse_session, tokens)?;
         Type::r#enum(en)
     } else {
-        let (t, i) = typelib::consume_and_extract_idents(_parse_session, tokens);
+        let (t, i) = typelib::consume_and_extract_idents(_parse_session, tokens)?;
         ident = i[0].clone();
         t
     };
     _parse_session.register_typedef(ident.clone());
-    Typedef::new(ident, ty)
+    Ok(Typedef::new(ident, ty))
 }
 
-fn struct_def(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Struct {
+fn struct_def(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Struct> {
     let idn = if is_next_ident(tokens) {
-        Some(consume_ident(tokens))
+        Some(consume_ident(_parse_session, tokens)?)
     } else {
         None
     };
@@ -477,35 +626,38 @@ fn struct_def(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Str
     }
 
     let st = Struct::new(idn.clone(), {
-        consume(Token::LBrace, tokens);
+        expect(_parse_session, Token::LBrace, tokens)?;
 
         let mut ms = vec![];
-        while !consume(Token::RBrace, tokens) {
-            ms.push(decl_member_vec(_parse_session, tokens));
-            consume(Token::Semicolon, tokens);
+        while !closes(_parse_session, Token::RBrace, tokens)? {
+            ms.push(decl_member_vec(_parse_session, tokens)?);
+            expect(_parse_session, Token::Semicolon, tokens)?;
         }
 
         consume(Token::Semicolon, tokens);
         ms.into_iter().flatten().collect()
     });
 
-    st
+    Ok(st)
 }
 
-fn union_def(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Union {
+fn union_def(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Union> {
     let idn = if is_next_ident(tokens) {
-        Some(consume_ident(tokens))
+        Some(consume_ident(_parse_session, tokens)?)
     } else {
         None
     };
 
     let un = Union::new(idn.clone(), {
-        consume(Token::LBrace, tokens);
+        expect(_parse_session, Token::LBrace, tokens)?;
 
         let mut ms = vec![];
-        while !consume(Token::RBrace, tokens) {
-            ms.push(decl_member_vec(_parse_session, tokens));
-            consume(Token::Semicolon, tokens);
+        while !closes(_parse_session, Token::RBrace, tokens)? {
+            ms.push(decl_member_vec(_parse_session, tokens)?);
+            expect(_parse_session, Token::Semicolon, tokens)?;
         }
 
         consume(Token::Semicolon, tokens);
@@ -515,43 +667,44 @@ fn union_def(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Unio
     if idn.is_some() {
         _parse_session.register_composite_type(idn.unwrap());
     }
-    un
+    Ok(un)
 }
 
-fn decl_member(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> MemberDecl {
-    let (types, ident) = typelib::consume_and_extract_idents(_parse_session, tokens);
+fn decl_member(
+    _parse_session: &mut ParseSession,
+    base: &Type,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<MemberDecl> {
+    let (types, ident) = typelib::consume_declarator(_parse_session, base.clone(), tokens)?;
     _parse_session.register_variable(ident[0].clone());
-    MemberDecl::new(ident[0].clone(), types)
+    Ok(MemberDecl::new(ident[0].clone(), types))
 }
 
-fn decl_member_vec(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Vec<MemberDecl> {
+fn decl_member_vec(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Vec<MemberDecl>> {
     let mut vec = vec![];
-    let base = tokens.remove(0);
+    let base = typelib::consume_base(_parse_session, tokens)?;
 
-    vec.push(decl_member(_parse_session, {
-        tokens.insert(0, base.clone());
-        tokens
-    }));
+    vec.push(decl_member(_parse_session, &base, tokens)?);
     while consume(Token::Comma, tokens) {
-        vec.push(decl_member(_parse_session, {
-            tokens.insert(0, base.clone());
-            tokens
-        }));
+        vec.push(decl_member(_parse_session, &base, tokens)?);
     }
-    vec
+    Ok(vec)
 }
 
-fn enum_def(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Enum {
+fn enum_def(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Enum> {
     let idn = if is_next_ident(tokens) {
-        Some(consume_ident(tokens))
+        Some(consume_ident(_parse_session, tokens)?)
     } else {
         None
     };
 
     let em = Enum::new(idn.clone(), {
-        consume(Token::LBrace, tokens);
-        let tmp = enum_member(_parse_session, tokens);
-        consume(Token::RBrace, tokens);
+        expect(_parse_session, Token::LBrace, tokens)?;
+        let tmp = enum_member(_parse_session, tokens)?;
+        expect(_parse_session, Token::RBrace, tokens)?;
         consume(Token::Semicolon, tokens);
         tmp
     });
@@ -559,23 +712,20 @@ fn enum_def(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Enum
     if idn.is_some() {
         _parse_session.register_composite_type(idn.unwrap());
     }
-    em
+    Ok(em)
 }
 
-fn enum_member(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Vec<EnumMember> {
+fn enum_member(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Vec<EnumMember>> {
     let mut members = Vec::new();
 
     loop {
-        let name = consume_ident(tokens);
+        let name = consume_ident(_parse_session, tokens)?;
 
         let value = if consume(Token::Equal, tokens) {
-            if let Token::NumInt(n) = tokens.first().unwrap() {
-                let n = *n;
-                tokens.remove(0);
-                Some(n)
-            } else {
-                panic!("Expected number after '=' in enum member");
-            }
+            Some(conditional(_parse_session, tokens)?)
         } else {
             None
         };
@@ -588,384 +738,645 @@ fn enum_member(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Ve
         }
     }
 
-    members
+    Ok(members)
 }
 
-fn block(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Block> {
+fn block(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Block>> {
     let mut code = vec![];
     _parse_session.push_scope();
-    while !consume(Token::RBrace, tokens) {
-        code.push(stmt(_parse_session, tokens));
+    while !closes(_parse_session, Token::RBrace, tokens)? {
+        let result = stmt(_parse_session, tokens);
+        code.extend(recover(_parse_session, tokens, result));
     }
     _parse_session.pop_scope();
-    Block::new(code)
+    Ok(Block::new(code))
 }
 
-pub fn expr(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Expr {
+pub fn expr(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Expr> {
     comma(_parse_session, tokens)
 }
 
-fn comma(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Expr {
-    let mut assigns = vec![*assign(_parse_session, tokens)];
+fn comma(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Expr> {
+    let mut assigns = vec![*assign(_parse_session, tokens)?];
     while consume(Token::Comma, tokens) {
-        assigns.push(*assign(_parse_session, tokens));
+        assigns.push(*assign(_parse_session, tokens)?);
     }
     if assigns.len() > 1 {
-        Expr::comma(assigns)
+        Ok(Expr::comma(assigns))
     } else {
-        assigns.first().unwrap().clone()
+        Ok(assigns.first().unwrap().clone())
     }
 }
 
-fn assign(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = conditional(_parse_session, tokens);
+fn assign(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = conditional(_parse_session, tokens)?;
+    let span = _parse_session.span(tokens);
     if consume(Token::Equal, tokens) {
-        node = Expr::assign(AssignOp::equal(), node, assign(_parse_session, tokens));
+        node = at(
+            span,
+            Expr::assign(AssignOp::equal(), node, assign(_parse_session, tokens)?),
+        );
     } else if consume(Token::PlusEqual, tokens) {
-        node = Expr::assign(AssignOp::plus_equal(), node, assign(_parse_session, tokens));
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::plus_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
+        );
     } else if consume(Token::MinusEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::minus_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::minus_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::AsteriskEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::asterisk_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::asterisk_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::SlashEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::slash_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::slash_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::PercentEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::percent_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::percent_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::CaretEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::caret_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::caret_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::PipeEqual, tokens) {
-        node = Expr::assign(AssignOp::pipe_equal(), node, assign(_parse_session, tokens));
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::pipe_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
+        );
     } else if consume(Token::LessLessEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::less_less_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::less_less_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::GreaterGreaterEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::greater_greater_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::greater_greater_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     } else if consume(Token::AmpersandEqual, tokens) {
-        node = Expr::assign(
-            AssignOp::ampersand_equal(),
-            node,
-            assign(_parse_session, tokens),
+        node = at(
+            span,
+            Expr::assign(
+                AssignOp::ampersand_equal(),
+                node,
+                assign(_parse_session, tokens)?,
+            ),
         );
     }
-    node
+    Ok(node)
 }
 
-fn conditional(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = logical_or(_parse_session, tokens);
+fn conditional(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = logical_or(_parse_session, tokens)?;
+    let span = _parse_session.span(tokens);
     if consume(Token::Question, tokens) {
-        let then_branch = expr(_parse_session, tokens);
-        consume(Token::Colon, tokens);
-        let else_branch = expr(_parse_session, tokens);
-        node = Expr::ternary(node, then_branch, else_branch);
+        let then_branch = expr(_parse_session, tokens)?;
+        expect(_parse_session, Token::Colon, tokens)?;
+        let else_branch = expr(_parse_session, tokens)?;
+        node = at(span, Expr::ternary(node, then_branch, else_branch));
     }
-    node
+    Ok(node)
 }
 
-fn logical_or(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = logical_and(_parse_session, tokens);
+fn logical_or(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = logical_and(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::PipePipe, tokens) {
-            node = Expr::binary(
-                BinaryOp::pipe_pipe(),
-                node,
-                logical_and(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::pipe_pipe(),
+                    node,
+                    logical_and(_parse_session, tokens)?,
+                ),
             );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn logical_and(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = equality(_parse_session, tokens);
+fn logical_and(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = equality(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::AmpersandAmpersand, tokens) {
-            node = Expr::binary(
-                BinaryOp::ampersand_ampersand(),
-                node,
-                equality(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::ampersand_ampersand(),
+                    node,
+                    equality(_parse_session, tokens)?,
+                ),
             );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn equality(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = relational(_parse_session, tokens);
+fn equality(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = relational(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::EqualEqual, tokens) {
-            node = Expr::binary(
-                BinaryOp::equal_equal(),
-                node,
-                relational(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::equal_equal(),
+                    node,
+                    relational(_parse_session, tokens)?,
+                ),
             );
         } else if consume(Token::NotEqual, tokens) {
-            node = Expr::binary(
-                BinaryOp::not_equal(),
-                node,
-                relational(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::not_equal(),
+                    node,
+                    relational(_parse_session, tokens)?,
+                ),
             );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn relational(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = bitwise_or(_parse_session, tokens);
+fn relational(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = bitwise_or(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::Less, tokens) {
-            node = Expr::binary(BinaryOp::less(), node, bitwise_or(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::less(), node, bitwise_or(_parse_session, tokens)?),
+            );
         } else if consume(Token::LessEqual, tokens) {
-            node = Expr::binary(
-                BinaryOp::less_equal(),
-                node,
-                bitwise_or(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::less_equal(),
+                    node,
+                    bitwise_or(_parse_session, tokens)?,
+                ),
             );
         } else if consume(Token::Greater, tokens) {
-            node = Expr::binary(
-                BinaryOp::greater(),
-                node,
-                bitwise_or(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::greater(),
+                    node,
+                    bitwise_or(_parse_session, tokens)?,
+                ),
             );
         } else if consume(Token::GreaterEqual, tokens) {
-            node = Expr::binary(
-                BinaryOp::greater_equal(),
-                node,
-                bitwise_or(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::greater_equal(),
+                    node,
+                    bitwise_or(_parse_session, tokens)?,
+                ),
             );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn bitwise_or(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = bitwise_xor(_parse_session, tokens);
+fn bitwise_or(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = bitwise_xor(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::Pipe, tokens) {
-            node = Expr::binary(BinaryOp::pipe(), node, bitwise_xor(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::pipe(), node, bitwise_xor(_parse_session, tokens)?),
+            );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn bitwise_xor(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = bitwise_and(_parse_session, tokens);
+fn bitwise_xor(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = bitwise_and(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::Caret, tokens) {
-            node = Expr::binary(BinaryOp::caret(), node, bitwise_and(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::caret(),
+                    node,
+                    bitwise_and(_parse_session, tokens)?,
+                ),
+            );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn bitwise_and(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = shift(_parse_session, tokens);
+fn bitwise_and(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = shift(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::Ampersand, tokens) {
-            node = Expr::binary(BinaryOp::ampersand(), node, shift(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::ampersand(), node, shift(_parse_session, tokens)?),
+            );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn shift(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = add(_parse_session, tokens);
+fn shift(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let mut node = add(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::LessLess, tokens) {
-            node = Expr::binary(BinaryOp::less_less(), node, add(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::less_less(), node, add(_parse_session, tokens)?),
+            );
         } else if consume(Token::GreaterGreater, tokens) {
-            node = Expr::binary(
-                BinaryOp::greater_greater(),
-                node,
-                add(_parse_session, tokens),
+            node = at(
+                span,
+                Expr::binary(
+                    BinaryOp::greater_greater(),
+                    node,
+                    add(_parse_session, tokens)?,
+                ),
             );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn add(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = mul(_parse_session, tokens);
+fn add(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Box<Expr>> {
+    let mut node = mul(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::Plus, tokens) {
-            node = Expr::binary(BinaryOp::plus(), node, mul(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::plus(), node, mul(_parse_session, tokens)?),
+            );
         } else if consume(Token::Minus, tokens) {
-            node = Expr::binary(BinaryOp::minus(), node, mul(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::minus(), node, mul(_parse_session, tokens)?),
+            );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn mul(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    let mut node = unary(_parse_session, tokens);
+fn mul(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Box<Expr>> {
+    let mut node = unary(_parse_session, tokens)?;
     loop {
+        let span = _parse_session.span(tokens);
         if consume(Token::Asterisk, tokens) {
-            node = Expr::binary(BinaryOp::asterisk(), node, unary(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::asterisk(), node, unary(_parse_session, tokens)?),
+            );
         } else if consume(Token::Slash, tokens) {
-            node = Expr::binary(BinaryOp::slash(), node, unary(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::slash(), node, unary(_parse_session, tokens)?),
+            );
         } else if consume(Token::Percent, tokens) {
-            node = Expr::binary(BinaryOp::percent(), node, unary(_parse_session, tokens));
+            node = at(
+                span,
+                Expr::binary(BinaryOp::percent(), node, unary(_parse_session, tokens)?),
+            );
         } else {
-            return node;
+            return Ok(node);
         }
     }
 }
 
-fn unary(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Box<Expr> {
-    if consume(Token::Plus, tokens) {
-        unary(_parse_session, tokens)
+fn unary(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Box<Expr>> {
+    let span = _parse_session.span(tokens);
+    let node = if consume(Token::Plus, tokens) {
+        unary(_parse_session, tokens)?
     } else if consume(Token::Minus, tokens) {
-        Expr::unary(UnaryOp::minus(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::minus(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::Bang, tokens) {
-        Expr::unary(UnaryOp::bang(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::bang(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::Tilde, tokens) {
-        Expr::unary(UnaryOp::tilde(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::tilde(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::Ampersand, tokens) {
-        Expr::unary(UnaryOp::ampersand(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::ampersand(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::Asterisk, tokens) {
-        Expr::unary(UnaryOp::asterisk(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::asterisk(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::PlusPlus, tokens) {
-        Expr::unary(UnaryOp::plus_plus(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::plus_plus(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::MinusMinus, tokens) {
-        Expr::unary(UnaryOp::minus_minus(), unary(_parse_session, tokens))
+        at(
+            span,
+            Expr::unary(UnaryOp::minus_minus(), unary(_parse_session, tokens)?),
+        )
     } else if consume(Token::sizeof(), tokens) {
-        if consume(Token::LParen, tokens) && is_next_type(_parse_session, tokens) {
-            let tmp = Expr::sizeof(Sizeof::r#type(consume_type(_parse_session, tokens)));
-            consume(Token::RParen, tokens);
+        let paren = consume(Token::LParen, tokens);
+        if paren && is_next_type(_parse_session, tokens) {
+            let tmp = Expr::sizeof(Sizeof::r#type(consume_type(_parse_session, tokens)?));
+            expect(_parse_session, Token::RParen, tokens)?;
             tmp
         } else {
-            let tmp = Expr::sizeof(Sizeof::expr(expr(_parse_session, tokens)));
-            consume(Token::RParen, tokens);
+            let tmp = Expr::sizeof(Sizeof::expr(expr(_parse_session, tokens)?));
+            if paren {
+                expect(_parse_session, Token::RParen, tokens)?;
+            }
             tmp
         }
     } else if is_next_cast(_parse_session, tokens) {
-        Expr::cast(
-            {
-                consume(Token::LParen, tokens);
-                let tmp = consume_type(_parse_session, tokens);
-                consume(Token::RParen, tokens);
-                tmp
-            },
-            *unary(_parse_session, tokens),
+        at(
+            span,
+            Expr::cast(
+                {
+                    consume(Token::LParen, tokens);
+                    let tmp = consume_type(_parse_session, tokens)?;
+                    expect(_parse_session, Token::RParen, tokens)?;
+                    tmp
+                },
+                *unary(_parse_session, tokens)?,
+            ),
         )
     } else {
-        Box::new(postfix(_parse_session, tokens))
-    }
+        Box::new(postfix(_parse_session, tokens)?)
+    };
+    Ok(node)
 }
 
-fn postfix(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Expr {
-    let psd = postfix_chain(_parse_session, tokens);
+fn postfix(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Expr> {
+    let psd = postfix_chain(_parse_session, tokens)?;
     let mut base = psd.base;
     let suffixes = psd.suffixes;
 
-    suffixes.iter().for_each(|suffixe| match suffixe {
-        PostfixSuffix::ArrayAcsess(index) => base = Expr::subscript(base.clone(), index.clone()),
-        PostfixSuffix::ArgList(args) => base = Expr::call(base.clone(), args.clone()),
-        PostfixSuffix::PostfixOp(op) => base = Expr::postfix(op.clone().clone(), base.clone()),
-        PostfixSuffix::MemberAccess(op, indet) => {
-            base = Expr::member_access(base.clone(), indet.clone(), op.clone())
+    suffixes.iter().for_each(|(suffixe, span)| {
+        base = match suffixe {
+            PostfixSuffix::ArrayAcsess(index) => Expr::subscript(base.clone(), index.clone()),
+            PostfixSuffix::ArgList(args) => Expr::call(base.clone(), args.clone()),
+            PostfixSuffix::PostfixOp(op) => Expr::postfix(op.clone().clone(), base.clone()),
+            PostfixSuffix::MemberAccess(op, indet) => {
+                Expr::member_access(base.clone(), indet.clone(), op.clone())
+            }
         }
+        .with_span(*span)
     });
 
-    base
+    Ok(base)
 }
 
-fn postfix_chain(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> PostfixChain {
-    let node = PostfixChain::new(primary(_parse_session, tokens), {
+fn postfix_chain(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<PostfixChain> {
+    let node = PostfixChain::new(primary(_parse_session, tokens)?, {
         let mut pos_vec = vec![];
         while is_next_postfix_suffix(tokens) {
-            if consume(Token::PlusPlus, tokens) {
-                pos_vec.push(PostfixSuffix::plus_plus());
+            let span = _parse_session.span(tokens);
+            let suffix = if consume(Token::PlusPlus, tokens) {
+                PostfixSuffix::plus_plus()
             } else if consume(Token::MinusMinus, tokens) {
-                pos_vec.push(PostfixSuffix::minus_minus());
+                PostfixSuffix::minus_minus()
             } else if consume(Token::MinusGreater, tokens) {
-                pos_vec.push(PostfixSuffix::MemberAccess(
+                PostfixSuffix::MemberAccess(
                     MemberAccessOp::minus_greater(),
-                    consume_ident(tokens),
-                ));
+                    consume_ident(_parse_session, tokens)?,
+                )
             } else if consume(Token::Dot, tokens) {
-                pos_vec.push(PostfixSuffix::MemberAccess(
+                PostfixSuffix::MemberAccess(
                     MemberAccessOp::dot(),
-                    consume_ident(tokens),
-                ));
+                    consume_ident(_parse_session, tokens)?,
+                )
             } else if consume(Token::LParen, tokens) {
-                pos_vec.push(PostfixSuffix::ArgList(arg_list(_parse_session, tokens)));
-                consume(Token::RParen, tokens);
-            } else if consume(Token::LBracket, tokens) {
-                pos_vec.push(PostfixSuffix::ArrayAcsess(expr(_parse_session, tokens)));
-                consume(Token::RBracket, tokens);
-            }
+                let tmp = PostfixSuffix::ArgList(arg_list(_parse_session, tokens)?);
+                expect(_parse_session, Token::RParen, tokens)?;
+                tmp
+            } else {
+                consume(Token::LBracket, tokens);
+                let tmp = PostfixSuffix::ArrayAcsess(expr(_parse_session, tokens)?);
+                expect(_parse_session, Token::RBracket, tokens)?;
+                tmp
+            };
+            pos_vec.push((suffix, span));
         }
         pos_vec
     });
 
-    node
+    Ok(node)
 }
 
-fn primary(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Expr {
+fn primary(_parse_session: &mut ParseSession, tokens: &mut TokenCursor<'_>) -> ParseResult<Expr> {
     // 次のトークンが"("なら、"(" expr ")"のはず
     if consume(Token::LParen, tokens) {
-        let node = expr(_parse_session, tokens);
-        let _ = consume(Token::RParen, tokens);
-        return node;
+        let node = expr(_parse_session, tokens)?;
+        expect(_parse_session, Token::RParen, tokens)?;
+        Ok(node)
     }
     // そうでなければ数値か変数か関数のはず
     else if is_next_atom(tokens) {
-        consume_atom(tokens)
-    } else {
+        Ok(consume_atom(tokens))
+    } else if is_next_ident(tokens) {
         // 変数か関数のはず
-        let ident = consume_ident(tokens);
-        Expr::ident(ident.clone())
+        let ident = consume_ident(_parse_session, tokens)?;
+        Ok(Expr::ident(ident.clone()))
+    } else {
+        Err(_parse_session.error("expression", tokens))
     }
 }
 
-fn arg_list(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Vec<Box<Expr>> {
+fn arg_list(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Vec<Box<Expr>>> {
     let mut args = Vec::new();
     if !tokens.is_empty() && tokens.first().unwrap() != &Token::RParen {
-        args.push(Box::new(*assign(_parse_session, tokens)));
+        args.push(Box::new(*assign(_parse_session, tokens)?));
         while consume(Token::Comma, tokens) {
-            args.push(Box::new(*assign(_parse_session, tokens)));
+            args.push(Box::new(*assign(_parse_session, tokens)?));
         }
     }
-    args
+    Ok(args)
 }
 
-fn consume(op: Token, tokens: &mut Vec<Token>) -> bool {
+// 演算子の位置を式に記録する
+fn at(span: Option<Span>, mut node: Box<Expr>) -> Box<Expr> {
+    *node = (*node).with_span(span);
+    node
+}
+
+// 来るべきトークンを消費する 無ければ何を期待していたかをエラーにする
+fn expect(
+    _parse_session: &ParseSession,
+    op: Token,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<()> {
+    if consume(op.clone(), tokens) {
+        Ok(())
+    } else {
+        Err(_parse_session.error(format!("'{}'", op), tokens))
+    }
+}
+
+// 閉じ括弧なら消費してtrue 閉じる前に入力が尽きたらエラー
+fn closes(
+    _parse_session: &ParseSession,
+    op: Token,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<bool> {
+    if tokens.is_empty() {
+        return Err(_parse_session.error(format!("'{}'", op), tokens));
+    }
+    Ok(consume(op, tokens))
+}
+
+// エラーを記録し，次の文の区切りまで読み飛ばして解析を続ける
+fn recover<T>(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+    result: ParseResult<T>,
+) -> Option<T> {
+    match result {
+        Ok(this) => Some(this),
+        Err(error) => {
+            _parse_session.errors.push(error);
+            synchronize(tokens);
+            None
+        }
+    }
+}
+
+// ';' までを読み飛ばす 途中のブロックは丸ごと飛ばし，外側のブロックを閉じる '}' は残す
+fn synchronize(tokens: &mut TokenCursor<'_>) {
+    let mut depth = 0;
+    while let Some(token) = tokens.first() {
+        if *token == Token::RBrace && depth == 0 {
+            return;
+        }
+        match tokens.bump().unwrap() {
+            Token::Semicolon if depth == 0 => return,
+            Token::LBrace => depth += 1,
+            Token::RBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn consume(op: Token, tokens: &mut TokenCursor<'_>) -> bool {
     if tokens.is_empty() {
         return false;
     }
@@ -974,7 +1385,7 @@ fn consume(op: Token, tokens: &mut Vec<Token>) -> bool {
         return false;
     }
 
-    tokens.remove(0);
+    tokens.advance(1);
     return true;
 }
 
@@ -1011,6 +1422,7 @@ fn is_next_type(_parse_session: &ParseSession, tokens: &[Token]) -> bool {
             | Token::Keyword(Keyword::Double)
             | Token::Keyword(Keyword::Char)
             | Token::Keyword(Keyword::Void)
+            | Token::Keyword(Keyword::Const)
             | Token::DotDotDot
     ) || next == &Token::r#struct()
         || next == &Token::r#union()
@@ -1044,6 +1456,7 @@ fn is_next_decl_stmt(_parse_session: &ParseSession, tokens: &[Token]) -> bool {
         || tokens.first().unwrap() == &Token::r#union()
         || tokens.first().unwrap() == &Token::r#enum()
         || tokens.first().unwrap() == &Token::typedef()
+        || tokens.first().unwrap() == &Token::r#static()
         || _parse_session.is_base_type(tokens.first().unwrap())
 }
 
@@ -1080,57 +1493,78 @@ fn is_next_cast(_parse_session: &ParseSession, tokens: &[Token]) -> bool {
         return false;
     }
 
-    let mut new_tokens = tokens.to_vec();
-    new_tokens.remove(0);
-    is_next_type(_parse_session, &new_tokens)
+    is_next_type(_parse_session, &tokens[1..])
 }
 
-fn consume_atom(tokens: &mut Vec<Token>) -> Expr {
+fn consume_atom(tokens: &mut TokenCursor<'_>) -> Expr {
     if tokens.is_empty() {
         panic!("Expected atom, but no tokens available");
     }
 
     if let Some(Token::NumInt(n)) = tokens.first() {
         let n = n.clone();
-        tokens.remove(0);
+        tokens.advance(1);
         Expr::num_int(n)
     } else if let Some(Token::Char(c)) = tokens.first() {
         let c = c.clone();
-        tokens.remove(0);
+        tokens.advance(1);
         Expr::char_lit(c)
     } else if let Some(Token::String(string)) = tokens.first() {
         let string = string.clone();
-        tokens.remove(0);
+        tokens.advance(1);
         Expr::string(string)
     } else if let Some(Token::NumFloat(f)) = tokens.first() {
         let f = f.clone();
-        tokens.remove(0);
+        tokens.advance(1);
         Expr::num_float(f)
     } else {
         panic!()
     }
 }
 
-fn consume_ident(tokens: &mut Vec<Token>) -> Ident {
-    let ident = get_ident(tokens);
-    tokens.remove(0);
-    ident
+fn consume_ident(
+    _parse_session: &ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Ident> {
+    let ident = get_ident(_parse_session, tokens)?.with_span(_parse_session.span(tokens));
+    tokens.advance(1);
+    Ok(ident)
 }
 
-fn consume_type(_parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Type {
+fn consume_type(
+    _parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Type> {
     typelib::consume_type(_parse_session, tokens)
 }
 
-fn get_ident(tokens: &[Token]) -> Ident {
+fn get_ident(_parse_session: &ParseSession, tokens: &TokenCursor) -> ParseResult<Ident> {
     if let Some(Token::Ident(name)) = tokens.first() {
         let name = name.clone();
-        Ident::new(name)
+        Ok(Ident::new(name))
     } else {
-        panic!("Expected identifier, found {:?}", tokens);
+        Err(_parse_session.error("identifier", tokens))
     }
 }
 
 #[test]
 fn test() {
-    let _ = program(&mut ParseSession::new(), &mut Vec::new());
+    let _ = program(&mut ParseSession::new(), &mut TokenCursor::new(&[]));
+}
+
+#[test]
+fn test_recover() {
+    let (tokens, spans) =
+        crate::lexer::tokenize("void main(void) {\n  int a = (1;\n  a = ;\n  a = 2;\n}").unwrap();
+    let errors = program(
+        &mut ParseSession::with_spans(spans),
+        &mut TokenCursor::new(&tokens),
+    )
+    .unwrap_err();
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        ["expected ')', found ';'", "expected expression, found ';'"]
+    );
+    assert_eq!(errors[0].span.map(|span| span.line.get()), Some(2));
 }
@@ -1,95 +1,1154 @@
-pub fn unescape_char_literals(input: &mut String) {
-    let mut result = String::new();
-    let mut chars = input.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        if ch == '\'' {
-            // 文字リテラルの開始
-            result.push(ch);
-
-            // 文字リテラルの中身を処理
-            while let Some(inner_ch) = chars.next() {
-                if inner_ch == '\\' {
-                    // エスケープシーケンス
-                    if let Some(escaped_ch) = chars.next() {
-                        match escaped_ch {
-                            'n' => result.push('\n'),  // '\n' → 改行文字
-                            't' => result.push('\t'),  // '\t' → タブ文字
-                            'r' => result.push('\r'),  // '\r' → キャリッジリターン
-                            '0' => result.push('\0'),  // '\0' → NULL文字
-                            '\\' => result.push('\\'), // '\\' → バックスラッシュ
-                            '\'' => result.push('\''), // '\'' → シングルクォート
-                            '"' => result.push('"'),   // '\"' → ダブルクォート
-                            _ => {
-                                // その他のエスケープシーケンスはそのまま
-                                result.push('\\');
-                                result.push(escaped_ch);
-                            }
-                        }
-                    }
-                } else if inner_ch == '\'' {
-                    // 文字リテラルの終了
-                    result.push(inner_ch);
-                    break;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use normalize_line_endings::normalized;
+
+use crate::lexer;
+use crate::span::{self, Span};
+
+/// `#include` で最後に探す，同梱の include/ のヘッダ
+/// 実行ファイルに埋め込むので，ソースの置き場所がなくても使える
+const SYSTEM_HEADERS: [(&str, &str); 2] = [
+    ("cynops.h", include_str!("../include/cynops.h")),
+    ("print.h", include_str!("../include/print.h")),
+];
+
+/// インクルードの入れ子の上限 自分自身を読み込み続けるのを防ぐ
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// 読み込んだソースファイル 診断の表示に使うので元のテキストを持っておく
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+}
+
+/// 展開後の行がどのファイルの何行目から来たか
+#[derive(Debug, Clone, Copy)]
+pub struct LineOrigin {
+    pub file: usize,
+    pub line: usize,
+}
+
+/// 前処理中のエラー 位置は展開後のテキスト上の位置
+#[derive(Debug, Clone)]
+pub struct PreprocessError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// 前処理の結果
+/// 展開後は1行ごとに元の位置を覚えているので，後段の診断を元のファイルに戻せる
+#[derive(Debug, Clone)]
+pub struct Preprocessed {
+    pub text: String,
+    pub files: Vec<SourceFile>,
+    pub origins: Vec<LineOrigin>,
+    pub errors: Vec<PreprocessError>,
+}
+
+impl Preprocessed {
+    /// 展開後の位置を元のファイル上の位置に戻す
+    /// マクロを展開した行では列がずれることがある
+    pub fn locate(&self, span: Span) -> Option<(&SourceFile, Span)> {
+        let origin = self.origins.get(span.line.get() as usize - 1)?;
+        let span = Span::new(origin.line, span.column as usize, span.len as usize);
+        Some((&self.files[origin.file], span))
+    }
+
+    /// 展開後の位置で診断を組み立てる
+    pub fn render(&self, span: Option<Span>, message: &str) -> String {
+        match span.and_then(|span| self.locate(span)) {
+            Some((file, span)) => span::render(&file.name, &file.source, Some(span), message),
+            None => span::render(&self.files[0].name, "", None, message),
+        }
+    }
+}
+
+/// ソースを前処理する
+/// `#include "..."` はそのファイルのあるディレクトリ，次にinclude_pathsの順に探す
+pub fn preprocess(name: &str, source: &str, include_paths: &[PathBuf]) -> Preprocessed {
+    let mut state = State {
+        include_paths,
+        macros: HashMap::new(),
+        once: HashSet::new(),
+        lines: Vec::new(),
+        depth: 0,
+        out: Preprocessed {
+            text: String::new(),
+            files: Vec::new(),
+            origins: Vec::new(),
+            errors: Vec::new(),
+        },
+    };
+
+    let file = state.add_file(name.to_string(), source);
+    state.process_file(file);
+
+    let mut out = state.out;
+    out.text = state.lines.join("\n");
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PpKind {
+    Ident,
+    Number,
+    Literal,
+    Punct,
+    Space,
+}
+
+/// 前処理用のトークン 展開後は文字列に戻すので空白もトークンとして残す
+#[derive(Debug, Clone)]
+struct PpToken {
+    kind: PpKind,
+    text: String,
+}
+
+impl PpToken {
+    fn new(kind: PpKind, text: impl ToString) -> Self {
+        Self {
+            kind,
+            text: text.to_string(),
+        }
+    }
+
+    fn space() -> Self {
+        Self::new(PpKind::Space, " ")
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        self.kind == PpKind::Punct && self.text == punct
+    }
+}
+
+/// 長い記号優先
+const PUNCTS: [&str; 23] = [
+    "...", "<<=", ">>=", "##", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=",
+];
+
+/// 1行を前処理用のトークンに分ける
+/// コメントは位置がずれないように同じ幅の空白にする
+/// in_commentは前の行から続くブロックコメントの中にいるか
+fn pp_tokenize(line: &str, in_comment: &mut bool) -> Vec<PpToken> {
+    let mut tokens = Vec::new();
+    let bytes = line.as_bytes();
+    let mut i = 0;
+
+    let blank = |text: &str| PpToken::new(PpKind::Space, " ".repeat(text.chars().count()));
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+
+    while i < bytes.len() {
+        let start = i;
+        let c = bytes[i];
+
+        // ブロックコメントの続きか，新しいコメント
+        if *in_comment || line[i..].starts_with("/*") {
+            let from = if *in_comment { i } else { i + 2 };
+            match line[from..].find("*/") {
+                Some(close) => {
+                    *in_comment = false;
+                    i = from + close + 2;
+                }
+                None => {
+                    *in_comment = true;
+                    i = bytes.len();
+                }
+            }
+            tokens.push(blank(&line[start..i]));
+            continue;
+        }
+        if line[i..].starts_with("//") {
+            tokens.push(blank(&line[i..]));
+            break;
+        }
+
+        let kind = if c.is_ascii_whitespace() {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            PpKind::Space
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && is_ident(bytes[i]) {
+                i += 1;
+            }
+            PpKind::Ident
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            // pp-number 1e-3 のような指数の符号も含める
+            i += 1;
+            while i < bytes.len() {
+                if matches!(bytes[i], b'e' | b'E' | b'p' | b'P')
+                    && matches!(bytes.get(i + 1), Some(b'+' | b'-'))
+                {
+                    i += 2;
+                } else if is_ident(bytes[i]) || bytes[i] == b'.' {
+                    i += 1;
                 } else {
-                    // 通常の文字
-                    result.push(inner_ch);
+                    break;
                 }
             }
+            PpKind::Number
+        } else if c == b'"' || c == b'\'' {
+            // 閉じていなければ行末まで 字句解析でエラーにする
+            i += lexer::literal_len(&line[i..]).unwrap_or(bytes.len() - i);
+            PpKind::Literal
         } else {
-            // 文字リテラル外はそのまま
-            result.push(ch);
+            let len = PUNCTS
+                .iter()
+                .find(|p| line[i..].starts_with(*p))
+                .map_or_else(|| line[i..].chars().next().unwrap().len_utf8(), |p| p.len());
+            i += len;
+            PpKind::Punct
+        };
+        tokens.push(PpToken::new(kind, &line[start..i]));
+    }
+    tokens
+}
+
+fn next_non_space(tokens: &[PpToken], from: usize) -> Option<usize> {
+    (from..tokens.len()).find(|&i| tokens[i].kind != PpKind::Space)
+}
+
+fn trim_spaces(tokens: &[PpToken]) -> Vec<PpToken> {
+    let start = next_non_space(tokens, 0).unwrap_or(tokens.len());
+    let end = tokens
+        .iter()
+        .rposition(|t| t.kind != PpKind::Space)
+        .map_or(start, |i| i + 1);
+    tokens[start..end.max(start)].to_vec()
+}
+
+fn concat(tokens: &[PpToken]) -> String {
+    tokens.iter().map(|t| t.text.as_str()).collect()
+}
+
+/// `#x` 実引数を文字列リテラルにする
+fn stringify(arg: &[PpToken]) -> PpToken {
+    let mut text = String::from("\"");
+    for token in trim_spaces(arg) {
+        match token.kind {
+            PpKind::Space => text.push(' '),
+            PpKind::Literal => {
+                for c in token.text.chars() {
+                    if c == '"' || c == '\\' {
+                        text.push('\\');
+                    }
+                    text.push(c);
+                }
+            }
+            _ => text.push_str(&token.text),
+        }
+    }
+    text.push('"');
+    PpToken::new(PpKind::Literal, text)
+}
+
+/// `a ## b` を1つのトークンにつなげる
+fn paste(tokens: Vec<PpToken>) -> Vec<PpToken> {
+    let mut result: Vec<PpToken> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].is("##") {
+            while result.last().is_some_and(|t| t.kind == PpKind::Space) {
+                result.pop();
+            }
+            let right = next_non_space(&tokens, i + 1);
+            let mut text = result.pop().map(|t| t.text).unwrap_or_default();
+            if let Some(j) = right {
+                text.push_str(&tokens[j].text);
+            }
+            result.extend(pp_tokenize(&text, &mut false));
+            i = right.map_or(tokens.len(), |j| j + 1);
+            continue;
+        }
+        result.push(tokens[i].clone());
+        i += 1;
+    }
+    result
+}
+
+/// `#pragma once` で覚えておくファイルの名前 同梱のヘッダは実在しないのでそのまま
+fn once_key(name: &str) -> PathBuf {
+    fs::canonicalize(name).unwrap_or(PathBuf::from(name))
+}
+
+/// physical[*i] から始まる論理行 行末の \ で次の行とつなげる
+fn logical_line(physical: &[&str], i: &mut usize) -> String {
+    let mut line = physical[*i].to_string();
+    while line.ends_with('\\') && *i + 1 < physical.len() {
+        line.pop();
+        *i += 1;
+        line.push_str(physical[*i]);
+    }
+    *i += 1;
+    line
+}
+
+/// 関数形式マクロの実引数を集める
+/// 開き括弧の位置を受け取り，実引数と閉じ括弧の次の位置を返す
+fn collect_args(tokens: &[PpToken], open: usize) -> Option<(Vec<Vec<PpToken>>, usize)> {
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open + 1) {
+        if token.is(")") && depth == 0 {
+            let args = args.iter().map(|arg| trim_spaces(arg)).collect();
+            return Some((args, i + 1));
+        }
+        if token.is(",") && depth == 0 {
+            args.push(Vec::new());
+            continue;
+        }
+        if token.is("(") {
+            depth += 1;
+        } else if token.is(")") {
+            depth -= 1;
+        }
+        args.last_mut().unwrap().push(token.clone());
+    }
+    None
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    /// 関数形式マクロなら仮引数の名前
+    params: Option<Vec<String>>,
+    variadic: bool,
+    body: Vec<PpToken>,
+}
+
+impl Macro {
+    fn param_index(&self, token: &PpToken) -> Option<usize> {
+        let params = self.params.as_ref()?;
+        if token.kind != PpKind::Ident {
+            return None;
         }
+        if self.variadic && token.text == "__VA_ARGS__" {
+            return Some(params.len());
+        }
+        params.iter().position(|p| *p == token.text)
     }
+}
+
+/// #if の入れ子1段分
+struct Cond {
+    /// 外側が有効か
+    parent: bool,
+    /// 今の枝が有効か
+    active: bool,
+    /// どれかの枝を既に選んだか
+    taken: bool,
+    seen_else: bool,
+    /// 診断用に #if の位置を覚えておく
+    span: Span,
+}
 
-    *input = result;
+/// 展開中の行の情報 __LINE__ と __FILE__ と診断に使う
+struct Context<'a> {
+    origin: LineOrigin,
+    line: &'a str,
+    at: usize,
 }
 
-pub fn remove_comments(src: &mut String) {
-    let mut result = String::new();
-    let mut chars = src.chars().peekable();
+struct State<'a> {
+    include_paths: &'a [PathBuf],
+    macros: HashMap<String, Macro>,
+    /// `#pragma once` 済みのファイル
+    once: HashSet<PathBuf>,
+    lines: Vec<String>,
+    depth: usize,
+    out: Preprocessed,
+}
+
+impl State<'_> {
+    fn add_file(&mut self, name: String, source: &str) -> usize {
+        let source = String::from_iter(normalized(source.chars()));
+        self.out.files.push(SourceFile { name, source });
+        self.out.files.len() - 1
+    }
+
+    /// 1行出力して，その行番号を返す
+    fn emit(&mut self, text: String, origin: LineOrigin) -> usize {
+        self.lines.push(text);
+        self.out.origins.push(origin);
+        self.lines.len()
+    }
+
+    fn error(&mut self, message: impl ToString, span: Span) {
+        self.out.errors.push(PreprocessError {
+            message: message.to_string(),
+            span: Some(span),
+        });
+    }
+
+    /// 行の中でnameが最初に現れる位置を指すSpan
+    fn span_of(ctx: &Context, name: &str) -> Span {
+        let column = ctx
+            .line
+            .find(name)
+            .map_or(1, |i| ctx.line[..i].chars().count() + 1);
+        Span::new(ctx.at, column, name.chars().count())
+    }
+
+    fn process_file(&mut self, file: usize) {
+        let text = self.out.files[file].source.clone();
+        let physical: Vec<&str> = text.split('\n').collect();
+
+        let mut conds: Vec<Cond> = Vec::new();
+        // 閉じていないブロックコメントの開始位置
+        let mut comment: Option<Span> = None;
+        let mut i = 0;
+        while i < physical.len() {
+            let start = i;
+            let mut line = logical_line(&physical, &mut i);
+            let active = conds.iter().all(|c| c.active);
+
+            let mut in_comment = comment.is_some();
+            let mut tokens = pp_tokenize(&line, &mut in_comment);
+            let first = next_non_space(&tokens, 0).map(|i| &tokens[i]);
+            let is_directive = first.is_some_and(|t| t.is("#"));
+            // 関数形式マクロの実引数が閉じていなければ，次の行もつなげて展開する
+            if active && !is_directive {
+                while self.unterminated_call(&tokens)
+                    && i < physical.len()
+                    && !physical[i].trim_start().starts_with('#')
+                {
+                    let next = logical_line(&physical, &mut i);
+                    tokens.push(PpToken::space());
+                    tokens.extend(pp_tokenize(&next, &mut in_comment));
+                    line.push(' ');
+                    line.push_str(&next);
+                }
+            }
+
+            let origin = LineOrigin {
+                file,
+                line: start + 1,
+            };
+            let at = self.emit(String::new(), origin);
+            let ctx = Context {
+                origin,
+                line: &line,
+                at,
+            };
+
+            if !in_comment {
+                comment = None;
+            } else if comment.is_none() {
+                comment = Some(Self::span_of(&ctx, "/*"));
+            }
+
+            if is_directive {
+                self.directive(&tokens, &ctx, &mut conds, active);
+            } else if active {
+                let expanded = self.expand(&tokens, &mut Vec::new(), &ctx);
+                self.lines[at - 1] = concat(&expanded);
+            }
+
+            // 継続行とつなげた行の分だけ空行を足して行数を合わせる
+            for line in start + 1..i {
+                self.emit(
+                    String::new(),
+                    LineOrigin {
+                        file,
+                        line: line + 1,
+                    },
+                );
+            }
+        }
+
+        for cond in conds {
+            self.error("unterminated conditional directive", cond.span);
+        }
+        if let Some(span) = comment {
+            self.error("unterminated comment", span);
+        }
+    }
+
+    // 閉じ括弧のない関数形式マクロの呼び出しがあるか
+    fn unterminated_call(&self, tokens: &[PpToken]) -> bool {
+        tokens.iter().enumerate().any(|(i, token)| {
+            token.kind == PpKind::Ident
+                && self
+                    .macros
+                    .get(&token.text)
+                    .is_some_and(|m| m.params.is_some())
+                && next_non_space(tokens, i + 1).is_some_and(|open| {
+                    tokens[open].is("(") && collect_args(tokens, open).is_none()
+                })
+        })
+    }
+
+    fn directive(
+        &mut self,
+        tokens: &[PpToken],
+        ctx: &Context,
+        conds: &mut Vec<Cond>,
+        active: bool,
+    ) {
+        let hash = next_non_space(tokens, 0).unwrap();
+        let name_index = next_non_space(tokens, hash + 1);
+        let name = name_index.map_or("", |i| tokens[i].text.as_str());
+        let rest = name_index.map_or(&[][..], |i| &tokens[i + 1..]);
+        // 指令の名前まで下線を引く
+        let column = Self::span_of(ctx, "#").column as usize;
+        let span = Span::new(ctx.at, column, name.chars().count() + 1);
 
-    while let Some(c) = chars.next() {
-        if c == '/' {
-            match chars.peek() {
-                Some('/') => {
-                    // 行コメントをスキップ
-                    chars.next();
-                    while let Some(nc) = chars.next() {
-                        if nc == '\n' {
-                            result.push('\n');
-                            break;
+        match name {
+            "if" | "ifdef" | "ifndef" => {
+                let value = active
+                    && match name {
+                        "if" => self.condition(rest, ctx),
+                        _ => {
+                            let defined = self.defined_name(rest, ctx);
+                            defined == (name == "ifdef")
                         }
+                    };
+                conds.push(Cond {
+                    parent: active,
+                    active: value,
+                    // 外側が無効なら，どの枝も選ばれないようにする
+                    taken: value || !active,
+                    seen_else: false,
+                    span,
+                });
+            }
+            "elif" => {
+                let Some(cond) = conds.last() else {
+                    return self.error("#elif without #if", span);
+                };
+                if cond.seen_else {
+                    return self.error("#elif after #else", span);
+                }
+                let value = !cond.taken && self.condition(rest, ctx);
+                let cond = conds.last_mut().unwrap();
+                cond.active = value;
+                cond.taken |= value;
+            }
+            "else" => {
+                let Some(cond) = conds.last_mut() else {
+                    return self.error("#else without #if", span);
+                };
+                if cond.seen_else {
+                    return self.error("#else after #else", span);
+                }
+                cond.active = cond.parent && !cond.taken;
+                cond.taken = true;
+                cond.seen_else = true;
+            }
+            "endif" => {
+                if conds.pop().is_none() {
+                    self.error("#endif without #if", span);
+                }
+            }
+            // 無効な枝の中は条件以外の指令を読まない
+            _ if !active => {}
+            "define" => self.define(rest, ctx),
+            "undef" => {
+                if let Some(name) = self.macro_name(rest, ctx) {
+                    self.macros.remove(&name);
+                }
+            }
+            "include" => self.include(rest, ctx, span),
+            "error" => self.error(format!("#error {}", concat(&trim_spaces(rest))), span),
+            "pragma" => {
+                // once 以外のpragmaは無視する
+                if concat(&trim_spaces(rest)) == "once" {
+                    let key = once_key(&self.out.files[ctx.origin.file].name);
+                    self.once.insert(key);
+                }
+            }
+            // `#` だけの行は何もしない
+            "" => {}
+            _ => self.error(format!("invalid preprocessing directive #{}", name), span),
+        }
+    }
+
+    fn macro_name(&mut self, tokens: &[PpToken], ctx: &Context) -> Option<String> {
+        match next_non_space(tokens, 0).map(|i| &tokens[i]) {
+            Some(token) if token.kind == PpKind::Ident => Some(token.text.clone()),
+            _ => {
+                let span = Span::new(ctx.at, ctx.line.trim_end().chars().count() + 1, 1);
+                self.error("expected macro name", span);
+                None
+            }
+        }
+    }
+
+    /// #ifdef / #ifndef
+    fn defined_name(&mut self, tokens: &[PpToken], ctx: &Context) -> bool {
+        self.macro_name(tokens, ctx)
+            .is_some_and(|name| self.is_defined(&name))
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name) || name == "__LINE__" || name == "__FILE__"
+    }
+
+    fn define(&mut self, tokens: &[PpToken], ctx: &Context) {
+        let Some(name) = self.macro_name(tokens, ctx) else {
+            return;
+        };
+        let mut i = next_non_space(tokens, 0).unwrap() + 1;
+
+        // 名前の直後に空白なしで ( が来たら関数形式マクロ
+        let mut params = None;
+        let mut variadic = false;
+        if tokens.get(i).is_some_and(|t| t.is("(")) {
+            let mut names = Vec::new();
+            loop {
+                let Some(j) = next_non_space(tokens, i + 1) else {
+                    let span = Self::span_of(ctx, &name);
+                    return self.error("expected ')' in macro parameter list", span);
+                };
+                let token = &tokens[j];
+                i = j;
+                if token.is(")") && (names.is_empty() || variadic) {
+                    break;
+                }
+                if token.is("...") && !variadic {
+                    variadic = true;
+                } else if token.kind == PpKind::Ident && !variadic {
+                    names.push(token.text.clone());
+                } else {
+                    let span = Self::span_of(ctx, &token.text);
+                    return self.error(
+                        format!("expected parameter name, found '{}'", token.text),
+                        span,
+                    );
+                }
+                match next_non_space(tokens, i + 1).map(|j| &tokens[j]) {
+                    Some(t) if t.is(",") && !variadic => i = next_non_space(tokens, i + 1).unwrap(),
+                    Some(t) if t.is(")") => {
+                        i = next_non_space(tokens, i + 1).unwrap();
+                        break;
+                    }
+                    _ => {
+                        let span = Self::span_of(ctx, &name);
+                        return self.error("expected ')' in macro parameter list", span);
                     }
-                    continue;
                 }
-                Some('*') => {
-                    // ブロックコメントをスキップ
-                    chars.next();
-                    while let Some(nc) = chars.next() {
-                        if nc == '*' {
-                            if let Some('/') = chars.peek() {
-                                chars.next();
-                                break;
-                            }
-                        }
+            }
+            i += 1;
+            params = Some(names);
+        }
+
+        let body = trim_spaces(&tokens[i..]);
+        self.macros.insert(
+            name,
+            Macro {
+                params,
+                variadic,
+                body,
+            },
+        );
+    }
+
+    fn include(&mut self, tokens: &[PpToken], ctx: &Context, span: Span) {
+        let mut tokens = trim_spaces(tokens);
+        // `#include MACRO` の形なら先に展開する
+        if tokens.first().is_some_and(|t| t.kind == PpKind::Ident) {
+            tokens = trim_spaces(&self.expand(&tokens, &mut Vec::new(), ctx));
+        }
+
+        let text = concat(&tokens);
+        let (path, quoted) =
+            if let Some(path) = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                (path.to_string(), true)
+            } else if let Some(path) = text.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+                (path.to_string(), false)
+            } else {
+                return self.error("expected \"FILENAME\" or <FILENAME>", span);
+            };
+
+        let current = Path::new(&self.out.files[ctx.origin.file].name);
+        let local = quoted.then(|| current.parent().unwrap_or(Path::new("")).join(&path));
+        let found = local
+            .into_iter()
+            .chain(self.include_paths.iter().map(|dir| dir.join(&path)))
+            .find(|candidate| candidate.is_file());
+
+        let (name, source) = match found {
+            Some(found) => (found.to_string_lossy().into_owned(), None),
+            None => match SYSTEM_HEADERS.iter().find(|(name, _)| *name == path) {
+                Some((name, source)) => (format!("<built-in>/{}", name), Some(*source)),
+                None => return self.error(format!("'{}' file not found", path), span),
+            },
+        };
+        if self.once.contains(&once_key(&name)) {
+            return;
+        }
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return self.error("#include nested too deeply", span);
+        }
+        let source = match source {
+            Some(source) => source.to_string(),
+            None => match fs::read_to_string(&name) {
+                Ok(source) => source,
+                Err(e) => return self.error(format!("cannot read '{}': {}", path, e), span),
+            },
+        };
+
+        let file = self.add_file(name, &source);
+        self.depth += 1;
+        self.process_file(file);
+        self.depth -= 1;
+    }
+
+    /// マクロを展開する
+    /// disabledは展開中のマクロ 自分自身を再帰的に展開しないようにする
+    fn expand(
+        &mut self,
+        tokens: &[PpToken],
+        disabled: &mut Vec<String>,
+        ctx: &Context,
+    ) -> Vec<PpToken> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            i += 1;
+            if token.kind != PpKind::Ident || disabled.contains(&token.text) {
+                out.push(token.clone());
+                continue;
+            }
+
+            let Some(m) = self.macros.get(&token.text).cloned() else {
+                match token.text.as_str() {
+                    "__LINE__" => out.push(PpToken::new(PpKind::Number, ctx.origin.line)),
+                    "__FILE__" => {
+                        let name = &self.out.files[ctx.origin.file].name;
+                        let name = PpToken::new(PpKind::Literal, name);
+                        out.push(stringify(&[name]));
                     }
-                    continue;
+                    _ => out.push(token.clone()),
                 }
-                Some(&next) => {
-                    result.push('/');
-                    result.push(next);
-                    chars.next();
+                continue;
+            };
+
+            let body = if let Some(params) = &m.params {
+                // 括弧が続かなければただの識別子
+                let Some(open) = next_non_space(tokens, i).filter(|&j| tokens[j].is("(")) else {
+                    out.push(token.clone());
                     continue;
+                };
+                let Some((args, end)) = collect_args(tokens, open) else {
+                    let span = Self::span_of(ctx, &token.text);
+                    self.error(
+                        format!("unterminated argument list invoking macro '{}'", token.text),
+                        span,
+                    );
+                    out.push(token.clone());
+                    continue;
+                };
+                i = end;
+                match self.arguments(&m, args) {
+                    Ok(args) => self.substitute(&m, &args, disabled, ctx),
+                    Err(found) => {
+                        let expected = params.len();
+                        let span = Self::span_of(ctx, &token.text);
+                        self.error(
+                            format!(
+                                "macro '{}' expects {} arguments, found {}",
+                                token.text, expected, found
+                            ),
+                            span,
+                        );
+                        continue;
+                    }
                 }
-                None => {
-                    result.push('/');
+            } else {
+                paste(m.body.clone())
+            };
+
+            // 展開結果が前後のトークンとくっつかないように空白を挟む
+            disabled.push(token.text.clone());
+            out.push(PpToken::space());
+            out.extend(self.expand(&body, disabled, ctx));
+            out.push(PpToken::space());
+            disabled.pop();
+        }
+        out
+    }
+
+    /// 実引数の数を確かめる 可変長引数は最後の1つにまとめる
+    fn arguments(
+        &self,
+        m: &Macro,
+        mut args: Vec<Vec<PpToken>>,
+    ) -> Result<Vec<Vec<PpToken>>, usize> {
+        let expected = m.params.as_ref().unwrap().len();
+        // F() は引数0個
+        if expected == 0 && args.len() == 1 && args[0].is_empty() {
+            args.clear();
+        }
+        if m.variadic {
+            if args.len() < expected {
+                return Err(args.len());
+            }
+            let rest = args.split_off(expected);
+            let mut va_args = Vec::new();
+            for (n, arg) in rest.into_iter().enumerate() {
+                if n > 0 {
+                    va_args.push(PpToken::new(PpKind::Punct, ","));
+                }
+                va_args.extend(arg);
+            }
+            args.push(va_args);
+        } else if args.len() != expected {
+            return Err(args.len());
+        }
+        Ok(args)
+    }
+
+    /// 仮引数を実引数に置き換える
+    fn substitute(
+        &mut self,
+        m: &Macro,
+        args: &[Vec<PpToken>],
+        disabled: &mut Vec<String>,
+        ctx: &Context,
+    ) -> Vec<PpToken> {
+        let body = &m.body;
+        let mut out: Vec<PpToken> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let token = &body[i];
+
+            // #x
+            if token.is("#") {
+                let next = next_non_space(body, i + 1);
+                if let Some(k) = next.and_then(|j| m.param_index(&body[j])) {
+                    out.push(stringify(&args[k]));
+                    i = next.unwrap() + 1;
                     continue;
                 }
             }
-        } else {
-            result.push(c);
+
+            if let Some(k) = m.param_index(token) {
+                // ## の両側は展開せずに使う
+                let pasted = out
+                    .iter()
+                    .rev()
+                    .find(|t| t.kind != PpKind::Space)
+                    .is_some_and(|t| t.is("##"))
+                    || next_non_space(body, i + 1).is_some_and(|j| body[j].is("##"));
+                if pasted {
+                    out.extend(args[k].clone());
+                } else {
+                    out.extend(self.expand(&args[k], disabled, ctx));
+                }
+            } else {
+                out.push(token.clone());
+            }
+            i += 1;
+        }
+        paste(out)
+    }
+
+    /// #if / #elif の条件
+    fn condition(&mut self, tokens: &[PpToken], ctx: &Context) -> bool {
+        // defined X と defined(X) は展開前に置き換える
+        let mut replaced = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            if tokens[i].kind == PpKind::Ident && tokens[i].text == "defined" {
+                let mut j = next_non_space(tokens, i + 1);
+                let paren = j.is_some_and(|j| tokens[j].is("("));
+                if paren {
+                    j = j.and_then(|j| next_non_space(tokens, j + 1));
+                }
+                let Some(name) = j.filter(|&j| tokens[j].kind == PpKind::Ident) else {
+                    let span = Self::span_of(ctx, "defined");
+                    self.error("expected macro name after 'defined'", span);
+                    return false;
+                };
+                i = name + 1;
+                if paren {
+                    match next_non_space(tokens, i) {
+                        Some(close) if tokens[close].is(")") => i = close + 1,
+                        _ => {
+                            let span = Self::span_of(ctx, "defined");
+                            self.error("expected ')' after 'defined'", span);
+                            return false;
+                        }
+                    }
+                }
+                let value = self.is_defined(&tokens[name].text) as u8;
+                replaced.push(PpToken::new(PpKind::Number, value));
+                continue;
+            }
+            replaced.push(tokens[i].clone());
+            i += 1;
+        }
+
+        // 展開しても残った識別子は0
+        let expanded: Vec<PpToken> = self
+            .expand(&replaced, &mut Vec::new(), ctx)
+            .into_iter()
+            .filter(|t| t.kind != PpKind::Space)
+            .map(|t| match t.kind {
+                PpKind::Ident => PpToken::new(PpKind::Number, 0),
+                _ => t,
+            })
+            .collect();
+
+        let mut eval = Eval {
+            tokens: &expanded,
+            pos: 0,
+        };
+        let result = eval
+            .ternary()
+            .and_then(|value| match expanded.get(eval.pos) {
+                None => Ok(value),
+                Some(token) => Err(format!("unexpected '{}' in #if", token.text)),
+            });
+        match result {
+            Ok(value) => value != 0,
+            Err(message) => {
+                let span = Self::span_of(ctx, "if");
+                self.error(message, span);
+                false
+            }
+        }
+    }
+}
+
+/// #if の定数式を評価する
+struct Eval<'a> {
+    tokens: &'a [PpToken],
+    pos: usize,
+}
+
+impl Eval<'_> {
+    fn peek(&self) -> Option<&PpToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let matched = self.peek().is_some_and(|t| t.is(punct));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn ternary(&mut self) -> Result<i64, String> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.ternary()?;
+        if !self.eat(":") {
+            return Err("expected ':' in #if".to_string());
+        }
+        let els = self.ternary()?;
+        Ok(if cond != 0 { then } else { els })
+    }
+
+    fn precedence(op: &str) -> Option<u8> {
+        Some(match op {
+            "||" => 1,
+            "&&" => 2,
+            "|" => 3,
+            "^" => 4,
+            "&" => 5,
+            "==" | "!=" => 6,
+            "<" | ">" | "<=" | ">=" => 7,
+            "<<" | ">>" => 8,
+            "+" | "-" => 9,
+            "*" | "/" | "%" => 10,
+            _ => return None,
+        })
+    }
+
+    fn binary(&mut self, min: u8) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+        while let Some(token) = self.peek().filter(|t| t.kind == PpKind::Punct) {
+            let op = token.text.clone();
+            let Some(prec) = Self::precedence(&op).filter(|&p| p > min) else {
+                break;
+            };
+            self.pos += 1;
+            let rhs = self.binary(prec)?;
+            lhs = match op.as_str() {
+                "||" => (lhs != 0 || rhs != 0) as i64,
+                "&&" => (lhs != 0 && rhs != 0) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                "/" | "%" if rhs == 0 => return Err("division by zero in #if".to_string()),
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.eat("-") {
+            return Ok(self.unary()?.wrapping_neg());
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        if self.eat("!") {
+            return Ok((self.unary()? == 0) as i64);
+        }
+        if self.eat("~") {
+            return Ok(!self.unary()?);
+        }
+        if self.eat("(") {
+            let value = self.ternary()?;
+            if !self.eat(")") {
+                return Err("expected ')' in #if".to_string());
+            }
+            return Ok(value);
+        }
+
+        let Some(token) = self.peek().cloned() else {
+            return Err("expected value in #if".to_string());
+        };
+        self.pos += 1;
+        match token.kind {
+            PpKind::Number => parse_integer(&token.text)
+                .ok_or_else(|| format!("invalid integer constant '{}' in #if", token.text)),
+            PpKind::Literal if token.text.starts_with('\'') => parse_char(&token.text)
+                .ok_or_else(|| format!("invalid character constant {} in #if", token.text)),
+            _ => Err(format!("unexpected '{}' in #if", token.text)),
         }
     }
-    *src = result;
+}
+
+fn parse_integer(text: &str) -> Option<i64> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let (digits, radix) = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        (bin, 2)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+fn parse_char(text: &str) -> Option<i64> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let c = match chars.next()? {
+        '\\' => match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            other => other,
+        },
+        c => c,
+    };
+    chars.next().is_none().then_some(c as i64)
+}
+
+#[test]
+fn test_preprocess() {
+    let source = "#define SQUARE(x) ((x) * (x))\n\
+                  #define CAT(a, b) a ## b\n\
+                  #define STR(x) #x\n\
+                  #if defined(SQUARE) && 1 + 1 == 2\n\
+                  int CAT(ab, c) = SQUARE(2);\n\
+                  #else\n\
+                  int no;\n\
+                  #endif\n\
+                  char *s = STR(a \"b\");\n\
+                  int line = __LINE__;";
+    let out = preprocess("a.c", source, &[]);
+    assert!(out.errors.is_empty());
+    let lines: Vec<&str> = out.text.lines().map(str::trim).collect();
+    assert_eq!(lines[4], "int  abc  =  ((2) * (2)) ;");
+    assert_eq!(lines[6], "");
+    assert_eq!(lines[8], "char *s =  \"a \\\"b\\\"\" ;");
+    assert_eq!(lines[9], "int line = 10;");
+
+    // コメントは空白に，リテラルの中はそのまま
+    let source = "#define X 1 // one\n\
+                  /* #define X 2\n\
+                  */ char *s = \"//X/*X*/\"; int x = X; /* a */";
+    let out = preprocess("a.c", source, &[]);
+    assert!(out.errors.is_empty());
+    let lines: Vec<&str> = out.text.lines().map(str::trim).collect();
+    assert_eq!(lines[1], "");
+    assert_eq!(lines[2], "char *s = \"//X/*X*/\"; int x =  1 ;");
+
+    let out = preprocess("a.c", "#if 1\n#error oops\n#endif\n#if 0", &[]);
+    let messages: Vec<String> = out.errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        ["#error oops", "unterminated conditional directive"]
+    );
+    assert_eq!(out.errors[0].span.unwrap().line.get(), 2);
+
+    // 実引数は次の行に続いてよい 行数は変わらない
+    let source = "#define ADD(a, b) ((a) + (b))\n\
+                  int x = ADD(1,\n\
+                  \t2) + ADD(3,\n\
+                  4);\n\
+                  int line = __LINE__;\n\
+                  int y = ADD(5,\n\
+                  #define Z";
+    let out = preprocess("a.c", source, &[]);
+    let lines: Vec<&str> = out.text.lines().map(str::trim).collect();
+    assert_eq!(lines[1], "int x =  ((1) + (2))  +  ((3) + (4)) ;");
+    assert_eq!(lines[2..4], ["", ""]);
+    assert_eq!(lines[4], "int line = 5;");
+    let messages: Vec<String> = out.errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        messages,
+        ["unterminated argument list invoking macro 'ADD'"]
+    );
+    assert_eq!(out.errors[0].span.unwrap().line.get(), 6);
+
+    // 同梱のヘッダは埋め込んだものを使い，-I のディレクトリにあればそちらを優先する
+    let out = preprocess("a.c", "#include <print.h>\n#include \"print.h\"", &[]);
+    assert!(out.errors.is_empty());
+    let names: Vec<&str> = out.files.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "a.c",
+            "<built-in>/print.h",
+            "<built-in>/cynops.h",
+            "<built-in>/print.h"
+        ]
+    );
+    let dir = std::env::temp_dir().join(format!("cynops-include-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("cynops.h"), "int overridden;").unwrap();
+    let out = preprocess("a.c", "#include <print.h>", std::slice::from_ref(&dir));
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(out.errors.is_empty());
+    assert!(out.text.contains("int overridden;"));
 }
@@ -1,11 +1,13 @@
 use super::TypedExpr;
 use super::{Block, Ident, types::FunctionSig};
 use crate::sema::ast::Symbol;
+use crate::span::Span;
 use std::hash::Hash;
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub enum DeclStmt {
     InitVec(Vec<Init>),
+    Static(Vec<Init>), // 静的記憶域 グローバル領域に置かれる
     Struct(Struct),
     Union(Union),
     Enum(Enum),
@@ -16,6 +18,10 @@ impl DeclStmt {
         DeclStmt::InitVec(vec)
     }
 
+    pub fn r#static(vec: Vec<Init>) -> Self {
+        DeclStmt::Static(vec)
+    }
+
     pub fn r#struct(strct: Struct) -> Self {
         DeclStmt::Struct(strct)
     }
@@ -78,11 +84,11 @@ impl Enum {
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct EnumMember {
     pub symbol: Symbol,
-    pub value: Option<usize>, // 明示的な値がある場合
+    pub value: usize, // 自動採番も含めて確定した値
 }
 
 impl EnumMember {
-    pub fn new(ident: Symbol, value: Option<usize>) -> Self {
+    pub fn new(ident: Symbol, value: usize) -> Self {
         EnumMember {
             symbol: ident,
             value,
@@ -107,10 +113,20 @@ pub enum InitData {
 pub struct Init {
     pub l: Symbol,
     pub r: Option<InitData>,
+    pub span: Option<Span>, // 宣言された名前の位置
 }
 
 impl Init {
     pub fn new(r: Symbol, l: Option<InitData>) -> Self {
-        Init { l: r, r: l }
+        Init {
+            l: r,
+            r: l,
+            span: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
     }
 }
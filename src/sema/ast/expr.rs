@@ -1,5 +1,6 @@
 use super::{Ident, Symbol, Type};
 use crate::op::*;
+use crate::span::Span;
 use ordered_float::OrderedFloat;
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -192,10 +193,11 @@ impl SemaExpr {
         SemaExpr::Comma(Comma { assigns })
     }
 }
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[derive(Debug, Clone)]
 pub struct TypedExpr {
     pub r#type: Type,
     pub r#expr: SemaExpr,
+    pub span: Option<Span>, // 比較やハッシュには使わない
 }
 
 impl TypedExpr {
@@ -203,6 +205,27 @@ impl TypedExpr {
         Self {
             r#type,
             r#expr: expr,
+            span: None,
         }
     }
+
+    pub fn with_span(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+impl PartialEq for TypedExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.r#type == other.r#type && self.r#expr == other.r#expr
+    }
+}
+
+impl Eq for TypedExpr {}
+
+impl std::hash::Hash for TypedExpr {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.r#type.hash(state);
+        self.r#expr.hash(state);
+    }
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::sema::r#type::TypeError;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::{Rc, Weak};
@@ -30,6 +31,18 @@ impl Symbol {
         }
         None
     }
+
+    // const変数の畳み込み済みの値を検索（親も遡る）
+    pub fn get_const(&self) -> Option<isize> {
+        let mut scope = self.scope.get_scope();
+        while let Some(s) = scope {
+            if s.borrow().symbols.contains_key(&self.ident) {
+                return s.borrow().constants.get(&self.ident).copied();
+            }
+            scope = s.borrow().parent.as_ref().and_then(|p| p.upgrade());
+        }
+        None
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -91,6 +104,7 @@ impl From<&ScopeId> for String {
 pub struct ScopeNode {
     pub id: ScopeId,
     pub symbols: HashMap<Ident, Type>,
+    pub constants: HashMap<Ident, isize>, // 定数で初期化されたconst変数の値
     pub parent: Option<Weak<RefCell<ScopeNode>>>,
     pub children: Vec<Rc<RefCell<ScopeNode>>>,
 }
@@ -107,6 +121,7 @@ impl ScopeNode {
         Rc::new(RefCell::new(ScopeNode {
             id,
             symbols: HashMap::new(),
+            constants: HashMap::new(),
             parent: parent.as_ref().map(|p| Rc::downgrade(p)),
             children: Vec::new(),
         }))
@@ -123,6 +138,13 @@ pub struct Session {
     pub root_scope: Rc<RefCell<ScopeNode>>,
     pub current_scope: Rc<RefCell<ScopeNode>>,
     pub id: usize,
+    pub errors: Vec<TypeError>, // 変換中に見つけた型エラー 型検査でまとめて報告する
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Session {
@@ -132,6 +154,7 @@ impl Session {
             root_scope: Rc::clone(&root),
             current_scope: root,
             id: 0,
+            errors: Vec::new(),
         }
     }
 
@@ -157,16 +180,24 @@ impl Session {
     }
     // 変数検索（親も遡る）
 
-    pub fn get_ident_scope(&self, name: &Ident) -> ScopePtr {
+    // 見つからなければ None 未定義の報告は型検査に任せる
+    pub fn get_ident_scope(&self, name: &Ident) -> Option<ScopePtr> {
         let mut scope = Some(Rc::clone(&self.current_scope));
 
         while let Some(s) = scope {
             if s.borrow().symbols.contains_key(name) {
-                return ScopePtr::new(Rc::downgrade(&s)); // このスコープに定義されている
+                return Some(ScopePtr::new(Rc::downgrade(&s))); // このスコープに定義されている
             }
             scope = s.borrow().parent.as_ref().and_then(|p| p.upgrade());
         }
-        panic!("{} is undefined", name.to_string())
+        None
+    }
+
+    pub fn register_constant(&mut self, name: Ident, value: isize) {
+        self.current_scope
+            .borrow_mut()
+            .constants
+            .insert(name, value);
     }
 
     pub fn register_function(&mut self, name: Ident, variants: Type) {
@@ -1,6 +1,7 @@
 use crate::sema::ast::ScopePtr;
 
 use super::{DeclStmt, Ident, TypedExpr};
+use crate::span::Span;
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Block {
     pub scope_par: ScopePtr,
@@ -86,11 +87,13 @@ pub struct Return {
 pub struct Label {
     pub name: Ident,
     pub stmt: Box<Stmt>,
+    pub span: Option<Span>, // ラベル名の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Goto {
     pub label: Ident,
+    pub span: Option<Span>, // 飛び先のラベル名の位置
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -155,8 +158,8 @@ pub enum Stmt {
     Goto(Goto),
     Label(Label),
     Block(Block),
-    Break,
-    Continue,
+    Break(Option<Span>),    // break の位置
+    Continue(Option<Span>), // continue の位置
 }
 impl Stmt {
     pub fn expr(expr: TypedExpr) -> Self {
@@ -177,14 +180,15 @@ impl Stmt {
         })
     }
 
-    pub fn goto(label: Ident) -> Self {
-        Stmt::Goto(Goto { label })
+    pub fn goto(label: Ident, span: Option<Span>) -> Self {
+        Stmt::Goto(Goto { label, span })
     }
 
-    pub fn label(name: Ident, stmt: Stmt) -> Self {
+    pub fn label(name: Ident, stmt: Stmt, span: Option<Span>) -> Self {
         Stmt::Label(Label {
             name,
             stmt: Box::new(stmt),
+            span,
         })
     }
 
@@ -192,11 +196,11 @@ impl Stmt {
         Stmt::Block(block)
     }
 
-    pub fn r#break() -> Self {
-        Stmt::Break
+    pub fn r#break(span: Option<Span>) -> Self {
+        Stmt::Break(span)
     }
 
-    pub fn r#continue() -> Self {
-        Stmt::Continue
+    pub fn r#continue(span: Option<Span>) -> Self {
+        Stmt::Continue(span)
     }
 }
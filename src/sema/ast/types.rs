@@ -41,6 +41,7 @@ pub enum Type {
     Array(Array),
     Unresolved, //後でなくすかも
     Typedef(Symbol),
+    Const(Box<Type>),
 }
 
 impl std::fmt::Debug for Type {
@@ -68,6 +69,9 @@ impl PartialEq for Type {
                 Some(ty) => other_type.eq(&ty),
                 None => false,
             },
+            // const修飾は型の同一性には影響しない(検査はsema::typeで別に行う)
+            (Type::Const(inner), other_type) => inner.as_ref().eq(other_type),
+            (other_type, Type::Const(inner)) => other_type.eq(inner),
             // その他の場合は標準的な比較
             (Type::Void, Type::Void) => true,
             (Type::Int, Type::Int) => true,
@@ -100,6 +104,8 @@ impl Hash for Type {
                     sym.hash(state);
                 }
             }
+            // PartialEqに合わせて修飾を無視する
+            Type::Const(inner) => inner.hash(state),
             Type::Void => std::mem::discriminant(self).hash(state),
             Type::Error => std::mem::discriminant(self).hash(state),
             Type::Int => std::mem::discriminant(self).hash(state),
@@ -148,6 +154,9 @@ impl Type {
     pub fn pointer(p: Type) -> Self {
         Type::Pointer(Box::new(p))
     }
+    pub fn r#const(ty: Type) -> Self {
+        Type::Const(Box::new(ty))
+    }
     /// Type を Rust 風の表記に変換する
     pub fn to_rust_format(&self) -> String {
         match self {
@@ -199,6 +208,7 @@ impl Type {
                 )
             }
             Type::Typedef(this) => this.get_type().unwrap().to_rust_format(),
+            Type::Const(inner) => format!("const {}", inner.to_rust_format()),
         }
     }
 }
@@ -224,6 +234,7 @@ impl Type {
             }
             // その他の型はそのまま返す（ただし内部のTypedefも再帰的にflat化）
             Type::Pointer(inner) => Type::Pointer(Box::new(inner.flat())),
+            Type::Const(inner) => Type::Const(Box::new(inner.flat())),
             Type::Array(arr) => Type::Array(Array {
                 array_of: Box::new(arr.array_of.flat()),
                 length: arr.length.clone(),
@@ -243,6 +254,20 @@ impl Type {
     }
 }
 
+impl Type {
+    // 一番外側のconst修飾を外す 値として読み出した式の型に使う
+    pub fn unqualified(&self) -> Type {
+        match self.flat() {
+            Type::Const(inner) => inner.unqualified(),
+            other => other,
+        }
+    }
+
+    pub fn is_const(&self) -> bool {
+        matches!(self.flat(), Type::Const(_))
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct FunctionSig {
     pub symbol: Symbol,
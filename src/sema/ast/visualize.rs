@@ -313,7 +313,8 @@ impl Visualize for Symbol {
 
         print_branch(
             "Type",
-            &self.get_type().unwrap().to_rust_format(),
+            // 未定義の変数は error と表示する
+            &self.get_type().unwrap_or(Type::Error).to_rust_format(),
             indent + 1,
             false,
             &next_prefix,
@@ -507,10 +508,10 @@ impl Visualize for Stmt {
             Stmt::Block(block) => {
                 block.visualize_with_context(indent, is_last, prefix);
             }
-            Stmt::Break => {
+            Stmt::Break(_) => {
                 print_branch("Break", "", indent, is_last, prefix);
             }
-            Stmt::Continue => {
+            Stmt::Continue(_) => {
                 print_branch("Continue", "", indent, is_last, prefix);
             }
         }
@@ -845,8 +846,12 @@ impl Visualize for DeclStmt {
 
     fn visualize_with_context(&self, indent: usize, is_last: bool, prefix: &[bool]) {
         match self {
-            DeclStmt::InitVec(inits) => {
-                print_branch("DeclStmt", "InitVec", indent, is_last, prefix);
+            DeclStmt::InitVec(inits) | DeclStmt::Static(inits) => {
+                let kind = match self {
+                    DeclStmt::Static(_) => "Static",
+                    _ => "InitVec",
+                };
+                print_branch("DeclStmt", kind, indent, is_last, prefix);
                 let next_prefix = extend_prefix(prefix, !is_last);
 
                 for (i, init) in inits.iter().enumerate() {
@@ -1016,10 +1021,7 @@ impl Visualize for EnumMember {
     }
 
     fn visualize_with_context(&self, indent: usize, is_last: bool, prefix: &[bool]) {
-        let variant_info = match &self.value {
-            Some(value) => format!("{} = {}", self.symbol.ident.name, value),
-            None => self.symbol.ident.name.clone(),
-        };
+        let variant_info = format!("{} = {}", self.symbol.ident.name, self.value);
         print_branch("Variant", &variant_info, indent, is_last, prefix);
     }
 }
@@ -1158,9 +1160,74 @@ impl OneLine for TypedExpr {
     }
 }
 
+impl OneLine for InitData {
+    fn oneline(&self) -> String {
+        match self {
+            InitData::Expr(expr) => expr.oneline(),
+            InitData::Compound(items) => {
+                let items: Vec<String> = items.iter().map(|x| x.oneline()).collect();
+                format!("{{{}}}", items.join(", "))
+            }
+        }
+    }
+}
+
+impl OneLine for Init {
+    fn oneline(&self) -> String {
+        let ty = self
+            .l
+            .get_type()
+            .map_or("?".into(), |ty| ty.to_rust_format());
+        match &self.r {
+            Some(r) => format!("{}: {} = {}", self.l.oneline(), ty, r.oneline()),
+            None => format!("{}: {}", self.l.oneline(), ty),
+        }
+    }
+}
+
+// 文の先頭行だけ 本体の文は含めない
+impl OneLine for Stmt {
+    fn oneline(&self) -> String {
+        let opt = |x: &Option<Box<TypedExpr>>| x.as_ref().map_or(String::new(), |x| x.oneline());
+        match self {
+            Stmt::ExprStmt(expr) => expr.oneline(),
+            Stmt::DeclStmt(DeclStmt::InitVec(inits)) => {
+                let inits: Vec<String> = inits.iter().map(|x| x.oneline()).collect();
+                inits.join(", ")
+            }
+            Stmt::DeclStmt(DeclStmt::Static(inits)) => {
+                let inits: Vec<String> = inits.iter().map(|x| x.oneline()).collect();
+                format!("static {}", inits.join(", "))
+            }
+            Stmt::DeclStmt(_) => "declaration".to_string(),
+            Stmt::Control(Control::If(this)) => format!("if ({})", this.cond.oneline()),
+            Stmt::Control(Control::While(this)) => format!("while ({})", this.cond.oneline()),
+            Stmt::Control(Control::DoWhile(this)) => {
+                format!("do ... while ({})", this.cond.oneline())
+            }
+            Stmt::Control(Control::For(this)) => format!(
+                "for ({}; {}; {})",
+                opt(&this.init),
+                opt(&this.cond),
+                opt(&this.step)
+            ),
+            Stmt::Control(Control::Switch(this)) => format!("switch ({})", this.cond.oneline()),
+            Stmt::Return(this) => match &this.value {
+                Some(value) => format!("return {}", value.oneline()),
+                None => "return".to_string(),
+            },
+            Stmt::Goto(this) => format!("goto {}", this.label.name),
+            Stmt::Label(this) => format!("{}:", this.name.name),
+            Stmt::Block(_) => "{ ... }".to_string(),
+            Stmt::Break(_) => "break".to_string(),
+            Stmt::Continue(_) => "continue".to_string(),
+        }
+    }
+}
+
 impl Visualize for ScopeNode {
     fn visualize(&self) {
-        println!("Scope Tree");
+        emit("Scope Tree\n");
         self.visualize_with_context(0, true, &[]);
     }
 
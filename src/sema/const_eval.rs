@@ -67,7 +67,9 @@ fn eval_sema_expr(expr: &SemaExpr, expr_type: &Type) -> Result<isize, String> {
         SemaExpr::Sizeof(sizeof) => eval_typed_sizeof(sizeof),
 
         // 実行時要素は処理しない
-        SemaExpr::Symbol(_) => Err("変数は定数計算できません".to_string()),
+        SemaExpr::Symbol(symbol) => symbol
+            .get_const()
+            .ok_or_else(|| "変数は定数計算できません".to_string()),
         SemaExpr::Call(_) => Err("関数呼び出しは定数計算できません".to_string()),
         SemaExpr::Subscript(_) => Err("配列添え字は定数計算できません".to_string()),
         SemaExpr::MemberAccess(_) => Err("メンバアクセスは定数計算できません".to_string()),
@@ -87,6 +89,7 @@ fn eval_typed_unary(unary: &crate::sema::ast::Unary) -> Result<isize, String> {
     let operand = eval_const_typed_expr(&unary.expr)?;
 
     match unary.op {
+        UnaryOp::Minus => Ok(-operand),
         UnaryOp::Bang => Ok(if operand != 0 { 0 } else { 1 }),
         UnaryOp::Tilde => Ok(!operand),
         UnaryOp::Ampersand => Err("アドレス演算子は定数計算できません".to_string()),
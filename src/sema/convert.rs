@@ -1,5 +1,6 @@
 use super::ast as new_ast;
 use super::ast::*;
+use super::r#type::TypeError;
 use crate::ast as old_ast;
 
 pub fn program(program: &old_ast::Program, session: &mut Session) -> new_ast::Program {
@@ -124,6 +125,7 @@ fn convert_type(ty: &old_ast::Type, session: &mut Session) -> new_ast::Type {
             },
         )),
         old_ast::Type::Typedef(ident) => session.get_type(&ident.as_same()).unwrap(),
+        old_ast::Type::Const(inner) => new_ast::Type::r#const(convert_type(inner, session)),
     }
 }
 
@@ -197,18 +199,45 @@ fn convert_union(u: &old_ast::Union, session: &mut Session) -> new_ast::Union {
 }
 
 fn convert_enum(e: &old_ast::Enum, session: &mut Session) -> new_ast::Enum {
-    let variants = e
-        .variants
-        .iter()
-        .map(|v| {
-            new_ast::EnumMember::new(
-                Symbol::new(v.ident.as_same(), session.current_scope()),
-                v.value,
-            )
-        })
-        .collect();
+    let ident = e.ident.as_ref().map(|i| i.as_same());
+    let mut variants: Vec<new_ast::EnumMember> = Vec::new();
+
+    for v in &e.variants {
+        // 値の指定がなければ直前の値+1 (先頭は0)
+        let next = variants
+            .last()
+            .map_or(0, |prev| (prev.value + 1) % (1 << 16));
+        let value = match &v.value {
+            Some(expr) => {
+                let resolved =
+                    super::r#type::resolve_typed_expr(&convert_expr(expr, session), session).result;
+                let span = resolved.span.or(v.ident.span);
+                match resolved.eval_const() {
+                    Ok(value) => value.rem_euclid(1 << 16) as usize, // intは16bit
+                    Err(_) => {
+                        // 型検査で報告し，値は指定がなかったものとして続ける
+                        session
+                            .errors
+                            .push(TypeError::NonConstantEnumerator(v.ident.name.clone()).at(span));
+                        next
+                    }
+                }
+            }
+            None => next,
+        };
+        variants.push(new_ast::EnumMember::new(
+            Symbol::new(v.ident.as_same(), session.current_scope()),
+            value,
+        ));
+
+        // 後続の列挙子の値から参照できるようにその都度登録する
+        let ty = new_ast::Type::Enum(new_ast::Enum::new(ident.clone(), variants.clone()));
+        variants
+            .iter()
+            .for_each(|x| session.register_symbols(x.symbol.ident.clone(), ty.clone()));
+    }
 
-    let converted = new_ast::Enum::new(e.ident.as_ref().map(|i| i.as_same()), variants);
+    let converted = new_ast::Enum::new(ident, variants);
     let ty = new_ast::Type::Enum(converted.clone());
 
     // 名前がある場合はsessionに登録
@@ -224,6 +253,18 @@ fn convert_enum(e: &old_ast::Enum, session: &mut Session) -> new_ast::Enum {
     converted
 }
 
+// 列挙子であればその値を返す
+fn enum_constant(symbol: &Symbol) -> Option<usize> {
+    match symbol.get_type()? {
+        new_ast::Type::Enum(e) => e
+            .variants
+            .iter()
+            .find(|v| v.symbol == *symbol)
+            .map(|v| v.value),
+        _ => None,
+    }
+}
+
 fn convert_member_decl(m: &old_ast::MemberDecl, session: &mut Session) -> Symbol {
     let ty = convert_type(&m.ty, session);
     session.register_symbols(m.ident.as_same(), ty);
@@ -240,13 +281,15 @@ fn convert_stmt(stmt: &old_ast::Stmt, session: &mut Session) -> new_ast::Stmt {
         old_ast::Stmt::Return(ret) => {
             new_ast::Stmt::r#return(ret.value.as_ref().map(|v| convert_expr(v, session)))
         }
-        old_ast::Stmt::Goto(goto) => new_ast::Stmt::goto(goto.label.as_same()),
-        old_ast::Stmt::Label(label) => {
-            new_ast::Stmt::label(label.name.as_same(), convert_stmt(&label.stmt, session))
-        }
+        old_ast::Stmt::Goto(goto) => new_ast::Stmt::goto(goto.label.as_same(), goto.label.span),
+        old_ast::Stmt::Label(label) => new_ast::Stmt::label(
+            label.name.as_same(),
+            convert_stmt(&label.stmt, session),
+            label.name.span,
+        ),
         old_ast::Stmt::Block(block) => new_ast::Stmt::block(*convert_block(block, session)),
-        old_ast::Stmt::Break => new_ast::Stmt::r#break(),
-        old_ast::Stmt::Continue => new_ast::Stmt::r#continue(),
+        old_ast::Stmt::Break(span) => new_ast::Stmt::r#break(*span),
+        old_ast::Stmt::Continue(span) => new_ast::Stmt::r#continue(*span),
     }
 }
 
@@ -326,6 +369,13 @@ fn convert_decl_stmt(decl: &old_ast::DeclStmt, session: &mut Session) -> new_ast
                 .collect();
             new_ast::DeclStmt::init_vec(converted_inits)
         }
+        old_ast::DeclStmt::Static(inits) => {
+            let converted_inits = inits
+                .iter()
+                .map(|init| convert_init(init, session))
+                .collect();
+            new_ast::DeclStmt::r#static(converted_inits)
+        }
         old_ast::DeclStmt::Struct(s) => new_ast::DeclStmt::r#struct(convert_struct(s, session)),
         old_ast::DeclStmt::Union(u) => new_ast::DeclStmt::union(convert_union(u, session)),
         old_ast::DeclStmt::Enum(e) => new_ast::DeclStmt::r#enum(convert_enum(e, session)),
@@ -346,7 +396,18 @@ fn convert_init(init: &old_ast::Init, session: &mut Session) -> new_ast::Init {
     let member_decl = convert_member_decl(&init.r, session);
     let converted_init_data = init.l.as_ref().map(|data| convert_init_data(data, session));
 
-    new_ast::Init::new(member_decl, converted_init_data)
+    // 定数で初期化されたconstスカラーは配列長などに使えるよう値を覚えておく
+    if let Some(new_ast::InitData::Expr(expr)) = &converted_init_data {
+        let ty = member_decl.get_type().unwrap();
+        if ty.is_const() && matches!(ty.unqualified(), new_ast::Type::Int | new_ast::Type::Char) {
+            let resolved = super::r#type::resolve_typed_expr(expr, session).result;
+            if let Ok(value) = resolved.eval_const() {
+                session.register_constant(member_decl.ident.clone(), value);
+            }
+        }
+    }
+
+    new_ast::Init::new(member_decl, converted_init_data).with_span(init.r.ident.span)
 }
 
 fn convert_init_data(data: &old_ast::InitData, session: &mut Session) -> new_ast::InitData {
@@ -381,9 +442,16 @@ fn convert_expr(expr: &old_ast::Expr, session: &mut Session) -> new_ast::TypedEx
         old_ast::Expr::String(s) => new_ast::SemaExpr::string(s.clone()),
         old_ast::Expr::Ident(ident) => {
             // Identを解決してSymbolに変換
-            let symbol =
-                new_ast::Symbol::new(ident.as_same(), session.get_ident_scope(&ident.as_same()));
-            new_ast::SemaExpr::ident(symbol)
+            // 未定義なら今のスコープに置いておけば型検査で UndefinedVariable になる
+            let scope = session
+                .get_ident_scope(&ident.as_same())
+                .unwrap_or_else(|| session.current_scope());
+            let symbol = new_ast::Symbol::new(ident.as_same(), scope);
+            // 列挙子はint定数に置き換える
+            match enum_constant(&symbol) {
+                Some(value) => new_ast::SemaExpr::num_int(value),
+                None => new_ast::SemaExpr::ident(symbol),
+            }
         }
         old_ast::Expr::NumInt(n) => new_ast::SemaExpr::num_int(*n),
         old_ast::Expr::NumFloat(f) => new_ast::SemaExpr::num_float(*f),
@@ -434,5 +502,5 @@ fn convert_expr(expr: &old_ast::Expr, session: &mut Session) -> new_ast::TypedEx
     };
 
     // とりあえずすべての式の型をUnresolvedにする
-    new_ast::TypedExpr::new(new_ast::Type::Unresolved, sema_expr)
+    new_ast::TypedExpr::new(new_ast::Type::Unresolved, sema_expr).with_span(expr.span())
 }
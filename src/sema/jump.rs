@@ -0,0 +1,118 @@
+use super::ast::*;
+use super::r#type::TypeError;
+use std::collections::HashMap;
+
+// goto文とラベルの整合性を関数ごとに検査する
+// 各地点で有効な宣言の列(chain)を記録し，
+// ラベル地点のchainがgoto地点のchainの先頭部分であれば
+// 宣言を飛び越えずに到達できる
+// break と continue が抜ける先のループや switch の中にあるかもここで見る
+pub fn check_function(body: &Block) -> Vec<TypeError> {
+    let mut walker = Walker::default();
+    walker.block(body);
+
+    let mut errors = walker.errors;
+    for (goto, from) in &walker.gotos {
+        let name = goto.label.name.clone();
+        match walker.labels.get(&goto.label) {
+            None => errors.push(TypeError::UndefinedLabel(name).at(goto.span)),
+            Some(to) if !from.starts_with(to) => {
+                errors.push(TypeError::JumpPastDeclaration(name).at(goto.span))
+            }
+            Some(_) => {}
+        }
+    }
+    errors
+}
+
+#[derive(Default)]
+struct Walker {
+    chain: Vec<usize>,
+    counter: usize,
+    labels: HashMap<Ident, Vec<usize>>,
+    gotos: Vec<(Goto, Vec<usize>)>,
+    loops: usize,    // 囲んでいるループの数
+    switches: usize, // 囲んでいる switch の数
+    errors: Vec<TypeError>,
+}
+
+impl Walker {
+    fn declare(&mut self) {
+        self.counter += 1;
+        self.chain.push(self.counter);
+    }
+
+    // スコープを抜けたら宣言を戻す
+    fn scoped<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        let len = self.chain.len();
+        f(self);
+        self.chain.truncate(len);
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.stmts(&block.statements);
+    }
+
+    fn stmts(&mut self, stmts: &[Box<Stmt>]) {
+        self.scoped(|this| stmts.iter().for_each(|s| this.stmt(s)));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.block(block),
+            Stmt::DeclStmt(DeclStmt::InitVec(inits)) => inits.iter().for_each(|_| self.declare()),
+            Stmt::Control(control) => self.control(control),
+            Stmt::Goto(goto) => self.gotos.push((goto.clone(), self.chain.clone())),
+            Stmt::Break(span) if self.loops == 0 && self.switches == 0 => {
+                self.errors.push(TypeError::BreakOutsideLoop.at(*span))
+            }
+            Stmt::Continue(span) if self.loops == 0 => {
+                self.errors.push(TypeError::ContinueOutsideLoop.at(*span))
+            }
+            Stmt::Label(label) => {
+                if self
+                    .labels
+                    .insert(label.name.clone(), self.chain.clone())
+                    .is_some()
+                {
+                    self.errors
+                        .push(TypeError::DuplicateLabel(label.name.name.clone()).at(label.span));
+                }
+                self.stmt(&label.stmt);
+            }
+            _ => {}
+        }
+    }
+
+    fn control(&mut self, control: &Control) {
+        match control {
+            Control::If(if_stmt) => {
+                self.scoped(|this| this.stmt(&if_stmt.then_branch));
+                if let Some(else_branch) = &if_stmt.else_branch {
+                    self.scoped(|this| this.stmt(else_branch));
+                }
+            }
+            Control::While(while_stmt) => self.in_loop(&while_stmt.body),
+            Control::DoWhile(do_while) => self.in_loop(&do_while.body),
+            Control::For(for_stmt) => self.in_loop(&for_stmt.body),
+            Control::Switch(switch) => self.scoped(|this| {
+                // 条件式を退避する隠れ変数の分
+                this.declare();
+                this.switches += 1;
+                for case in &switch.cases {
+                    match case {
+                        SwitchCase::Case(case) => this.stmts(&case.stmts),
+                        SwitchCase::Default(default) => this.stmts(&default.stmts),
+                    }
+                }
+                this.switches -= 1;
+            }),
+        }
+    }
+
+    fn in_loop(&mut self, body: &Stmt) {
+        self.loops += 1;
+        self.scoped(|this| this.stmt(body));
+        self.loops -= 1;
+    }
+}
@@ -1,5 +1,6 @@
 pub mod ast;
 pub mod const_eval;
 pub mod convert;
+pub mod jump;
 pub mod simplification;
 pub mod r#type;
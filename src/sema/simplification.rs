@@ -6,6 +6,12 @@ pub struct Session {
     id: usize,
 }
 
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Session {
     pub fn new() -> Self {
         Self { id: 0 }
@@ -83,7 +89,7 @@ fn simplify_stmt(stmt_: &mut Stmt, session: &mut Session) -> Vec<Box<Stmt>> {
             block(this, session);
             vec![Box::new(stmt_.clone())]
         }
-        Stmt::Break | Stmt::Continue => {
+        Stmt::Break(_) | Stmt::Continue(_) => {
             vec![Box::new(stmt_.clone())]
         }
     }
@@ -91,7 +97,7 @@ fn simplify_stmt(stmt_: &mut Stmt, session: &mut Session) -> Vec<Box<Stmt>> {
 
 fn simplify_decl_stmt(decl_stmt: &mut DeclStmt, session: &mut Session) -> Vec<DeclStmt> {
     match decl_stmt {
-        DeclStmt::InitVec(inits) => {
+        DeclStmt::InitVec(inits) | DeclStmt::Static(inits) => {
             for init in inits {
                 if let Some(init_data) = &mut init.l {
                     init_data_simplify(init_data, session);
@@ -213,13 +219,13 @@ fn stmt(stmt_: &mut Stmt, session: &mut Session) {
         Stmt::Block(this) => {
             block(this, session);
         }
-        Stmt::Break | Stmt::Continue => {}
+        Stmt::Break(_) | Stmt::Continue(_) => {}
     }
 }
 
 fn decl_stmt_simplify(decl_stmt: &mut DeclStmt, session: &mut Session) {
     match decl_stmt {
-        DeclStmt::InitVec(inits) => {
+        DeclStmt::InitVec(inits) | DeclStmt::Static(inits) => {
             for init in inits {
                 if let Some(init_data) = &mut init.l {
                     init_data_simplify(init_data, session);
@@ -345,6 +351,8 @@ fn control_simplify(control: &mut Control, session: &mut Session) {
 }
 
 fn _expr(expr: Expr) -> Expr {
+    // 変換後の式も元の演算子の位置を指すようにする
+    let span = expr.span();
     match expr {
         Expr::Assign(this) => assign(this),
         Expr::Unary(this) => unary(this),
@@ -364,26 +372,31 @@ fn _expr(expr: Expr) -> Expr {
         Expr::NumInt(this) => Expr::NumInt(this),
         Expr::NumFloat(this) => Expr::NumFloat(this),
     }
+    .with_span(span)
 }
 
 fn postfix(postfix: Postfix) -> Expr {
     let simplified_expr = Box::new(_expr(*postfix.expr));
 
     Expr::comma(vec![
-        *Expr::assign(
+        Expr::assign(
             AssignOp::Equal,
             simplified_expr.clone(),
-            Expr::binary(
-                if postfix.op == PostfixOp::plus_plus() {
-                    BinaryOp::plus()
-                } else {
-                    BinaryOp::minus()
-                },
-                simplified_expr.clone(),
-                Box::new(Expr::NumInt(1)),
+            Box::new(
+                Expr::binary(
+                    if postfix.op == PostfixOp::plus_plus() {
+                        BinaryOp::plus()
+                    } else {
+                        BinaryOp::minus()
+                    },
+                    simplified_expr.clone(),
+                    Box::new(Expr::NumInt(1)),
+                )
+                .with_span(postfix.span),
             ),
-        ),
-        *Expr::binary(
+        )
+        .with_span(postfix.span),
+        Expr::binary(
             if postfix.op == PostfixOp::plus_plus() {
                 BinaryOp::minus()
             } else {
@@ -391,7 +404,8 @@ fn postfix(postfix: Postfix) -> Expr {
             },
             simplified_expr,
             Box::new(Expr::NumInt(1)),
-        ),
+        )
+        .with_span(postfix.span),
     ])
 }
 
@@ -411,14 +425,17 @@ fn unary(unary: Unary) -> Expr {
             *Expr::assign(
                 AssignOp::equal(),
                 simplified_expr.clone(),
-                Expr::binary(
-                    if unary.op == UnaryOp::PlusPlus {
-                        BinaryOp::plus()
-                    } else {
-                        BinaryOp::minus()
-                    },
-                    simplified_expr,
-                    Box::new(Expr::NumInt(1)),
+                Box::new(
+                    Expr::binary(
+                        if unary.op == UnaryOp::PlusPlus {
+                            BinaryOp::plus()
+                        } else {
+                            BinaryOp::minus()
+                        },
+                        simplified_expr,
+                        Box::new(Expr::NumInt(1)),
+                    )
+                    .with_span(unary.span),
                 ),
             )
         }
@@ -441,22 +458,25 @@ fn assign(assign: Assign) -> Expr {
     let assign_expr = Expr::assign(
         AssignOp::equal(),
         simplified_lhs.clone(),
-        Expr::binary(
-            match assign.op {
-                AssignOp::PlusEqual => BinaryOp::plus(),
-                AssignOp::MinusEqual => BinaryOp::minus(),
-                AssignOp::AsteriskEqual => BinaryOp::asterisk(),
-                AssignOp::SlashEqual => BinaryOp::slash(),
-                AssignOp::PercentEqual => BinaryOp::percent(),
-                AssignOp::CaretEqual => BinaryOp::caret(),
-                AssignOp::PipeEqual => BinaryOp::pipe(),
-                AssignOp::LessLessEqual => BinaryOp::less_less(),
-                AssignOp::GreaterGreaterEqual => BinaryOp::greater_greater(),
-                AssignOp::AmpersandEqual => BinaryOp::ampersand(),
-                AssignOp::Equal => unreachable!(),
-            },
-            simplified_lhs,
-            simplified_rhs,
+        Box::new(
+            Expr::binary(
+                match assign.op {
+                    AssignOp::PlusEqual => BinaryOp::plus(),
+                    AssignOp::MinusEqual => BinaryOp::minus(),
+                    AssignOp::AsteriskEqual => BinaryOp::asterisk(),
+                    AssignOp::SlashEqual => BinaryOp::slash(),
+                    AssignOp::PercentEqual => BinaryOp::percent(),
+                    AssignOp::CaretEqual => BinaryOp::caret(),
+                    AssignOp::PipeEqual => BinaryOp::pipe(),
+                    AssignOp::LessLessEqual => BinaryOp::less_less(),
+                    AssignOp::GreaterGreaterEqual => BinaryOp::greater_greater(),
+                    AssignOp::AmpersandEqual => BinaryOp::ampersand(),
+                    AssignOp::Equal => unreachable!(),
+                },
+                simplified_lhs,
+                simplified_rhs,
+            )
+            .with_span(assign.span),
         ),
     );
 
@@ -488,7 +508,8 @@ fn subscript(subscript: Subscript) -> Expr {
 fn member_access(member_access: MemberAccess) -> Expr {
     match member_access.kind {
         MemberAccessOp::MinusGreater => Expr::member_access(
-            *Expr::unary(UnaryOp::asterisk(), Box::new(_expr(*member_access.base))),
+            Expr::unary(UnaryOp::asterisk(), Box::new(_expr(*member_access.base)))
+                .with_span(member_access.span),
             member_access.member,
             MemberAccessOp::dot(),
         ),
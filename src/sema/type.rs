@@ -1,5 +1,7 @@
 use super::ast::*;
+use crate::codegen::stack::Word;
 use crate::op::*;
+use crate::span::Span;
 use crate::visualize::*;
 
 #[derive(Debug, Clone)]
@@ -18,6 +20,39 @@ pub enum TypeError {
         base_type: Type,
         member: String,
     },
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    JumpPastDeclaration(String),
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    NonConstantInitializer(String),
+    NonConstantEnumerator(String),
+    AssignToConst(String),
+    IntegerOverflow(usize),
+    DiscardsConst {
+        expected: Type,
+        found: Type,
+        context: String,
+    },
+    Located(Span, Box<TypeError>), // 位置の分かったエラー
+}
+
+impl TypeError {
+    // 位置を付ける 既に内側の式の位置が付いていればそちらを残す
+    pub fn at(self, span: Option<Span>) -> Self {
+        match (self, span) {
+            (TypeError::Located(span, inner), _) => TypeError::Located(span, inner),
+            (this, Some(span)) => TypeError::Located(span, Box::new(this)),
+            (this, None) => this,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            TypeError::Located(span, _) => Some(*span),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for TypeError {
@@ -55,6 +90,55 @@ impl std::fmt::Display for TypeError {
                     base_type.to_rust_format()
                 )
             }
+            TypeError::UndefinedLabel(name) => {
+                write!(f, "Undefined label: {}", name)
+            }
+            TypeError::DuplicateLabel(name) => {
+                write!(f, "Duplicate label: {}", name)
+            }
+            TypeError::BreakOutsideLoop => {
+                write!(f, "break statement not within a loop or switch")
+            }
+            TypeError::ContinueOutsideLoop => {
+                write!(f, "continue statement not within a loop")
+            }
+            TypeError::JumpPastDeclaration(name) => {
+                write!(
+                    f,
+                    "Jump to label '{}' skips over a variable declaration",
+                    name
+                )
+            }
+            TypeError::NonConstantInitializer(name) => {
+                write!(
+                    f,
+                    "Initializer of static storage variable '{}' is not constant",
+                    name
+                )
+            }
+            TypeError::NonConstantEnumerator(name) => {
+                write!(f, "Value of enumerator '{}' is not constant", name)
+            }
+            TypeError::AssignToConst(target) => {
+                write!(f, "Assignment to read-only location '{}'", target)
+            }
+            TypeError::IntegerOverflow(value) => {
+                write!(f, "Integer literal '{}' does not fit in 16 bits", value)
+            }
+            TypeError::DiscardsConst {
+                expected,
+                found,
+                context,
+            } => {
+                write!(
+                    f,
+                    "Conversion from '{}' to '{}' in {} discards const qualifier",
+                    found.to_rust_format(),
+                    expected.to_rust_format(),
+                    context
+                )
+            }
+            TypeError::Located(_, inner) => inner.fmt(f),
         }
     }
 }
@@ -80,6 +164,57 @@ fn is_error_type(ty: &Type) -> bool {
     matches!(ty, Type::Error)
 }
 
+// enumはintと相互に暗黙変換できる
+fn is_integer_like(ty: &Type) -> bool {
+    matches!(ty.unqualified(), Type::Int | Type::Enum(_))
+}
+
+// ポインタ(配列)の指す先がconstかどうか
+fn pointee_is_const(ty: &Type) -> bool {
+    match ty.unqualified() {
+        Type::Pointer(inner) => inner.is_const(),
+        Type::Array(array) => array.array_of.is_const(),
+        _ => false,
+    }
+}
+
+// const T * を T * へ変換するような，指す先のconstが外れる変換かどうか
+fn discards_const(expected: &Type, found: &Type) -> bool {
+    match expected.unqualified() {
+        Type::Pointer(target) => pointee_is_const(found) && !target.is_const(),
+        _ => false,
+    }
+}
+
+// 代入先がconstで修飾された場所かどうか
+fn is_const_lvalue(expr: &TypedExpr, session: &Session) -> bool {
+    match &expr.r#expr {
+        SemaExpr::Symbol(symbol) => session
+            .get_type(&symbol.ident)
+            .is_some_and(|ty| ty.is_const()),
+        SemaExpr::Unary(unary) if matches!(unary.op, UnaryOp::Asterisk) => {
+            pointee_is_const(&unary.expr.r#type)
+        }
+        SemaExpr::Subscript(subscript) => pointee_is_const(&subscript.subject.r#type),
+        SemaExpr::MemberAccess(member) => {
+            let member_is_const = match member.base.r#type.unqualified() {
+                Type::Struct(s) => s.member.iter().find(|m| m.ident == member.member).cloned(),
+                Type::Union(u) => u.member.iter().find(|m| m.ident == member.member).cloned(),
+                _ => None,
+            }
+            .and_then(|m| m.get_type())
+            .is_some_and(|ty| ty.is_const());
+            member_is_const || is_const_lvalue(&member.base, session)
+        }
+        _ => false,
+    }
+}
+
+// 代入・初期化・引数で受け入れられる型かどうか
+fn is_assignable(expected: &Type, found: &Type) -> bool {
+    expected == found || (is_integer_like(expected) && is_integer_like(found))
+}
+
 // Error型を含む場合はError型を返すヘルパー関数
 fn propagate_error_type(types: &[&Type]) -> Option<Type> {
     if types.iter().any(|t| is_error_type(t)) {
@@ -91,7 +226,7 @@ fn propagate_error_type(types: &[&Type]) -> Option<Type> {
 
 pub fn program(program: &Program, session: &mut Session) -> TypeCheckResult<Program> {
     let mut resolved_items = Vec::new();
-    let mut all_errors = Vec::new();
+    let mut all_errors = std::mem::take(&mut session.errors);
 
     for item in &program.items {
         let mut result = resolve_toplevel(item, session);
@@ -120,7 +255,11 @@ fn resolve_toplevel(toplevel: &TopLevel, session: &mut Session) -> TypeCheckResu
             TypeCheckResult::new(TopLevel::FunctionProto(func_proto.clone()))
         }
         TopLevel::Stmt(stmt) => {
-            let result = resolve_stmt(stmt, session);
+            let mut result = resolve_stmt(stmt, session);
+            // グローバル変数の初期化子はコンパイル時に畳み込むので定数に限る
+            if let Stmt::DeclStmt(DeclStmt::InitVec(inits)) = &result.result {
+                check_constant_inits(inits, &mut result.errors);
+            }
             TypeCheckResult {
                 result: TopLevel::Stmt(result.result),
                 errors: result.errors,
@@ -129,6 +268,36 @@ fn resolve_toplevel(toplevel: &TopLevel, session: &mut Session) -> TypeCheckResu
     }
 }
 
+fn check_constant_inits(inits: &[Init], errors: &mut Vec<TypeError>) {
+    for init in inits {
+        if let Some(init_data) = &init.r
+            && !is_constant_init(init_data)
+        {
+            errors.push(
+                TypeError::NonConstantInitializer(init.l.ident.name.clone())
+                    .at(init_span(init_data).or(init.span)),
+            );
+        }
+    }
+}
+
+// 初期化子の位置 複合初期化子なら最初の要素の位置
+fn init_span(init_data: &InitData) -> Option<Span> {
+    match init_data {
+        InitData::Expr(expr) => expr.span,
+        InitData::Compound(compounds) => compounds.first().and_then(init_span),
+    }
+}
+
+fn is_constant_init(init_data: &InitData) -> bool {
+    match init_data {
+        InitData::Expr(expr) => {
+            matches!(expr.expr, SemaExpr::String(_)) || expr.clone().eval_const().is_ok()
+        }
+        InitData::Compound(com) => com.iter().all(is_constant_init),
+    }
+}
+
 // TODO カス　絶対になおす
 fn resolve_function_def(
     func_def: &FunctionDef,
@@ -156,6 +325,7 @@ fn resolve_function_def(
 
     let mut body_result = resolve_block(&func_def.body, session);
     errors.append(&mut body_result.errors);
+    errors.append(&mut super::jump::check_function(&body_result.result));
 
     TypeCheckResult {
         result: FunctionDef {
@@ -228,6 +398,7 @@ fn resolve_stmt(stmt: &Stmt, session: &mut Session) -> TypeCheckResult<Stmt> {
             Stmt::Label(Label {
                 name: label.name.clone(),
                 stmt: Box::new(stmt_result.result),
+                span: label.span,
             })
         }
         _ => stmt.clone(),
@@ -368,6 +539,17 @@ fn resolve_decl_stmt(decl: &DeclStmt, session: &mut Session) -> TypeCheckResult<
             }
             DeclStmt::InitVec(resolved_inits)
         }
+        DeclStmt::Static(inits) => {
+            let mut resolved_inits = Vec::new();
+            for init in inits {
+                let mut init_result = resolve_init(init, session);
+                errors.append(&mut init_result.errors);
+                resolved_inits.push(init_result.result);
+            }
+            // 静的記憶域はプログラム開始時に初期化するので定数に限る
+            check_constant_inits(&resolved_inits, &mut errors);
+            DeclStmt::Static(resolved_inits)
+        }
         _ => decl.clone(),
     };
 
@@ -386,6 +568,15 @@ fn resolve_init(init: &Init, session: &mut Session) -> TypeCheckResult<Init> {
         infer_array_length(&mut resolved_type, init_data, session);
     }
 
+    // 配列の長さの式も検査する
+    let mut dimension = &resolved_type;
+    while let Type::Array(array) = dimension {
+        if let Some(length) = &array.length {
+            errors.append(&mut resolve_typed_expr(length, session).errors);
+        }
+        dimension = array.array_of.as_ref();
+    }
+
     let resolved_member_decl = init.l.clone();
 
     // 初期化データがある場合、型の互換性をチェック
@@ -408,8 +599,10 @@ fn resolve_init(init: &Init, session: &mut Session) -> TypeCheckResult<Init> {
         result: Init {
             l: resolved_member_decl,
             r: init_data_result,
+            span: init.span,
         },
-        errors,
+        // 初期化子に位置が無ければ宣言された名前を指す
+        errors: errors.into_iter().map(|e| e.at(init.span)).collect(),
     }
 }
 
@@ -428,13 +621,25 @@ fn check_init_compatibility(
             // Error型の場合は互換性チェックをスキップ
             if !is_error_type(&var_type_flat)
                 && !is_error_type(&expr_type_flat)
-                && var_type_flat != expr_type_flat
+                && !is_assignable(&var_type_flat, &expr_type_flat)
             {
-                errors.push(TypeError::IncompatibleTypes {
-                    expected: var_type_flat,
-                    found: expr_type_flat,
-                    context: "variable initialization".to_string(),
-                });
+                errors.push(
+                    TypeError::IncompatibleTypes {
+                        expected: var_type_flat,
+                        found: expr_type_flat,
+                        context: "variable initialization".to_string(),
+                    }
+                    .at(expr.span),
+                );
+            } else if discards_const(&var_type_flat, &expr_type_flat) {
+                errors.push(
+                    TypeError::DiscardsConst {
+                        expected: var_type_flat,
+                        found: expr_type_flat,
+                        context: "variable initialization".to_string(),
+                    }
+                    .at(expr.span),
+                );
             }
         }
         InitData::Compound(compounds) => {
@@ -504,14 +709,16 @@ pub fn resolve_typed_expr(expr: &TypedExpr, session: &mut Session) -> TypeCheckR
     let sema_result = resolve_sema_expr(&expr.r#expr, session);
     let mut errors = sema_result.errors;
 
-    let inferred_type = infer_type(&sema_result.result, session, &mut errors).flat();
+    // 値として読み出すので一番外側のconstは外す
+    let inferred_type = infer_type(&sema_result.result, session, &mut errors).unqualified();
 
     TypeCheckResult {
         result: TypedExpr {
             r#type: inferred_type,
             r#expr: sema_result.result,
+            span: expr.span,
         },
-        errors,
+        errors: errors.into_iter().map(|e| e.at(expr.span)).collect(),
     }
 }
 
@@ -529,13 +736,26 @@ fn resolve_sema_expr(expr: &SemaExpr, session: &mut Session) -> TypeCheckResult<
             let lhs_flat = lhs_result.result.r#type.flat();
             let rhs_flat = rhs_result.result.r#type.flat();
 
+            if is_const_lvalue(&lhs_result.result, session) {
+                errors.push(TypeError::AssignToConst(lhs_result.result.oneline()));
+            }
+
             // Error型の場合は互換性チェックをスキップ、そうでなければエラーをログして継続
-            if !is_error_type(&lhs_flat) && !is_error_type(&rhs_flat) && lhs_flat != rhs_flat {
+            if !is_error_type(&lhs_flat)
+                && !is_error_type(&rhs_flat)
+                && !is_assignable(&lhs_flat, &rhs_flat)
+            {
                 errors.push(TypeError::IncompatibleTypes {
                     expected: lhs_flat,
                     found: rhs_flat,
                     context: "assignment".to_string(),
                 });
+            } else if discards_const(&lhs_flat, &rhs_flat) {
+                errors.push(TypeError::DiscardsConst {
+                    expected: lhs_flat,
+                    found: rhs_flat,
+                    context: "assignment".to_string(),
+                });
             }
 
             SemaExpr::Assign(Assign {
@@ -620,7 +840,7 @@ fn resolve_sema_expr(expr: &SemaExpr, session: &mut Session) -> TypeCheckResult<
             })
         }
         SemaExpr::Cast(cast) => {
-            let flattened_cast_type = cast.type_to.flat();
+            let flattened_cast_type = cast.type_to.unqualified();
             let mut expr_result = resolve_typed_expr(&cast.expr, session);
             errors.append(&mut expr_result.errors);
 
@@ -651,6 +871,19 @@ fn resolve_sema_expr(expr: &SemaExpr, session: &mut Session) -> TypeCheckResult<
             };
             SemaExpr::Sizeof(resolved_sizeof)
         }
+        SemaExpr::Symbol(symbol) => {
+            if session.get_type(&symbol.ident).is_none() {
+                errors.push(TypeError::UndefinedVariable(symbol.ident.name.clone()));
+            }
+            expr.clone()
+        }
+        SemaExpr::NumInt(n) => {
+            // Push(Word)で黙って切り詰められないようにここで弾く
+            if *n > Word::MAX as usize {
+                errors.push(TypeError::IntegerOverflow(*n));
+            }
+            expr.clone()
+        }
         _ => expr.clone(),
     };
 
@@ -702,13 +935,19 @@ fn check_function_call(
             // Error型の場合は型チェックをスキップ
             if !is_error_type(&expected_flat)
                 && !is_error_type(&actual_flat)
-                && expected_flat != actual_flat
+                && !is_assignable(&expected_flat, &actual_flat)
             {
                 errors.push(TypeError::IncompatibleTypes {
                     expected: expected_flat,
                     found: actual_flat,
                     context: format!("function call argument {} ({})", i + 1, func_expr.oneline()),
                 });
+            } else if discards_const(&expected_flat, &actual_flat) {
+                errors.push(TypeError::DiscardsConst {
+                    expected: expected_flat,
+                    found: actual_flat,
+                    context: format!("function call argument {} ({})", i + 1, func_expr.oneline()),
+                });
             }
         }
     } else {
@@ -735,17 +974,14 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
         SemaExpr::Symbol(symbol) => {
             match session.get_type(&symbol.ident) {
                 Some(t) => t.flat(), // シンボルの型も平坦化
-                None => {
-                    errors.push(TypeError::UndefinedVariable(symbol.ident.name.clone()));
-                    Type::Error // 未定義変数の場合はError型を返す
-                }
+                None => Type::Error, // 未定義変数 報告は resolve_sema_expr で一度だけする
             }
         }
         SemaExpr::Binary(binary) => infer_binary_type(binary, session, errors),
         SemaExpr::Unary(unary) => infer_unary_type(unary, session, errors),
         SemaExpr::Assign(assign) => infer_type(&assign.lhs.r#expr, session, errors),
         SemaExpr::Call(call) => {
-            let func_type = infer_type(&call.func.r#expr, session, errors).flat();
+            let func_type = infer_type(&call.func.r#expr, session, errors).unqualified();
 
             // Error型の場合はError型を返す
             if is_error_type(&func_type) {
@@ -763,7 +999,7 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
             }
         }
         SemaExpr::Subscript(subscript) => {
-            let subject_type = infer_type(&subscript.subject.r#expr, session, errors).flat();
+            let subject_type = infer_type(&subscript.subject.r#expr, session, errors).unqualified();
 
             // Error型の場合はError型を返す
             if is_error_type(&subject_type) {
@@ -783,7 +1019,8 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
             }
         }
         SemaExpr::MemberAccess(member) => {
-            let base_type = infer_type(&member.base.r#expr, session, errors).flat();
+            let qualified_base = infer_type(&member.base.r#expr, session, errors).flat();
+            let base_type = qualified_base.unqualified();
 
             // Error型の場合はError型を返す
             if is_error_type(&base_type) {
@@ -795,7 +1032,7 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
                 _ => unreachable!(),
             };
 
-            match actual_type.flat() {
+            let member_type = match actual_type.flat() {
                 Type::Struct(ref s) => {
                     match s.member
                         .iter()
@@ -835,11 +1072,18 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
                     });
                     Type::Error
                 }
+            };
+
+            // const構造体のメンバーもconst
+            if qualified_base.is_const() && !member_type.is_const() {
+                Type::r#const(member_type)
+            } else {
+                member_type
             }
         }
         SemaExpr::Ternary(ternary) => {
-            let then_type = infer_type(&ternary.then_branch.r#expr, session, errors).flat();
-            let else_type = infer_type(&ternary.else_branch.r#expr, session, errors).flat();
+            let then_type = infer_type(&ternary.then_branch.r#expr, session, errors).unqualified();
+            let else_type = infer_type(&ternary.else_branch.r#expr, session, errors).unqualified();
 
             // Error型がある場合はError型を伝播
             if let Some(error_type) = propagate_error_type(&[&then_type, &else_type]) {
@@ -859,7 +1103,7 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
                 Type::Error
             }
         }
-        SemaExpr::Cast(cast) => cast.type_to.flat(), // キャストの結果型も平坦化
+        SemaExpr::Cast(cast) => cast.type_to.unqualified(), // キャストの結果型も平坦化
         SemaExpr::Comma(comma) => {
             if let Some(last_expr) = comma.assigns.last() {
                 infer_type(&last_expr.r#expr, session, errors)
@@ -872,8 +1116,8 @@ fn infer_type(expr: &SemaExpr, session: &mut Session, errors: &mut Vec<TypeError
 }
 
 fn infer_binary_type(binary: &Binary, session: &mut Session, errors: &mut Vec<TypeError>) -> Type {
-    let lhs_type = infer_type(&binary.lhs.r#expr, session, errors).flat();
-    let rhs_type = infer_type(&binary.rhs.r#expr, session, errors).flat();
+    let lhs_type = infer_type(&binary.lhs.r#expr, session, errors).unqualified();
+    let rhs_type = infer_type(&binary.rhs.r#expr, session, errors).unqualified();
 
     // Error型がある場合はError型を伝播
     if let Some(error_type) = propagate_error_type(&[&lhs_type, &rhs_type]) {
@@ -887,7 +1131,10 @@ fn infer_binary_type(binary: &Binary, session: &mut Session, errors: &mut Vec<Ty
         }
         BinaryOp::Arithmetic(_) => {
             // 算術演算では両オペランドの型が一致している必要がある（平坦化後で比較）
-            if lhs_type == rhs_type {
+            // enumが混ざる場合はintとして計算する
+            if is_integer_like(&lhs_type) && is_integer_like(&rhs_type) {
+                Type::Int
+            } else if lhs_type == rhs_type {
                 lhs_type
             } else {
                 errors.push(TypeError::IncompatibleTypes {
@@ -902,7 +1149,8 @@ fn infer_binary_type(binary: &Binary, session: &mut Session, errors: &mut Vec<Ty
 }
 
 fn infer_unary_type(unary: &Unary, session: &mut Session, errors: &mut Vec<TypeError>) -> Type {
-    let operand_type = infer_type(&unary.expr.r#expr, session, errors).flat();
+    let qualified_operand = infer_type(&unary.expr.r#expr, session, errors).flat();
+    let operand_type = qualified_operand.unqualified();
 
     // Error型の場合はError型を返す
     if is_error_type(&operand_type) {
@@ -912,7 +1160,8 @@ fn infer_unary_type(unary: &Unary, session: &mut Session, errors: &mut Vec<TypeE
     match unary.op {
         UnaryOp::Bang => Type::Int,
         UnaryOp::Tilde => operand_type,
-        UnaryOp::Ampersand => Type::Pointer(Box::new(operand_type)),
+        // アドレスを取るときはconstを残す
+        UnaryOp::Ampersand => Type::Pointer(Box::new(qualified_operand)),
         UnaryOp::Asterisk => {
             if let Type::Pointer(inner) = operand_type {
                 (*inner).flat() // デリファレンス結果も平坦化
@@ -0,0 +1,100 @@
+use std::cell::Cell;
+use std::num::NonZeroU32;
+
+/// ソース上の位置 行・列は1始まりで，列は文字単位
+/// ASTの至る所に埋め込むのでOption<Span>が8バイトに収まるようにしておく
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub line: NonZeroU32,
+    pub column: u16,
+    pub len: u16,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, len: usize) -> Self {
+        Self {
+            line: NonZeroU32::new(line as u32).expect("行番号は1始まり"),
+            column: column.min(u16::MAX as usize) as u16,
+            len: len.min(u16::MAX as usize) as u16,
+        }
+    }
+}
+
+/// 行頭のバイト位置を持っておき，バイト位置から行・列を求める
+pub struct LineMap<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+    /// 直前に求めたバイト位置と列 長い行で毎回行頭から数え直さないようにする
+    last: Cell<(usize, usize)>,
+}
+
+impl<'a> LineMap<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+            last: Cell::new((usize::MAX, 0)),
+        }
+    }
+
+    /// offsetから始まるlenバイトの範囲をSpanにする
+    pub fn span(&self, offset: usize, len: usize) -> Span {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        // 字句解析は前から順に位置を求めるので，同じ行なら直前の位置から数えれば済む
+        let (last_offset, last_column) = self.last.get();
+        let column = if line_start <= last_offset && last_offset <= offset {
+            last_column + self.source[last_offset..offset].chars().count()
+        } else {
+            self.source[line_start..offset].chars().count() + 1
+        };
+        self.last.set((offset, column));
+        let len = self.source[offset..offset + len].chars().count();
+        Span::new(line, column, len.max(1))
+    }
+}
+
+/// gccと同じ形式で診断を組み立てる
+///
+/// ```text
+/// file.c:12:5: error: message
+///    12 |     x = y;
+///       |     ^~~~~
+/// ```
+pub fn render(file: &str, source: &str, span: Option<Span>, message: &str) -> String {
+    let Some(span) = span else {
+        return format!("{}: error: {}", file, message);
+    };
+
+    let mut out = format!("{}:{}:{}: error: {}", file, span.line, span.column, message);
+
+    if let Some(line) = source.lines().nth(span.line.get() as usize - 1) {
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // タブはそのまま残して位置がずれないようにする
+        let indent: String = line
+            .chars()
+            .take(span.column as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let underline = format!("^{}", "~".repeat(span.len.saturating_sub(1) as usize));
+
+        out.push_str(&format!("\n {} | {}", number, line));
+        out.push_str(&format!("\n {} | {}{}", gutter, indent, underline));
+    }
+    out
+}
+
+#[test]
+fn test_render() {
+    let source = "int main(void) {\n\tx = y;\n}";
+    let span = LineMap::new(source).span(source.find('x').unwrap(), 1);
+    assert_eq!(span, Span::new(2, 2, 1));
+    assert_eq!(
+        render("a.c", source, Some(span), "oops"),
+        "a.c:2:2: error: oops\n 2 | \tx = y;\n   | \t^"
+    );
+}
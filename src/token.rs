@@ -98,10 +98,12 @@ pub enum Keyword {
     Enum,     // 'enum'
     Typedef,  // 'typedef'
     Sizeof,   // 'sizeof'
+    Static,   // 'static'
+    Const,    // 'const'
 }
 
 impl Keyword {
-    pub const SYMBOLS: [(&str, Self); 21] = [
+    pub const SYMBOLS: [(&str, Self); 23] = [
         ("int", Self::Int),
         ("double", Self::Double),
         ("char", Self::Char),
@@ -123,6 +125,8 @@ impl Keyword {
         ("enum", Self::Enum),
         ("typedef", Self::Typedef),
         ("sizeof", Self::Sizeof),
+        ("static", Self::Static),
+        ("const", Self::Const),
     ];
 
     pub fn classify(input: &str) -> Option<Self> {
@@ -248,6 +252,13 @@ impl Token {
     pub fn r#enum() -> Self {
         Token::Keyword(Keyword::Enum)
     }
+    pub fn r#static() -> Self {
+        Token::Keyword(Keyword::Static)
+    }
+
+    pub fn r#const() -> Self {
+        Token::Keyword(Keyword::Const)
+    }
 
     pub fn r#typedef() -> Self {
         Token::Keyword(Keyword::Typedef)
@@ -257,3 +268,23 @@ impl Token {
         Token::Keyword(Keyword::Sizeof)
     }
 }
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::NumInt(n) => write!(f, "{}", n),
+            Token::NumFloat(n) => write!(f, "{}", n),
+            Token::Char(c) => write!(f, "{:?}", c),
+            Token::String(s) => write!(f, "{:?}", String::from_iter(s)),
+            Token::Keyword(keyword) => {
+                let (symbol, _) = Keyword::SYMBOLS.iter().find(|(_, k)| k == keyword).unwrap();
+                write!(f, "{}", symbol)
+            }
+            _ => {
+                let (symbol, _) = Token::SYMBOLS.iter().find(|(_, t)| t == self).unwrap();
+                write!(f, "{}", symbol)
+            }
+        }
+    }
+}
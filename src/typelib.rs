@@ -1,35 +1,51 @@
 use crate::ast::{Array, Expr, Func, Ident, Type};
-use crate::parser::ParseSession;
 use crate::parser::expr;
+use crate::parser::{ParseResult, ParseSession, TokenCursor};
 use crate::token::Token;
 /// Parse a complete C type declaration
 
-pub fn get_type(parse_session: &mut ParseSession, tokens: &Vec<Token>) -> Type {
+pub fn get_type(parse_session: &mut ParseSession, tokens: &TokenCursor) -> ParseResult<Type> {
+    // カーソルのコピーを読むので元のカーソルは進まない
     consume_type(parse_session, &mut tokens.clone())
 }
 
 pub fn consume_and_extract_idents(
     session: &mut ParseSession,
-    tokens: &mut Vec<Token>,
-) -> (Type, Vec<Ident>) {
-    let original_tokens = tokens.clone();
-    let original_len = tokens.len();
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<(Type, Vec<Ident>)> {
+    let base = consume_base(session, tokens)?;
+    consume_declarator(session, base, tokens)
+}
+
+/// 基本型を読み終えた後の宣言子を読む
+/// int *a, b[2]; のように基本型を共有する2つ目以降の宣言子にも使う
+pub fn consume_declarator(
+    session: &mut ParseSession,
+    base: Type,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<(Type, Vec<Ident>)> {
+    let start = *tokens;
 
-    let parsed_type = consume_type(session, tokens);
-    let remaining_len = tokens.len();
+    let parsed_type = call(p(base, tokens), tokens, session)?;
 
     // 消費されたトークンの範囲からidentを抽出
     let mut idents = vec![];
-    for i in 0..(original_len - remaining_len) {
-        if let Token::Ident(name) = &original_tokens[i] {
-            if !session.is_base_type(&Token::Ident(name.to_string())) {
-                let id = Ident { name: name.clone() };
+    let consumed = start.take(tokens.pos() - start.pos());
+    for (i, token) in consumed.iter().enumerate() {
+        if let Token::Ident(name) = token {
+            if !session.is_base_type(token) {
+                let id = Ident::new(name).with_span(session.span_at(start.pos() + i));
                 idents.push(id);
             }
         }
     }
 
-    (parsed_type, idents)
+    // 宣言には名前が要る
+    if idents.is_empty() {
+        return Err(session.error("identifier", tokens));
+    }
+
+    Ok((parsed_type, idents))
 }
 
 fn find_matching_paren(tokens: &[Token]) -> usize {
@@ -50,88 +66,124 @@ fn find_matching_paren(tokens: &[Token]) -> usize {
     paren_index + 1
 }
 
-pub fn consume_type(parse_session: &mut ParseSession, tokens: &mut Vec<Token>) -> Type {
-    let base = base(tokens, parse_session);
-    call(base, tokens, parse_session)
+pub fn consume_type(
+    parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Type> {
+    let base = consume_base(parse_session, tokens)?;
+    call(p(base, tokens), tokens, parse_session)
 }
 
-fn base(tokens: &mut Vec<Token>, parse_session: &mut ParseSession) -> Type {
-    let base_type = parse_session.cast(tokens).unwrap();
-    tokens.drain(0..base_type.1);
-    p(base_type.0, tokens)
+/// 宣言の先頭にある基本型部分(const修飾を含む)を読む
+/// ポインタは宣言子ごとに付くのでここでは読まない
+pub fn consume_base(
+    parse_session: &mut ParseSession,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<Type> {
+    // const int / int const のどちらも受け付ける
+    let mut is_const = consume_const(tokens);
+    let Some((base_type, len)) = parse_session.cast(tokens) else {
+        return Err(parse_session.error("type", tokens));
+    };
+    tokens.advance(len);
+    is_const |= consume_const(tokens);
+
+    Ok(if is_const {
+        Type::r#const(base_type)
+    } else {
+        base_type
+    })
 }
 
-fn p(mut base_type: Type, tokens: &mut Vec<Token>) -> Type {
+fn p(mut base_type: Type, tokens: &mut TokenCursor<'_>) -> Type {
     // 前: 基本型の前のポインタを処理
     while is_next_token(tokens, Token::Asterisk) {
-        tokens.remove(0);
+        tokens.advance(1);
         base_type = Type::Pointer(Box::new(base_type));
+        // int *const p のようにポインタ自体をconstにする
+        if consume_const(tokens) {
+            base_type = Type::r#const(base_type);
+        }
     }
     base_type
 }
 
-fn call(mut base_type: Type, tokens: &mut Vec<Token>, parse_session: &mut ParseSession) -> Type {
+fn consume_const(tokens: &mut TokenCursor<'_>) -> bool {
+    let mut found = false;
+    while is_next_token(tokens, Token::r#const()) {
+        tokens.advance(1);
+        found = true;
+    }
+    found
+}
+
+fn call(
+    mut base_type: Type,
+    tokens: &mut TokenCursor<'_>,
+    parse_session: &mut ParseSession,
+) -> ParseResult<Type> {
     let mut center = if is_next_ident(tokens) {
-        tokens.remove(0);
-        vec![]
+        tokens.advance(1);
+        tokens.take(0)
     } else if is_next_token(tokens, Token::LParen) {
+        // 括弧の中は外側を読んでから読むので，中身だけを見るカーソルを取っておく
         let end = find_matching_paren(tokens);
-        let mut tmp: Vec<Token> = tokens.drain(0..end).collect();
-        tmp.remove(0);
-        tmp.pop();
-        tmp
+        let mut inner = *tokens;
+        inner.advance(1);
+        tokens.advance(end);
+        inner.take(end.saturating_sub(2))
     } else {
-        vec![]
+        tokens.take(0)
     };
 
     // 残るは後
 
     if is_next_token(tokens, Token::LParen) {
-        tokens.remove(0);
+        tokens.advance(1);
         let mut param_types = vec![];
-        param_types.push(consume_type(parse_session, tokens));
+        param_types.push(consume_type(parse_session, tokens)?);
 
         while is_next_token(tokens, Token::Comma) {
-            tokens.remove(0);
-            param_types.push(consume_type(parse_session, tokens));
+            tokens.advance(1);
+            param_types.push(consume_type(parse_session, tokens)?);
         }
-        tokens.remove(0);
+        expect(parse_session, Token::RParen, tokens)?;
 
         base_type = Type::Func(Func {
             return_type: Box::new(base_type),
             params: param_types,
         });
     } else if is_next_token(tokens, Token::LBracket) {
-        tokens.remove(0);
+        tokens.advance(1);
         let mut array_sizes: Vec<Option<Expr>> = vec![];
 
         array_sizes.push(if !is_next_token(tokens, Token::RBracket) {
-            Some(expr(parse_session, tokens))
+            Some(expr(parse_session, tokens)?)
         } else {
             None
         });
-        tokens.remove(0);
+        expect(parse_session, Token::RBracket, tokens)?;
         while is_next_token(tokens, Token::LBracket) {
-            tokens.remove(0);
+            tokens.advance(1);
             array_sizes.push(if !is_next_token(tokens, Token::LBracket) {
-                Some(expr(parse_session, tokens))
+                Some(expr(parse_session, tokens)?)
             } else {
                 None
             });
-            tokens.remove(0);
+            expect(parse_session, Token::RBracket, tokens)?;
         }
 
         for size in array_sizes.into_iter().rev() {
             base_type = Type::Array(Array::new(base_type, size));
         }
     } else {
-        return base_type;
+        return Ok(base_type);
     }
 
     if !center.is_empty() {
         call(p(base_type, &mut center), &mut center, parse_session)
     } else {
-        base_type
+        Ok(base_type)
     }
 }
 
@@ -144,6 +196,18 @@ fn is_next_ident(tokens: &[Token]) -> bool {
     return matches!(next, Token::Ident(_));
 }
 
+fn expect(
+    parse_session: &ParseSession,
+    token: Token,
+    tokens: &mut TokenCursor<'_>,
+) -> ParseResult<()> {
+    if !is_next_token(tokens, token.clone()) {
+        return Err(parse_session.error(format!("'{}'", token), tokens));
+    }
+    tokens.advance(1);
+    Ok(())
+}
+
 fn is_next_token(tokens: &[Token], token: Token) -> bool {
     if tokens.is_empty() {
         return false;
@@ -3,29 +3,52 @@ pub trait Visualize {
     fn visualize_with_context(&self, indent: usize, is_last: bool, prefix: &[bool]);
 }
 
+use std::cell::RefCell;
+
+thread_local! {
+    // captureの間は標準出力ではなくここに書く
+    static CAPTURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn emit(text: &str) {
+    CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+        Some(buffer) => buffer.push_str(text),
+        None => print!("{}", text),
+    });
+}
+
+/// f の中で表示したものを文字列として受け取る
+pub fn capture(f: impl FnOnce()) -> String {
+    let outer = CAPTURE.with(|capture| capture.replace(Some(String::new())));
+    f();
+    CAPTURE
+        .with(|capture| capture.replace(outer))
+        .unwrap_or_default()
+}
+
 // Helper functions for tree visualization
 pub fn print_branch(label: &str, value: &str, _indent: usize, is_last: bool, prefix: &[bool]) {
     // Draw the tree structure
     for &p in prefix {
         if p {
-            print!("│   ");
+            emit("│   ");
         } else {
-            print!("    ");
+            emit("    ");
         }
     }
 
     // Draw the current branch
     if is_last {
-        print!("└── ");
+        emit("└── ");
     } else {
-        print!("├── ");
+        emit("├── ");
     }
 
     // Print the label and value
     if value.is_empty() {
-        println!("{}", label);
+        emit(&format!("{}\n", label));
     } else {
-        println!("{}: {}", label, value);
+        emit(&format!("{}: {}\n", label, value));
     }
 }
 
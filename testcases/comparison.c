@@ -0,0 +1,71 @@
+void putchar(char);
+
+void check(int x) {
+    if (x) {
+        putchar('1');
+    } else {
+        putchar('0');
+    }
+    return;
+}
+
+enum Level { LOW = -2, MID = 0, HIGH = 3 };
+
+void main(void) {
+    int a = -1;
+    int b = 1;
+    // 符号付きで比べる
+    check(a < b);
+    check(a <= b);
+    check(a > b);
+    check(a >= b);
+    putchar('\n');  // 1100
+
+    // 同じ値
+    check(a < a);
+    check(a <= a);
+    check(a > a);
+    check(a >= a);
+    putchar('\n');  // 0101
+
+    // 境界の値
+    int max = 32767;
+    int min = -32768;
+    check(min < max);
+    check(max > min);
+    check(max < -1);
+    check(-1 < max);
+    check(min < -1);
+    check(0 > min);
+    check(min >= min);
+    putchar('\n');  // 1101111
+
+    // 負の数どうし
+    check(-5 < -3);
+    check(-3 < -5);
+    check(-300 <= -300);
+    putchar('\n');  // 101
+
+    // 負の値まで数える
+    int i;
+    int n = 0;
+    for (i = 3; i > -3; i--) {
+        n++;
+    }
+    putchar((char)((int)'0' + n));
+    putchar('\n');  // 6
+
+    enum Level l = LOW;
+    check(l < MID);
+    check(HIGH > l);
+    putchar('\n');  // 11
+
+    // const でも符号付き
+    const int c = -1;
+    const int *p = &min;
+    check(c < 0);
+    check(c >= b);
+    check(*p < c);
+    putchar('\n');  // 101
+    return;
+}
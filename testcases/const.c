@@ -0,0 +1,80 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+const int SIZE = 4;
+const int squares[SIZE] = {0, 1, 4, 9};
+
+struct Point {
+    int x;
+    int y;
+};
+
+int read(const int *p) {
+    return *p;
+}
+
+void main(void) {
+    // const変数で配列の長さを決められる
+    const int n = SIZE * 2;
+    int buf[n];
+    int i;
+    int total = 0;
+    for (i = 0; i < n; i++) {
+        buf[i] = i * i;
+    }
+    for (i = 0; i < n; i++) {
+        total = total + buf[i];
+    }
+    print_int(total); // 140
+    print_int(squares[1] + squares[2] + squares[3]); // 14
+
+    const char greeting[] = "hi\n";
+    putchar(greeting[0]);
+    putchar(greeting[1]);
+    putchar(greeting[2]);
+
+    // ポインタ自体はconstでも指す先は書き換えられる
+    int value = 1;
+    int *const p = &value;
+    *p = 42;
+    print_int(value); // 42
+
+    // 指す先がconstなら付け替えは自由
+    const int *q = &squares[3];
+    print_int(read(q)); // 9
+    q = &squares[2];
+    print_int(*q); // 4
+    print_int(read(&value)); // 42
+
+    const struct Point origin = {3, 7};
+    print_int(origin.x + origin.y); // 10
+
+    int const m = 5, k = 6;
+    print_int(m * k); // 30
+
+    switch (value) {
+    case SIZE * 10 + 2:
+        print_int(SIZE);
+        break;
+    default:
+        print_int(0);
+    }
+    return;
+}
@@ -0,0 +1,55 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+void main(void) {
+    // 条件が偽でも一度は実行される
+    int i = 10;
+    do {
+        print_int(i);
+    } while (i < 5);
+
+    i = 0;
+    int sum = 0;
+    do {
+        int j = i * 2;
+        sum = sum + j;
+        i = i + 1;
+    } while (i < 5);
+    print_int(sum);  // 20
+
+    // continueは条件判定へ進む
+    i = 0;
+    do {
+        i = i + 1;
+        if (i % 2 == 0) continue;
+        putchar((char)((int)'0' + i));
+    } while (i < 7);
+    putchar('\n');  // 1357
+
+    // break
+    i = 0;
+    do {
+        int k = i;
+        if (k == 3) break;
+        i = i + 1;
+    } while (1);
+    print_int(i);  // 3
+    return;
+}
@@ -0,0 +1,73 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+int find(int target) {
+    int i = 0;
+    while (i < 10) {
+        int sq = i * i;
+        if (sq == target) {
+            int dummy = 0;
+            goto found;
+        }
+        i = i + 1;
+    }
+    return -1;
+found:
+    return i;
+}
+
+void main(void) {
+    // 後方へのgotoによるループ
+    int n = 0;
+again:
+    {
+        int t = n * 2;
+        n = n + 1;
+        if (n < 4) goto again;
+        print_int(t);  // 6
+    }
+
+    print_int(find(49));  // 7
+    print_int(find(50));  // -1
+
+    // 多重ループからの脱出
+    int i = 0;
+    int j = 0;
+    for (i = 0; i < 5; i = i + 1) {
+        int a = i;
+        for (j = 0; j < 5; j = j + 1) {
+            int b = j;
+            if (a * b == 6) goto out;
+        }
+    }
+out:
+    print_int(i * 10 + j);  // 23
+
+    // 宣言より前のラベルへ入るgoto
+    goto inner;
+    putchar('x');
+    {
+    inner:
+        putchar('y');
+        int k = 5;
+        print_int(k);
+    }
+    return;
+}
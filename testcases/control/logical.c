@@ -0,0 +1,55 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+int touch(int v) {
+    putchar('t');
+    return v;
+}
+
+void main(void) {
+    print_int(1 && 1);  // 1
+    print_int(1 && 0);  // 0
+    print_int(0 || 0);  // 0
+    print_int(5 || 0);  // 1
+    print_int(3 && 7);  // 1
+
+    // 右辺は必要な時だけ評価される
+    print_int(0 && touch(1));  // 0
+    print_int(1 || touch(1));  // 1
+    print_int(1 && touch(0));  // t0
+    print_int(0 || touch(2));  // t1
+
+    // ポインタのガード
+    int x = 42;
+    int *p = (int *)0;
+    if (p != (int *)0 && *p == 42) putchar('a');
+    p = &x;
+    if (p != (int *)0 && *p == 42) putchar('b');
+    putchar('\n');
+
+    int i = 0;
+    int n = 0;
+    while (i < 10 && n < 3) {
+        if (i % 2 == 0 || i == 7) n = n + 1;
+        i = i + 1;
+    }
+    print_int(i * 10 + n);  // 53
+    return;
+}
@@ -0,0 +1,94 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+int classify(int x) {
+    switch (x) {
+        case 0:
+            return 10;
+        case 1:
+        case 2:
+            return 20;
+        default:
+            return 30;
+    }
+    return 0;
+}
+
+void main(void) {
+    print_int(classify(0));  // 10
+    print_int(classify(2));  // 20
+    print_int(classify(7));  // 30
+
+    // fall-through
+    int n = 0;
+    switch (1) {
+        case 0:
+            n = n + 1;
+        case 1:
+            n = n + 10;
+        case 2:
+            n = n + 100;
+            break;
+        case 3:
+            n = n + 1000;
+    }
+    print_int(n);  // 110
+
+    // defaultが途中にある場合
+    int i = 0;
+    while (i < 4) {
+        switch (i) {
+            case 0:
+                putchar('a');
+                break;
+            default:
+                putchar('d');
+            case 2: {
+                int t = i * 2;
+                print_int(t);
+                break;
+            }
+        }
+        i = i + 1;
+    }
+
+    // ループ内のcontinue
+    int sum = 0;
+    for (i = 0; i < 6; i = i + 1) {
+        int k = i;
+        switch (k % 3) {
+            case 0:
+                continue;
+            case 1:
+                sum = sum + k;
+                break;
+        }
+        sum = sum + 100;
+    }
+    print_int(sum);  // 1+4 + 400 = 405
+
+    // 一致するcaseが無い場合
+    switch (9) {
+        case 1:
+            putchar('x');
+    }
+    putchar('\n');
+    return;
+}
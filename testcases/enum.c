@@ -0,0 +1,74 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+enum Color { RED, GREEN = 5, BLUE };
+enum Op { ADD = 1, SUB = ADD + 1, MUL = SUB * 2, LAST };
+enum Sign { NEGATIVE = -1, NEUTRAL, POSITIVE };
+
+char name(enum Color c) {
+    switch (c) {
+        case RED:
+            return 'r';
+        case GREEN:
+            return 'g';
+        case BLUE:
+            return 'b';
+    }
+    return '?';
+}
+
+int apply(enum Op op, int a, int b) {
+    if (op == ADD) return a + b;
+    if (op == SUB) return a - b;
+    return a * b;
+}
+
+void main(void) {
+    print_int(RED);    // 0
+    print_int(GREEN);  // 5
+    print_int(BLUE);   // 6
+    print_int(LAST);   // 5
+
+    enum Color c = BLUE;
+    putchar(name(c));
+    putchar(name(RED));
+    putchar(name(5));
+    putchar('\n');
+
+    // intとの相互変換
+    int n = c + 1;
+    print_int(n);  // 7
+    c = 0;
+    putchar(name(c));
+    putchar('\n');
+    print_int(c < GREEN);  // 1
+
+    print_int(apply(MUL, 6, 7));  // 42
+    print_int(apply(SUB, 6, 7));  // -1
+
+    // 配列長
+    int table[BLUE + 1];
+    table[BLUE] = 9;
+    print_int(table[BLUE]);  // 9
+
+    print_int(NEGATIVE);  // -1
+    print_int(POSITIVE);  // 1
+    return;
+}
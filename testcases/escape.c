@@ -0,0 +1,33 @@
+#include <print.h>
+
+void show(int x) {
+    print_int(x);
+    putchar('\n');
+    return;
+}
+
+void main(void) {
+    // 文字列の中の // や /* */ はコメントではない
+    char url[] = "http://x/*y*/\n\0";
+    printf((char (*)[0]) & url);
+
+    /* 複数行の
+       コメント */
+    show((int)'\101'); // 65
+    show((int)'\x41'); // 65
+    show((int)'\a');   // 7
+    show((int)'\b');   // 8
+    show((int)'\f');   // 12
+    show((int)'\v');   // 11
+    show((int)'\e');   // 27
+    show((int)'\0');   // 0
+    show((int)'\\');   // 92
+    show((int)'\'');   // 39
+
+    char s[] = "\x41\102\7\"";
+    show((int)s[0]); // 65
+    show((int)s[1]); // 66
+    show((int)s[2]); // 7
+    show((int)s[3]); // 34
+    return;
+}
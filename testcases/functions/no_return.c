@@ -0,0 +1,13 @@
+void putchar(char);
+
+// return を書かずに終わる関数
+void hello(void) {
+    putchar('h');
+    putchar('i');
+}
+
+// main も呼び出しで終わる
+void main(void) {
+    hello();
+    putchar('\n');
+}
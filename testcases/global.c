@@ -0,0 +1,101 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+struct Config {
+    int width;
+    int height;
+    char mark;
+};
+
+int counter;
+int limit = 2 * 3 + 1;
+int table[4] = {10, 20, 30};
+char title[] = "board\0";
+struct Config config = {3, 2, '#'};
+char board[2][3];
+
+void tick(void) {
+    counter = counter + 1;
+    return;
+}
+
+void fill(void) {
+    int y = 0;
+    int x = 0;
+    for (y = 0; y < config.height; y = y + 1) {
+        for (x = 0; x < config.width; x = x + 1) {
+            board[y][x] = config.mark;
+        }
+    }
+    board[1][1] = '.';
+    return;
+}
+
+void show(void) {
+    int y = 0;
+    int x = 0;
+    for (y = 0; y < config.height; y = y + 1) {
+        for (x = 0; x < config.width; x = x + 1) {
+            putchar(board[y][x]);
+        }
+        putchar('\n');
+    }
+    return;
+}
+
+int sum_table(void) {
+    int i = 0;
+    int s = 0;
+    for (i = 0; i < 4; i = i + 1) {
+        s = s + table[i];
+    }
+    return s;
+}
+
+void main(void) {
+    print_int(counter);  // 0
+    tick();
+    tick();
+    tick();
+    print_int(counter);  // 3
+    print_int(limit);    // 7
+
+    print_int(sum_table());  // 60
+    table[3] = 40;
+    print_int(sum_table());  // 100
+
+    int i = 0;
+    for (i = 0; title[i] != '\0'; i = i + 1) {
+        putchar(title[i]);
+    }
+    putchar('\n');
+
+    fill();
+    show();
+
+    // ポインタ経由でもアクセスできる
+    int *p = &counter;
+    *p = 42;
+    print_int(counter);  // 42
+    struct Config *c = &config;
+    c->width = 2;
+    show();
+    return;
+}
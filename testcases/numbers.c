@@ -0,0 +1,24 @@
+#include <print.h>
+void print_double(double);
+
+void show(int x) {
+    print_int(x);
+    putchar('\n');
+    return;
+}
+
+void main(void) {
+    show(0xFF);         // 255
+    show(0x7ff);        // 2047
+    show(0o17);         // 15
+    show(017);          // 15
+    show(0b1010);       // 10
+    show(0);            // 0
+    show(0xFFFF);       // -1
+    show(0x00ff & 0x0f0); // 240
+
+    print_double(1.5e1), putchar('\n'); // 15.0
+    print_double(25e-1), putchar('\n'); // 2.5
+    print_double(.5f), putchar('\n');   // 0.5
+    return;
+}
@@ -0,0 +1,56 @@
+#include <print.h>
+#include "macros.h"
+#include "macros.h"
+
+#define SIZE 4
+#define TWICE SIZE * 2
+#define CAT(a, b) a##b
+#define STR(x) #x
+#define SUM(...) sum3(__VA_ARGS__)
+#define LONG_MACRO(a, b) \
+    ((a) + \
+     (b))
+
+void show(int x) {
+    print_int(x);
+    putchar('\n');
+    return;
+}
+
+int sum3(int a, int b, int c) {
+    return a + b + c;
+}
+
+#if SIZE > 8
+#error SIZE is too large
+#elif defined(SQUARE) && !defined UNDEFINED
+int mode = 1;
+#else
+int mode = 2;
+#endif
+
+#ifndef SIZE
+int missing;
+#endif
+
+void main(void) {
+    int CAT(val, ue) = SQUARE(SIZE);
+    show(value); // 16
+    show(TWICE); // 8
+    show(MAX(3, SQUARE(2))); // 4
+    show(SUM(1, 2, 3)); // 6
+    show(LONG_MACRO(40, 2)); // 42
+    show(mode); // 1
+    show(__LINE__); // 44
+
+    char s[] = STR(hi);
+    putchar(s[0]), putchar(s[1]), putchar('\n'); // hi
+
+#undef SIZE
+#ifdef SIZE
+    show(0);
+#else
+    show(-1); // -1
+#endif
+    return;
+}
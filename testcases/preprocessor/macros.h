@@ -0,0 +1,4 @@
+#pragma once
+
+#define SQUARE(x) ((x) * (x))
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
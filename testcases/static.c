@@ -0,0 +1,72 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+static int shared = 100;
+
+int next_id(void) {
+    static int id = 10;
+    id = id + 1;
+    return id;
+}
+
+int other(void) {
+    // 別の関数の同名static変数とは別物
+    static int id;
+    id = id + 5;
+    return id;
+}
+
+// メモ化したフィボナッチ
+static int fib(int n) {
+    static int memo[20];
+    if (n < 2) return n;
+    if (memo[n] != 0) return memo[n];
+    int r = fib(n - 1) + fib(n - 2);
+    memo[n] = r;
+    return r;
+}
+
+int depth(int n) {
+    static int calls = 0;
+    calls = calls + 1;
+    if (n > 0) depth(n - 1);
+    return calls;
+}
+
+void main(void) {
+    print_int(next_id());  // 11
+    print_int(next_id());  // 12
+    print_int(other());    // 5
+    print_int(other());    // 10
+    print_int(next_id());  // 13
+
+    print_int(fib(19));  // 4181
+    print_int(depth(4));  // 5
+    print_int(depth(0));  // 6
+
+    {
+        static char mark = 'm';
+        putchar(mark);
+        putchar('\n');
+    }
+    shared = shared + 1;
+    print_int(shared);  // 101
+    return;
+}
@@ -0,0 +1,75 @@
+void putchar(char);
+void print_int_core(int x) {
+    if (x < 0) {
+        putchar('-');
+        x = -x;
+    }
+
+    if (x >= 10) {
+        print_int_core(x / 10);
+    }
+    putchar((char)((int)'0' + (x % 10)));
+    return;
+}
+
+void print_int(int a) {
+    print_int_core(a);
+    putchar('\n');
+    return;
+}
+
+union Value {
+    int i;
+    char c;
+    int pair[2];
+};
+
+struct Tagged {
+    int tag;
+    union Value v;
+};
+
+int eval(struct Tagged t) {
+    if (t.tag == 0) return t.v.i;
+    if (t.tag == 1) return (int)t.v.c;
+    return t.v.pair[0] + t.v.pair[1];
+}
+
+void main(void) {
+    union Value u;
+    u.i = 65;
+    print_int(u.i);  // 65
+    putchar(u.c);    // A
+    putchar('\n');
+
+    u.pair[0] = 3;
+    u.pair[1] = 4;
+    print_int(u.i);  // 3
+
+    union Value w = {7};
+    print_int(w.i);        // 7
+    print_int(w.pair[1]);  // 0
+
+    union Value copy = w;
+    copy.i = copy.i + 1;
+    print_int(copy.i);  // 8
+    print_int(w.i);     // 7
+
+    union Value *p = &u;
+    print_int((*p).pair[1]);  // 4
+    p->i = 9;
+    print_int(u.i);  // 9
+
+    struct Tagged a = {0, {12}};
+    struct Tagged b;
+    b.tag = 1;
+    b.v.c = 'z';
+    struct Tagged c;
+    c.tag = 2;
+    c.v.pair[0] = 20;
+    c.v.pair[1] = 22;
+    print_int(eval(a));  // 12
+    print_int(eval(b));  // 122
+    print_int(eval(c));  // 42
+    return;
+}
@@ -0,0 +1,126 @@
+// 失敗の種類ごとに --help に書いた終了コードで終わることを確かめる
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// 標準入力から source をコンパイルし (終了コード, 標準エラー出力) を返す
+fn run(source: &str, args: &[&str]) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_Cynops"))
+        .arg("-")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("起動できない");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().expect("シグナルで終了した"),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+// 同梱の prelude の int.c の後ろに broken を足したディレクトリ
+fn broken_prelude(name: &str, broken: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(&dir).unwrap();
+    let bundled = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/codegen/insert_c");
+    for file in ["base.c", "int.c", "float.c"] {
+        fs::copy(bundled.join(file), dir.join(file)).unwrap();
+    }
+    let mut int = fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join("int.c"))
+        .unwrap();
+    write!(int, "\n{}", broken).unwrap();
+    dir
+}
+
+#[test]
+fn exit_codes() {
+    let program = "void main(void) {\n    int x = 1 + 2;\n}\n";
+
+    let (code, _) = run("void main(void) {\n    int a = 1 @ 2;\n}\n", &[]);
+    assert_eq!(code, 1, "構文エラー");
+
+    let (code, _) = run("void main(void) {\n    x = 1;\n}\n", &[]);
+    assert_eq!(code, 2, "型エラー");
+    // 型エラーがあっても型付きの AST は表示する
+    let (code, stderr) = run("void main(void) {\n    x = 1;\n}\n", &["typed"]);
+    assert_eq!(code, 2, "{}", stderr);
+
+    let (code, _) = run("void main(void) {\n    break;\n}\n", &[]);
+    assert_eq!(code, 2, "ループの外の break");
+
+    // 壊れた prelude はユーザーの誤りではない
+    let prelude = broken_prelude(
+        "undefined_prelude",
+        "int broken(void) {\n    return undefined_name;\n}\n",
+    );
+    let option = format!("--prelude={}", prelude.display());
+    let (code, stderr) = run(program, &[&option]);
+    assert_eq!(code, 70, "内部エラー");
+    assert!(
+        stderr.starts_with("internal error: Failed to compile the runtime prelude"),
+        "{}",
+        stderr
+    );
+
+    // コード生成の panic もバックトレースを出さずに内部エラーにする
+    let prelude = broken_prelude(
+        "panicking_prelude",
+        "int broken(void) {\n    int a[];\n    return 0;\n}\n",
+    );
+    let option = format!("--prelude={}", prelude.display());
+    let (code, stderr) = run(program, &[&option]);
+    assert_eq!(code, 70, "内部エラー");
+    assert!(
+        stderr.starts_with("error: internal compiler error: "),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("stack backtrace"), "{}", stderr);
+}
+
+#[test]
+fn output_with_named_emit() {
+    // -o の行き先がほかの --emit に取られていなければ BF を書き出す
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("output_with_named_emit");
+    fs::create_dir_all(&dir).unwrap();
+    let (bf, typed) = (dir.join("out.bf"), dir.join("typed.txt"));
+    let _ = fs::remove_file(&bf);
+    let _ = fs::remove_file(&typed);
+    let output = format!("-o{}", bf.display());
+    let emit = format!("--emit=typed={}", typed.display());
+    let (code, stderr) = run(
+        "#include <cynops.h>\nvoid main(void) {\n    putchar('A');\n}\n",
+        &[&output, &emit],
+    );
+    assert_eq!(code, 0, "{}", stderr);
+    let code = fs::read_to_string(&bf).unwrap();
+    assert!(!code.is_empty() && code.trim_end().chars().all(|c| "+-<>[].,".contains(c)));
+    assert!(fs::metadata(&typed).unwrap().len() > 0);
+}
+
+#[test]
+fn statements_in_stack_dump() {
+    // 元の文は文を添えるダンプを頼んだときだけ作る
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("statements_in_stack_dump");
+    fs::create_dir_all(&dir).unwrap();
+    let stack = dir.join("stack.txt");
+    let emit = format!("--emit=stack={},bf", stack.display());
+    let (code, stderr) = run(
+        "#include <cynops.h>\nvoid main(void) {\n    putchar('A');\n}\n",
+        &[&emit],
+    );
+    assert_eq!(code, 0, "{}", stderr);
+    let dump = fs::read_to_string(&stack).unwrap();
+    assert!(dump.contains("\n  # putchar('A')\n"), "{}", dump);
+}
@@ -0,0 +1,146 @@
+// 不正な入力で compile が panic せず，位置付きの診断を返すことを確かめる
+use cynops::{Options, Stage, compile};
+
+// (段階, 行, 列, メッセージ) 列は1始まり
+fn diagnose(source: &str) -> Vec<(Stage, u32, u16, String)> {
+    let Err(diagnostics) = compile(source, &Options::default()) else {
+        panic!("エラーにならなかった:\n{}", source);
+    };
+    diagnostics
+        .errors
+        .iter()
+        .map(|e| {
+            let (_, span) = e.location.clone().expect("位置がない");
+            (e.stage, span.line.get(), span.column, e.message.clone())
+        })
+        .collect()
+}
+
+#[test]
+fn undefined_identifier() {
+    let errors = diagnose("void main(void) {\n    int y;\n    x = 1;\n    y = x;\n}\n");
+    assert_eq!(
+        errors,
+        [
+            (Stage::Type, 3, 5, "Undefined variable: x".to_string()),
+            (Stage::Type, 4, 9, "Undefined variable: x".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn lexer_errors() {
+    let errors = diagnose("void main(void) {\n    int a = 1 @ 2;\n    a = `;\n}\n");
+    assert_eq!(
+        errors,
+        [
+            (Stage::Parse, 2, 15, "unexpected character '@'".to_string()),
+            (Stage::Parse, 3, 9, "unexpected character '`'".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn bad_literals() {
+    let errors = diagnose(
+        "void main(void) {\n    char c = 'ab';\n    char d = '\\x';\n    char *s = \"abc;\n}\n",
+    );
+    assert_eq!(
+        errors,
+        [
+            (
+                Stage::Parse,
+                2,
+                14,
+                "character literal must contain exactly one character".to_string()
+            ),
+            (
+                Stage::Parse,
+                3,
+                14,
+                "\\x used with no following hex digits".to_string()
+            ),
+            (
+                Stage::Parse,
+                4,
+                15,
+                "missing terminating \" for string literal".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn bad_numbers() {
+    let errors = diagnose(
+        "void main(void) {\n    int x = 70000;\n    int y = 09;\n    y = 1 + 12abc;\n    y = 42u;\n}\n",
+    );
+    assert_eq!(
+        errors,
+        [
+            (
+                Stage::Parse,
+                2,
+                13,
+                "integer constant '70000' does not fit in 16 bits".to_string()
+            ),
+            (
+                Stage::Parse,
+                3,
+                13,
+                "invalid digit '9' in octal constant".to_string()
+            ),
+            (
+                Stage::Parse,
+                4,
+                13,
+                "invalid integer constant '12abc'".to_string()
+            ),
+            (
+                Stage::Parse,
+                5,
+                9,
+                "unsupported suffix 'u' on integer constant".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn non_constant_enumerator() {
+    let errors =
+        diagnose("int x;\nenum E { A, B = x + 1, C };\nvoid main(void) {\n    x = C;\n}\n");
+    assert_eq!(
+        errors,
+        [(
+            Stage::Type,
+            2,
+            19,
+            "Value of enumerator 'B' is not constant".to_string()
+        )]
+    );
+}
+
+#[test]
+fn jump_outside_loop() {
+    let errors = diagnose(
+        "void main(void) {\n    break;\n    switch (1) {\n    default:\n        continue;\n    }\n    while (1) {\n        switch (1) {\n        case 1:\n            continue;\n        }\n        break;\n    }\n}\n",
+    );
+    assert_eq!(
+        errors,
+        [
+            (
+                Stage::Type,
+                2,
+                5,
+                "break statement not within a loop or switch".to_string()
+            ),
+            (
+                Stage::Type,
+                5,
+                9,
+                "continue statement not within a loop".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,90 @@
+// testcases/ 以下の .c をすべて run モードで実行し，.out と比べる
+// .in があれば標準入力に流す（test.sh と同じ規則）
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+fn collect(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect(&path, out);
+        } else if path.extension().is_some_and(|e| e == "c") {
+            out.push(path);
+        }
+    }
+}
+
+fn run(source: &Path) -> Result<(), String> {
+    let expected = fs::read(source.with_extension("out"))
+        .map_err(|e| format!("missing {}: {}", source.with_extension("out").display(), e))?;
+    let input = fs::read(source.with_extension("in")).unwrap_or_default();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_Cynops"))
+        .arg(source)
+        .arg("run")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&input).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    if !output.status.success() {
+        return Err(format!(
+            "exited with {}\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    if output.stdout != expected {
+        return Err(format!(
+            "expected:\n{}\nactual:\n{}",
+            String::from_utf8_lossy(&expected),
+            String::from_utf8_lossy(&output.stdout)
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn testcases() {
+    let mut sources = Vec::new();
+    collect(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testcases")
+            .as_path(),
+        &mut sources,
+    );
+    sources.sort();
+    assert!(!sources.is_empty(), "テストケースが見つからない");
+
+    // 1件ずつ別プロセスなので並列に走らせる
+    let failures: Vec<String> = std::thread::scope(|scope| {
+        let handles: Vec<_> = sources
+            .iter()
+            .map(|source| scope.spawn(move || run(source)))
+            .collect();
+        sources
+            .iter()
+            .zip(handles)
+            .filter_map(|(source, handle)| {
+                handle
+                    .join()
+                    .unwrap()
+                    .err()
+                    .map(|e| format!("===== {} =====\n{}", source.display(), e))
+            })
+            .collect()
+    });
+
+    assert!(
+        failures.is_empty(),
+        "{} / {} testcases failed\n{}",
+        failures.len(),
+        sources.len(),
+        failures.join("\n")
+    );
+}